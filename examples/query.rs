@@ -1,43 +1,45 @@
-//! Let's see if we can chain query method calls...
+//! Let's see if we can page through a search...
 
-use backdrop::{
-    database::DATABASE,
-    models::media::{
-        metadata::{Orientation, Resolution},
-        Media,
-    },
+use backdrop::search::{
+    execute::Search,
+    parse::parse,
+    sort::{SortOrder, SortType},
 };
 use camino::Utf8PathBuf;
 
 #[tokio::main]
 async fn main() {
-    let mut conn = DATABASE.acquire().await.expect("db conn");
-    let all = sqlx::query_as::<_, Media>("SELECT * FROM info")
-        .fetch_all(&mut *conn)
-        .await
-        .unwrap();
-
     // SEARCH: all media where:
     // - orientation is portrait,
     // - filename contains a number, and
     // - resolution is >1080p
-    let executed_search = all
-        .iter()
-        .filter(|m| {
-            matches!(
-                Orientation::from(Resolution::new(m.width_px, m.height_px)),
-                Orientation::Portrait
-            )
-        })
-        .filter(|m| {
-            Utf8PathBuf::from(&m.path)
-                .file_name()
-                .unwrap_or_default()
-                .to_string()
-                .contains(('0'..='9').collect::<Vec<_>>().as_slice())
-        })
-        .filter(|m| m.width_px > 1920 && m.height_px > 1080)
-        .collect::<Vec<_>>();
+    let exprs = parse("orientation:portrait").expect("query should parse");
+    let search = Search::new(exprs, (SortType::DateCreated, SortOrder::Descending));
+    println!("portrait media: {}", search.count().await.unwrap());
+
+    let mut cursor = None;
+    loop {
+        let page = search.page(cursor.as_ref(), 50).await.unwrap();
+
+        let executed_search = page
+            .results
+            .media()
+            .iter()
+            .filter(|m| {
+                Utf8PathBuf::from(&m.path)
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string()
+                    .contains(('0'..='9').collect::<Vec<_>>().as_slice())
+            })
+            .filter(|m| m.width_px > 1920 && m.height_px > 1080)
+            .collect::<Vec<_>>();
+
+        println!("found results: {:#?}", executed_search);
 
-    println!("found results: {:#?}", executed_search);
+        match page.next {
+            Some(next) => cursor = Some(next),
+            None => break,
+        }
+    }
 }
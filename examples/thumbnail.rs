@@ -2,7 +2,7 @@
 
 use backdrop::{
     config::{BugReportInfo, Config},
-    database::DATABASE,
+    database::{DATABASE, MEDIA_COLUMNS},
     models::media::Media,
 };
 use camino::Utf8PathBuf;
@@ -32,7 +32,7 @@ async fn main() {
     .await;
 
     let mut conn = DATABASE.acquire().await.expect("db connection");
-    let media = sqlx::query_as::<_, Media>("SELECT * FROM info LIMIT 1")
+    let media = sqlx::query_as::<_, Media>(&format!("SELECT {MEDIA_COLUMNS} FROM info LIMIT 1"))
         .fetch_one(&mut *conn)
         .await
         .unwrap();
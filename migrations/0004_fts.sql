@@ -0,0 +1,54 @@
+-- info_fts: full-text search over media file names, folders, and metadata
+--
+-- this is kept in sync with `info` by the triggers below. don't write to it
+-- directly!
+CREATE VIRTUAL TABLE IF NOT EXISTS info_fts USING fts5(
+    -- the `info.id` of the media
+    media_id UNINDEXED,
+    -- file name, like `IMG_2034.jpg`
+    name,
+    -- the folders it's in, like `/sdcard/DCIM/Camera/`
+    folders,
+    -- string values from `other_metadata` (camera model, software, etc.)
+    metadata,
+    -- speeds up short prefix searches (`"ca"*`)
+    prefix = '2 3'
+);
+
+CREATE TRIGGER IF NOT EXISTS info_fts_insert AFTER INSERT ON info BEGIN
+    INSERT INTO info_fts (media_id, name, folders, metadata)
+    VALUES (
+        new.id,
+        substr(new.path, length(rtrim(new.path, replace(new.path, '/', ''))) + 1),
+        rtrim(new.path, replace(new.path, '/', '')),
+        (SELECT group_concat(json_extract(value, '$.value'), ' ') FROM json_each(new.other_metadata))
+    );
+END;
+
+CREATE TRIGGER IF NOT EXISTS info_fts_delete AFTER DELETE ON info BEGIN
+    DELETE FROM info_fts WHERE media_id = old.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS info_fts_update AFTER UPDATE ON info BEGIN
+    DELETE FROM info_fts WHERE media_id = old.id;
+    INSERT INTO info_fts (media_id, name, folders, metadata)
+    VALUES (
+        new.id,
+        substr(new.path, length(rtrim(new.path, replace(new.path, '/', ''))) + 1),
+        rtrim(new.path, replace(new.path, '/', '')),
+        (SELECT group_concat(json_extract(value, '$.value'), ' ') FROM json_each(new.other_metadata))
+    );
+END;
+
+-- index everything that's already cached
+INSERT INTO info_fts (media_id, name, folders, metadata)
+SELECT
+    id,
+    substr(path, length(rtrim(path, replace(path, '/', ''))) + 1),
+    rtrim(path, replace(path, '/', '')),
+    (SELECT group_concat(json_extract(value, '$.value'), ' ') FROM json_each(info.other_metadata))
+FROM info
+WHERE id NOT IN (SELECT media_id FROM info_fts);
+
+-- file names matter most when ranking, then folders, then metadata
+INSERT INTO info_fts (info_fts, rank) VALUES ('rank', 'bm25(0.0, 10.0, 5.0, 1.0)');
@@ -0,0 +1,10 @@
+-- rating: favorites and star ratings that users give their media
+--
+-- these are kept apart from `info`, so rescanning media doesn't reset them
+CREATE TABLE IF NOT EXISTS rating(
+    media_id TEXT NOT NULL PRIMARY KEY,
+    -- 1 when the media is a favorite
+    favorite INTEGER NOT NULL DEFAULT 0,
+    -- from 0 (unrated) to 5
+    stars INTEGER NOT NULL DEFAULT 0 CHECK (stars BETWEEN 0 AND 5)
+);
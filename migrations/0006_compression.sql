@@ -0,0 +1,5 @@
+-- info.compression: whether media is compressed losslessly
+--
+-- either `lossless` or `lossy`. this is `NULL` until the media is rescanned,
+-- or when we can't tell.
+ALTER TABLE info ADD COLUMN compression TEXT;
@@ -0,0 +1,33 @@
+-- tag_sections: groups of tags that shouldn't be mixed
+CREATE TABLE IF NOT EXISTS tag_sections(
+    id TEXT NOT NULL PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE COLLATE NOCASE
+);
+
+-- tags: every tag that media can have
+--
+-- media still carry copies of their tags in `info.tags`. the catalog is the
+-- source of truth, so edits here are copied over to them
+CREATE TABLE IF NOT EXISTS tags(
+    -- the tag's uuid. this never changes, even when the tag is renamed
+    id TEXT NOT NULL PRIMARY KEY,
+    name TEXT NOT NULL COLLATE NOCASE,
+    -- the section it's in, if any
+    section_id TEXT REFERENCES tag_sections(id)
+);
+
+-- tag names are unique within their section
+CREATE UNIQUE INDEX IF NOT EXISTS tags_name_index ON tags(name, ifnull(section_id, ''));
+
+-- add any tags (and sections) that media already have
+INSERT OR IGNORE INTO tag_sections (id, name)
+SELECT randomblob(16), json_extract(t.value, '$.tag_section.name')
+FROM info, json_each(info.tags) AS t
+WHERE json_extract(t.value, '$.tag_section.name') IS NOT NULL;
+
+INSERT OR IGNORE INTO tags (id, name, section_id)
+SELECT
+    json_extract(t.value, '$.uuid'),
+    json_extract(t.value, '$.name'),
+    (SELECT id FROM tag_sections WHERE name = json_extract(t.value, '$.tag_section.name'))
+FROM info, json_each(info.tags) AS t;
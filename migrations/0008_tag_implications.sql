@@ -0,0 +1,20 @@
+-- tag_implications: tags that imply others, like "christmas" implying "holiday"
+--
+-- media with a tag also have every tag it implies (and every tag *those*
+-- imply, and so on). cycles are rejected before they get here
+CREATE TABLE IF NOT EXISTS tag_implications(
+    tag_id TEXT NOT NULL REFERENCES tags(id),
+    implied_id TEXT NOT NULL REFERENCES tags(id),
+    PRIMARY KEY (tag_id, implied_id)
+);
+
+-- for walking the implications backwards (what implies "holiday"?)
+CREATE INDEX IF NOT EXISTS tag_implications_implied_index ON tag_implications(implied_id);
+
+-- add implications that media copies of tags already have
+INSERT OR IGNORE INTO tag_implications (tag_id, implied_id)
+SELECT json_extract(t.value, '$.uuid'), i.value
+FROM info, json_each(info.tags) AS t, json_each(t.value, '$.implies') AS i
+WHERE json_extract(t.value, '$.uuid') IN (SELECT id FROM tags)
+    AND i.value IN (SELECT id FROM tags)
+    AND i.value IS NOT json_extract(t.value, '$.uuid');
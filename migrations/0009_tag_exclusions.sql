@@ -0,0 +1,9 @@
+-- tag_exclusions: tags that a media file shouldn't have, even when another of
+-- its tags implies them
+--
+-- like ratings, these are kept apart from `info` so rescans don't reset them
+CREATE TABLE IF NOT EXISTS tag_exclusions(
+    media_id TEXT NOT NULL,
+    tag_id TEXT NOT NULL REFERENCES tags(id),
+    PRIMARY KEY (media_id, tag_id)
+);
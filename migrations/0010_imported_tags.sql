@@ -0,0 +1,9 @@
+-- imported_tags: tags that media got from keywords in their own files
+--
+-- when a file is imported again, these are swapped for whatever keywords it
+-- has now. other tags are left alone
+CREATE TABLE IF NOT EXISTS imported_tags(
+    media_id TEXT NOT NULL,
+    tag_id TEXT NOT NULL REFERENCES tags(id),
+    PRIMARY KEY (media_id, tag_id)
+);
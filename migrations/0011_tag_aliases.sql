@@ -0,0 +1,14 @@
+-- tag_aliases: old names of tags that were merged into others
+--
+-- searching or importing an alias finds the tag it points to. real tag names
+-- win over aliases
+CREATE TABLE IF NOT EXISTS tag_aliases(
+    name TEXT NOT NULL COLLATE NOCASE,
+    -- the section the old tag was in, if any
+    section_id TEXT REFERENCES tag_sections(id),
+    tag_id TEXT NOT NULL REFERENCES tags(id)
+);
+
+-- like tag names, aliases are unique within their section
+CREATE UNIQUE INDEX IF NOT EXISTS tag_aliases_name_index ON tag_aliases(name, ifnull(section_id, ''));
+CREATE INDEX IF NOT EXISTS tag_aliases_tag_index ON tag_aliases(tag_id);
@@ -0,0 +1,22 @@
+-- tag_edits: bulk tag changes, kept around so they can be undone
+CREATE TABLE IF NOT EXISTS tag_edits(
+    id TEXT NOT NULL PRIMARY KEY,
+    -- whether the tags were added to media, or removed from them
+    kind TEXT NOT NULL CHECK (kind IN ('add', 'remove')),
+    made_at TEXT NOT NULL,
+    -- 1 after the edit is undone. it can't be undone twice
+    undone INTEGER NOT NULL DEFAULT 0
+);
+
+-- tag_edit_changes: each media file and tag that an edit actually changed
+--
+-- media that already had (or didn't have) a tag aren't listed, so undoing an
+-- edit leaves them alone
+CREATE TABLE IF NOT EXISTS tag_edit_changes(
+    edit_id TEXT NOT NULL REFERENCES tag_edits(id),
+    media_id TEXT NOT NULL,
+    tag_id TEXT NOT NULL REFERENCES tags(id),
+    PRIMARY KEY (edit_id, media_id, tag_id)
+);
+
+CREATE INDEX IF NOT EXISTS tag_edit_changes_tag_index ON tag_edit_changes(tag_id);
@@ -0,0 +1,49 @@
+-- media_tags: the tags that each media file was given
+--
+-- media used to keep copies of their tags in `info.tags`, which edits to the
+-- catalog (from `0007_tags`) were copied over to. now, they only point at the
+-- catalog, so renaming a tag doesn't touch any media
+CREATE TABLE IF NOT EXISTS media_tags(
+    media_id TEXT NOT NULL,
+    tag_id TEXT NOT NULL REFERENCES tags(id),
+    -- where the tag came from: 'user' when it was given in the app, or 'file'
+    -- when it was imported from the media file's own keywords
+    source TEXT NOT NULL DEFAULT 'user' CHECK (source IN ('user', 'file')),
+    PRIMARY KEY (media_id, tag_id)
+);
+
+-- the primary key finds a media file's tags. this finds a tag's media
+CREATE INDEX IF NOT EXISTS media_tags_tag_index ON media_tags(tag_id);
+
+-- add any tags (and sections) that media have, but the catalog doesn't
+INSERT OR IGNORE INTO tag_sections (id, name)
+SELECT randomblob(16), json_extract(t.value, '$.tag_section.name')
+FROM info, json_each(info.tags) AS t
+WHERE json_extract(t.value, '$.tag_section.name') IS NOT NULL;
+
+INSERT OR IGNORE INTO tags (id, name, section_id)
+SELECT
+    json_extract(t.value, '$.uuid'),
+    json_extract(t.value, '$.name'),
+    (SELECT id FROM tag_sections WHERE name = json_extract(t.value, '$.tag_section.name'))
+FROM info, json_each(info.tags) AS t;
+
+-- then move each media file's tags over, keeping their order
+INSERT OR IGNORE INTO media_tags (media_id, tag_id, source)
+SELECT
+    info.id,
+    json_extract(t.value, '$.uuid'),
+    CASE WHEN EXISTS (
+        SELECT 1 FROM imported_tags AS i
+        WHERE i.media_id = info.id AND i.tag_id = json_extract(t.value, '$.uuid')
+    ) THEN 'file' ELSE 'user' END
+FROM info, json_each(info.tags) AS t
+ORDER BY info.rowid, t.key;
+
+-- `source` replaces `imported_tags`
+DROP TABLE imported_tags;
+ALTER TABLE info DROP COLUMN tags;
+
+-- tags that an edit removed keep their source, so undoing it puts them back
+-- the same way
+ALTER TABLE tag_edit_changes ADD COLUMN source TEXT NOT NULL DEFAULT 'user';
@@ -0,0 +1,10 @@
+-- rating_media_delete: removes a media file's rating when it's deleted
+--
+-- `rating` doesn't point at `info`, so nothing else cleans it up. rescans
+-- update media in place, so they don't set this off
+CREATE TRIGGER IF NOT EXISTS rating_media_delete AFTER DELETE ON info BEGIN
+    DELETE FROM rating WHERE media_id = old.id;
+END;
+
+-- and drop any ratings left over from media that are already gone
+DELETE FROM rating WHERE media_id NOT IN (SELECT id FROM info);
@@ -3,7 +3,10 @@ use std::sync::OnceLock;
 use camino::Utf8PathBuf;
 use tokio::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
 
-use crate::error::{bug_msg, ConfigError};
+use crate::{
+    error::{bug_msg, ConfigError},
+    models::tags::TagSection,
+};
 
 pub type SharedConfig = RwLock<Config>;
 
@@ -24,6 +27,11 @@ pub struct Config {
 
     /// Information for automatically reporting bugs.
     pub bug_report_info: BugReportInfo,
+
+    /// The section that tags are made in when they're imported from keywords
+    /// inside media files. When `None`, they aren't put in one.
+    #[serde(default = "default_keyword_section")]
+    pub keyword_section: Option<TagSection>,
 }
 
 impl Config {
@@ -38,6 +46,7 @@ impl Config {
             data_dir,
             cache_dir,
             bug_report_info,
+            keyword_section: default_keyword_section(),
         }
     }
 
@@ -81,6 +90,7 @@ impl Config {
                 data_dir,
                 cache_dir,
                 bug_report_info,
+                keyword_section: default_keyword_section(),
             });
 
             CONFIG
@@ -115,6 +125,14 @@ impl Config {
     }
 }
 
+/// Imported keywords go in a "keywords" section unless the user says
+/// otherwise.
+fn default_keyword_section() -> Option<TagSection> {
+    Some(TagSection {
+        name: String::from("keywords"),
+    })
+}
+
 /// Some info to help with bug reporting.
 ///
 /// I really want this for telling users where to report bugs.
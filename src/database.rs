@@ -17,7 +17,51 @@ pub const RAVES_DB_FILE: &str = "raves.sqlite";
 
 pub const HASHES_TABLE: &str = "hashes";
 pub const INFO_TABLE: &str = "info";
+/// Full-text index over the [`INFO_TABLE`]. Triggers keep it up to date.
+pub const INFO_FTS_TABLE: &str = "info_fts";
 pub const THUMBNAILS_TABLE: &str = "thumbnail";
+/// Favorites and star ratings, by media `id`.
+pub const RATING_TABLE: &str = "rating";
+/// The tag catalog.
+pub const TAGS_TABLE: &str = "tags";
+pub const TAG_SECTIONS_TABLE: &str = "tag_sections";
+/// Which tags imply which others. There are never any cycles.
+pub const TAG_IMPLICATIONS_TABLE: &str = "tag_implications";
+/// Implied tags that a media file shouldn't have, by media `id`.
+pub const TAG_EXCLUSIONS_TABLE: &str = "tag_exclusions";
+/// The tags that each media file was given, by media `id`, in order.
+pub const MEDIA_TAGS_TABLE: &str = "media_tags";
+/// Old names of merged tags, which still find the tag they were merged into.
+pub const TAG_ALIASES_TABLE: &str = "tag_aliases";
+/// Bulk tag edits, which can be undone.
+pub const TAG_EDITS_TABLE: &str = "tag_edits";
+/// The media and tags that each bulk tag edit changed.
+pub const TAG_EDIT_CHANGES_TABLE: &str = "tag_edit_changes";
+
+/// Every column that a [`Media`](crate::models::media::Media) is read from:
+/// the [`INFO_TABLE`]'s own, then its tags from the [`MEDIA_TAGS_TABLE`], as
+/// JSON.
+///
+/// Always select these (instead of `*`) when reading media.
+pub const MEDIA_COLUMNS: &str = "info.*, (\
+    SELECT json_group_array(json(tag)) FROM (\
+        SELECT json_object(\
+            'name', t.name, \
+            'uuid', t.id, \
+            'tag_section', json(CASE WHEN s.name IS NULL THEN 'null' \
+                ELSE json_object('name', s.name) END), \
+            'implies', json((SELECT json_group_array(implied_id) FROM \
+                (SELECT implied_id FROM tag_implications WHERE tag_id = t.id ORDER BY rowid)))\
+        ) AS tag \
+        FROM media_tags AS mt JOIN tags AS t ON t.id = mt.tag_id \
+        LEFT JOIN tag_sections AS s ON s.id = t.section_id \
+        WHERE mt.media_id = info.id ORDER BY mt.rowid\
+    )\
+) AS tags";
+
+/// A collation that sorts text like a person would, so `IMG_2` comes before
+/// `IMG_10`. It's added to every connection.
+pub const NATURAL_COLLATION: &str = "natural_sort";
 
 /// A path to the folder containing the backend's database.
 ///
@@ -62,7 +106,8 @@ pub static DATABASE: LazyLock<Pool<Sqlite>> = LazyLock::new(|| {
                 )
             })
             .expect("database opts str")
-            .create_if_missing(true);
+            .create_if_missing(true)
+            .collation(NATURAL_COLLATION, crate::search::sort::natural_cmp);
 
     // connect to the pool
     let pool = sqlx::Pool::<Sqlite>::connect_lazy_with(options);
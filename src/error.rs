@@ -3,7 +3,7 @@ use camino::Utf8PathBuf;
 use core::error::Error;
 use pisserror::Error;
 
-use crate::config::Config;
+use crate::{config::Config, search::parse::Span};
 
 /// Stick this at the end of bug warnings/errors.
 ///
@@ -91,6 +91,69 @@ pub enum RavesError {
 
     #[error("FFmpeg didn't find a good video stream for this video file. Path: `{_0}`.")]
     FfmpegNoGoodVideoStreams(String),
+
+    //
+    // search
+    //
+    #[error("The search cursor has {got} sort keys, but the search sorts by {expected}. Was it from another search?")]
+    MismatchedCursor { expected: usize, got: usize },
+
+    //
+    // ratings
+    //
+    #[error("Media can have at most 5 stars, but got {stars}.")]
+    InvalidRating { stars: u8 },
+
+    //
+    // tags
+    //
+    #[error("No tag has the UUID `{id}`.")]
+    TagNotFound { id: String },
+
+    #[error("No tag section has the UUID `{id}`.")]
+    TagSectionNotFound { id: String },
+
+    #[error("Tags and tag sections need a name.")]
+    EmptyTagName,
+
+    #[error("A tag named `{name}` already exists in that section.")]
+    TagNameTaken { name: String },
+
+    #[error("A tag section named `{name}` already exists.")]
+    TagSectionNameTaken { name: String },
+
+    #[error("The tag section `{name}` still has {tags} tag(s). Delete or move them first.")]
+    TagSectionNotEmpty { name: String, tags: u64 },
+
+    #[error("Tags can't imply themselves, but this would make a cycle: {cycle}")]
+    TagImplicationCycle { cycle: String },
+
+    //
+    // tag edits
+    //
+    #[error("No tag edit has the UUID `{id}`.")]
+    TagEditNotFound { id: uuid::Uuid },
+
+    #[error("The tag edit `{id}` was already undone.")]
+    TagEditAlreadyUndone { id: uuid::Uuid },
+
+    //
+    // sidecars
+    //
+    #[error("Failed to read the existing XMP sidecar at `{path}`. err: {err}")]
+    SidecarReadFailed {
+        path: Utf8PathBuf,
+        err: std::io::Error,
+    },
+
+    #[error("The existing XMP sidecar at `{path}` isn't XMP we can read, so it was left alone.")]
+    SidecarUnreadable { path: Utf8PathBuf },
+
+    #[error("Failed to write the XMP sidecar at `{path}`. err: {err}")]
+    SidecarWriteFailed {
+        path: Utf8PathBuf,
+        err: std::io::Error,
+    },
 }
 
 #[derive(Debug, Error)]
@@ -161,3 +224,47 @@ pub enum HashError {
     #[error("Failed to read file at `{_0}`. err: {_1}")]
     FileReadFailure(Utf8PathBuf, std::io::Error),
 }
+
+/// An error that occurred while parsing a search query.
+///
+/// Each one carries the [`Span`] of the offending text, so it can be shown
+/// to the user.
+#[derive(Clone, Debug, PartialEq, Error)]
+pub enum QueryParseError {
+    #[error("Unknown search modifier `{modifier}` at {span}.")]
+    UnknownModifier { modifier: String, span: Span },
+
+    #[error("The `{modifier}` modifier needs a value. (at {span})")]
+    MissingValue { modifier: String, span: Span },
+
+    #[error("The `{modifier}` modifier can't use `{value}` at {span}. Expected {expected}.")]
+    InvalidValue {
+        modifier: String,
+        value: String,
+        expected: String,
+        span: Span,
+    },
+
+    #[error("The quote at {span} is never closed.")]
+    UnterminatedQuote { span: Span },
+
+    #[error("The parenthesis at {span} doesn't have a match.")]
+    UnmatchedParen { span: Span },
+
+    #[error("Expected a search term at {span}.")]
+    ExpectedTerm { span: Span },
+}
+
+impl QueryParseError {
+    /// The part of the query that caused this error.
+    pub fn span(&self) -> Span {
+        match self {
+            QueryParseError::UnknownModifier { span, .. }
+            | QueryParseError::MissingValue { span, .. }
+            | QueryParseError::InvalidValue { span, .. }
+            | QueryParseError::UnterminatedQuote { span }
+            | QueryParseError::UnmatchedParen { span }
+            | QueryParseError::ExpectedTerm { span } => *span,
+        }
+    }
+}
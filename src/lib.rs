@@ -27,10 +27,10 @@ Under active development.
         - [ ] Video
         - [ ] General (including Folder. i.e. `stat`)
     - [ ] Tagging
-        - [ ] Can access existing tags from media (requires metadata)
+        - [x] Can access existing tags from media (requires metadata)
         - [ ] Store in database using own format
             - Issueify but: entire database of serialized `Tag`s and `Media`.
-        - [ ] Export database from own format to associate directly with media
+        - [x] Export database from own format to associate directly with media
         - [ ] Implied tags
             - Issueify but: "implied" means that media with one tag is implied to have another.
             - If it shouldn't have that tag, you can say that.
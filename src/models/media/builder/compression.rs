@@ -0,0 +1,147 @@
+//! Figures out whether media is compressed losslessly.
+//!
+//! Most formats are always one or the other. WebP and AVIF can be both, so we
+//! peek at their headers.
+
+use camino::Utf8Path;
+use tokio::io::AsyncReadExt as _;
+
+use crate::{error::RavesError, models::media::metadata::Compression};
+
+use super::MediaBuilder;
+
+/// How much of a file to look through for its header.
+const HEADER_LEN: u64 = 64 * 1024;
+
+impl MediaBuilder {
+    /// Finds the compression of an image from its MIME type (like `webp`)
+    /// and, when needed, its header.
+    #[tracing::instrument(skip(self))]
+    pub(super) async fn apply_image_compression(
+        &mut self,
+        path: &Utf8Path,
+        mime_type: &str,
+    ) -> Result<(), RavesError> {
+        let mut header = Vec::new();
+        if matches!(mime_type, "webp" | "avif") {
+            let file = tokio::fs::File::open(path).await.map_err(|error| {
+                RavesError::FailedToOpenMediaFile {
+                    path: path.to_path_buf(),
+                    error,
+                }
+            })?;
+
+            file.take(HEADER_LEN)
+                .read_to_end(&mut header)
+                .await
+                .map_err(|err| RavesError::FileMetadataFailure {
+                    path: path.to_string(),
+                    err,
+                })?;
+        }
+
+        self.compression = image_compression(mime_type, &header);
+        tracing::debug!("image compression is {:?}", self.compression);
+        Ok(())
+    }
+}
+
+/// The compression of an image with the given MIME type and header.
+pub(super) fn image_compression(mime_type: &str, header: &[u8]) -> Option<Compression> {
+    match mime_type {
+        "png" | "gif" | "bmp" | "x-ms-bmp" | "qoi" => Some(Compression::Lossless),
+        "jpeg" | "jpg" | "heif" | "heic" => Some(Compression::Lossy),
+        "webp" => webp_compression(header),
+        "avif" => avif_compression(header),
+        _ => None,
+    }
+}
+
+/// The compression of a video's codec, by its FFmpeg name (like `h264`).
+pub(super) fn video_compression(codec: &str) -> Option<Compression> {
+    match codec {
+        "ffv1" | "huffyuv" | "ffvhuff" | "utvideo" | "magicyuv" | "png" | "rawvideo" | "qtrle" => {
+            Some(Compression::Lossless)
+        }
+        "h264" | "hevc" | "av1" | "vp8" | "vp9" | "mpeg4" | "mpeg2video" | "mpeg1video"
+        | "mjpeg" | "prores" | "theora" | "h263" | "wmv3" | "vc1" => Some(Compression::Lossy),
+        _ => None,
+    }
+}
+
+/// WebP images hold either a lossy `VP8 ` chunk or a lossless `VP8L` one.
+/// (Animations hold one for each frame, so the first one wins.)
+fn webp_compression(header: &[u8]) -> Option<Compression> {
+    if header.get(..4)? != b"RIFF" || header.get(8..12)? != b"WEBP" {
+        return None;
+    }
+
+    header.windows(4).skip(12).find_map(|chunk| match chunk {
+        b"VP8L" => Some(Compression::Lossless),
+        b"VP8 " => Some(Compression::Lossy),
+        _ => None,
+    })
+}
+
+/// AVIF can only be lossless when it keeps the original RGB values, which it
+/// marks with the "identity" matrix (`0`) in its `nclx` color box.
+fn avif_compression(header: &[u8]) -> Option<Compression> {
+    // the box looks like `colr`, `nclx`, then three `u16`s: primaries,
+    // transfer, and matrix
+    let start = header.windows(8).position(|w| w == b"colrnclx")? + 12;
+    let matrix = header.get(start..start + 2)?;
+
+    Some(match u16::from_be_bytes([matrix[0], matrix[1]]) {
+        0 => Compression::Lossless,
+        _ => Compression::Lossy,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn webp_chunks() {
+        let webp = |chunk: &[u8]| [b"RIFF\0\0\0\0WEBP".as_slice(), chunk, b"\0\0\0\0"].concat();
+
+        assert_eq!(
+            image_compression("webp", &webp(b"VP8L")),
+            Some(Compression::Lossless)
+        );
+        assert_eq!(
+            image_compression("webp", &webp(b"VP8 ")),
+            Some(Compression::Lossy)
+        );
+        // extended files can have alpha before the image
+        assert_eq!(
+            image_compression("webp", &webp(b"VP8X\0\0\0\0ALPH\0\0\0\0VP8 ")),
+            Some(Compression::Lossy)
+        );
+        assert_eq!(image_compression("webp", b"not a webp"), None);
+    }
+
+    #[test]
+    fn avif_matrix() {
+        let avif = |matrix: u8| [b"....colrnclx\0\x01\0\x0d\0".as_slice(), &[matrix, 1]].concat();
+
+        assert_eq!(
+            image_compression("avif", &avif(0)),
+            Some(Compression::Lossless)
+        );
+        assert_eq!(
+            image_compression("avif", &avif(6)),
+            Some(Compression::Lossy)
+        );
+        assert_eq!(image_compression("avif", b"no color box"), None);
+    }
+
+    #[test]
+    fn fixed_formats() {
+        assert_eq!(image_compression("png", &[]), Some(Compression::Lossless));
+        assert_eq!(image_compression("jpeg", &[]), Some(Compression::Lossy));
+        assert_eq!(video_compression("ffv1"), Some(Compression::Lossless));
+        assert_eq!(video_compression("h264"), Some(Compression::Lossy));
+        assert_eq!(image_compression("tiff", &[]), None);
+    }
+}
@@ -1,5 +1,5 @@
 use camino::Utf8Path;
-use kamadak_exif::{Exif as KamadakExif, In, Tag};
+use kamadak_exif::{Context, Exif as KamadakExif, In, Tag};
 use sqlx::types::Json;
 
 use crate::{
@@ -7,7 +7,7 @@ use crate::{
     models::media::metadata::{MediaKind, OtherMetadataMap, OtherMetadataValue, SpecificMetadata},
 };
 
-use super::MediaBuilder;
+use super::{keywords::xp_keywords, MediaBuilder};
 
 impl MediaBuilder {
     /// Applies EXIF data from `kamadak_exif` to `self`.
@@ -67,12 +67,23 @@ impl MediaBuilder {
         self.other_metadata = Some(Json(mapped));
         tracing::debug!("got other metadata from exif!");
 
+        // keywords from windows
+        if let Some(kamadak_exif::Value::Byte(ref bytes)) =
+            exif.get_field(XP_KEYWORDS, p).map(|field| &field.value)
+        {
+            self.keywords.extend(xp_keywords(bytes));
+            tracing::debug!("got keywords from exif!");
+        }
+
         tracing::debug!("finished looking for exif data!");
 
         Ok(())
     }
 }
 
+/// Windows' `XPKeywords` tag, which `kamadak_exif` doesn't have a name for.
+const XP_KEYWORDS: Tag = Tag(Context::Tiff, 0x9c9e);
+
 /// We use this function to 'look' at the metadata of the file, returning EXIF
 /// information from `kamadak_exif`.
 ///
@@ -0,0 +1,291 @@
+//! Reads the keywords that media files carry themselves, then turns them into
+//! tags.
+//!
+//! Keywords can hide in a few places:
+//!
+//! - XMP: `dc:subject` and `lr:hierarchicalSubject` (for Lightroom's
+//!   `parent|child` keywords, where we use the last part).
+//! - IPTC: the `Keywords` dataset, inside Photoshop's `8BIM` resources.
+//! - EXIF: Windows' `XPKeywords`. (That one's read in the `kamadak` module.)
+
+use std::collections::HashSet;
+
+use camino::Utf8Path;
+use tokio::io::{AsyncReadExt as _, AsyncSeekExt as _};
+
+use crate::{
+    config::Config,
+    error::RavesError,
+    models::{media::Media, tags::Tag, xmp::rdf_items},
+};
+
+use super::MediaBuilder;
+
+/// How much of the start of a file we look through for keywords. Metadata
+/// almost always comes before the image data, so this is plenty.
+const HEAD_LEN: u64 = 1024 * 1024;
+
+/// How much of the end of a file we look through, too. Some formats, like
+/// WebP, usually put their XMP last.
+const TAIL_LEN: u64 = 256 * 1024;
+
+impl MediaBuilder {
+    /// Finds any XMP and IPTC keywords in the file at `path`.
+    ///
+    /// Only the start and end of the file are read, so big files don't have
+    /// to fit in memory.
+    #[tracing::instrument(skip(self))]
+    pub(super) async fn apply_keywords(&mut self, path: &Utf8Path) -> Result<(), RavesError> {
+        let (head, tail) =
+            read_ends(path)
+                .await
+                .map_err(|err| RavesError::FileMetadataFailure {
+                    path: path.to_string(),
+                    err,
+                })?;
+
+        if let Some(xmp) = xmp_packet(&head).or_else(|| xmp_packet(&tail)) {
+            self.keywords.extend(rdf_items(&xmp, "dc:subject"));
+            self.keywords.extend(
+                rdf_items(&xmp, "lr:hierarchicalSubject")
+                    .into_iter()
+                    .filter_map(|k| k.rsplit('|').next().map(String::from)),
+            );
+        }
+        self.keywords.extend(iptc_keywords(&head));
+
+        tracing::debug!("found keywords: {:?}", self.keywords);
+        Ok(())
+    }
+}
+
+/// Turns keywords from a media file into its tags.
+///
+/// This writes to the database, so only call it once the media is saved.
+/// Tags that came from its file last time are swapped for the new ones, while
+/// the rest are kept.
+#[tracing::instrument(skip(media), fields(media.id = %media.id))]
+pub(crate) async fn import_keywords(
+    media: &mut Media,
+    keywords: &[String],
+) -> Result<(), RavesError> {
+    // don't bother the database for files that never had keywords
+    if keywords.is_empty() && media.tags.is_empty() {
+        return Ok(());
+    }
+
+    let section = match keywords.is_empty() {
+        true => None,
+        false => Config::read().await.keyword_section.clone(),
+    };
+    let (imported, forgotten) = Tag::import_keywords(media.id, keywords, section.as_ref()).await?;
+
+    let tags = &mut media.tags.0;
+    tags.retain(|tag| !forgotten.contains(&tag.uuid));
+    tags.extend(imported);
+
+    // keep the first of any repeats
+    let mut seen = HashSet::new();
+    tags.retain(|tag| seen.insert(tag.uuid.clone()));
+
+    Ok(())
+}
+
+/// Reads the start and end of a file. When the file is small, the tail is
+/// empty, since the head has all of it.
+async fn read_ends(path: &Utf8Path) -> std::io::Result<(Vec<u8>, Vec<u8>)> {
+    let mut file = tokio::fs::File::open(path).await?;
+    let len = file.metadata().await?.len();
+
+    let mut head = Vec::new();
+    (&mut file).take(HEAD_LEN).read_to_end(&mut head).await?;
+
+    let mut tail = Vec::new();
+    if len > HEAD_LEN {
+        file.seek(std::io::SeekFrom::Start(
+            len.saturating_sub(TAIL_LEN).max(HEAD_LEN),
+        ))
+        .await?;
+        file.take(TAIL_LEN).read_to_end(&mut tail).await?;
+    }
+
+    Ok((head, tail))
+}
+
+/// Finds the XMP packet in a file, if it has one.
+///
+/// XMP is plain XML wherever it's stored, so we just look for its start and
+/// end.
+fn xmp_packet(bytes: &[u8]) -> Option<String> {
+    let start = find(bytes, b"<x:xmpmeta")?;
+    let len = find(&bytes[start..], b"</x:xmpmeta>")? + b"</x:xmpmeta>".len();
+    Some(String::from_utf8_lossy(&bytes[start..start + len]).into_owned())
+}
+
+/// Grabs the IPTC keywords in a file's Photoshop resources.
+fn iptc_keywords(bytes: &[u8]) -> Vec<String> {
+    let Some(iptc) = photoshop_resource(bytes, 0x0404) else {
+        return Vec::new();
+    };
+
+    // IPTC records are a tag marker (`0x1C`), record number, dataset number,
+    // then a big-endian length. keywords are in record 2, dataset 25
+    let mut keywords = Vec::new();
+    let mut rest = iptc;
+    while let [0x1C, record, dataset, a, b, data @ ..] = rest {
+        let len = u16::from_be_bytes([*a, *b]) as usize;
+
+        // the top bit means an "extended" length, which keywords never need
+        if len & 0x8000 != 0 || data.len() < len {
+            break;
+        }
+
+        if (*record, *dataset) == (2, 25) {
+            keywords.push(text(&data[..len]));
+        }
+        rest = &data[len..];
+    }
+
+    keywords
+}
+
+/// Finds the data of the Photoshop (`8BIM`) resource with the given id.
+fn photoshop_resource(bytes: &[u8], id: u16) -> Option<&[u8]> {
+    let mut at = 0;
+    while let Some(found) = find(&bytes[at..], b"8BIM") {
+        let resource = &bytes[at + found + 4..];
+        at += found + 4;
+
+        // id, then a padded pascal string for the name, then the length
+        let [a, b, name_len, ..] = *resource else {
+            continue;
+        };
+        let name_len = name_len as usize + 1;
+        let len_at = 2 + name_len + name_len % 2;
+        let Some(&[w, x, y, z]) = resource.get(len_at..len_at + 4) else {
+            continue;
+        };
+        let len = u32::from_be_bytes([w, x, y, z]) as usize;
+
+        if u16::from_be_bytes([a, b]) == id {
+            return resource.get(len_at + 4..len_at + 4 + len);
+        }
+    }
+
+    None
+}
+
+/// Reads EXIF's `XPKeywords`, which is UTF-16 split up by semicolons.
+pub(super) fn xp_keywords(bytes: &[u8]) -> Vec<String> {
+    let units = bytes
+        .chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .take_while(|&u| u != 0)
+        .collect::<Vec<_>>();
+
+    String::from_utf16_lossy(&units)
+        .split(';')
+        .map(String::from)
+        .collect()
+}
+
+/// Text in old metadata is usually UTF-8, but it can also be Latin-1.
+fn text(bytes: &[u8]) -> String {
+    match core::str::from_utf8(bytes) {
+        Ok(s) => s.to_string(),
+        Err(_) => bytes.iter().map(|&b| b as char).collect(),
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn xmp_subjects() {
+        let file = br#"....<x:xmpmeta xmlns:x="adobe:ns:meta/"><rdf:RDF><rdf:Description>
+            <dc:subject><rdf:Bag>
+                <rdf:li>cat</rdf:li>
+                <rdf:li xml:lang="en">fish &amp; chips</rdf:li>
+                <rdf:li/>
+            </rdf:Bag></dc:subject>
+            <lr:hierarchicalSubject><rdf:Bag>
+                <rdf:li>places|France|Paris</rdf:li>
+            </rdf:Bag></lr:hierarchicalSubject>
+        </rdf:Description></rdf:RDF></x:xmpmeta>...."#;
+
+        let xmp = xmp_packet(file).unwrap();
+        assert_eq!(rdf_items(&xmp, "dc:subject"), ["cat", "fish & chips"]);
+        assert_eq!(
+            rdf_items(&xmp, "lr:hierarchicalSubject"),
+            ["places|France|Paris"]
+        );
+        assert!(rdf_items(&xmp, "dc:creator").is_empty());
+    }
+
+    #[test]
+    fn iptc_records() {
+        let record =
+            |dataset: u8, data: &[u8]| [&[0x1C, 2, dataset, 0, data.len() as u8], data].concat();
+        let iptc = [
+            record(0, b"\x00\x04"),
+            record(25, b"beach"),
+            record(5, b"a title"),
+            record(25, b"caf\xe9"),
+        ]
+        .concat();
+
+        // `8BIM`, id, empty name (padded to 2 bytes), then the length
+        let file = [
+            b"Photoshop 3.0\0".as_slice(),
+            b"8BIM\x04\x0c\0\0\0\0\0\0",
+            b"8BIM\x04\x04\0\0",
+            &(iptc.len() as u32).to_be_bytes(),
+            &iptc,
+        ]
+        .concat();
+
+        assert_eq!(iptc_keywords(&file), ["beach", "café"]);
+        assert!(iptc_keywords(b"nothing here").is_empty());
+    }
+
+    #[test]
+    fn windows_keywords() {
+        let bytes = "dog;park\0"
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes)
+            .collect::<Vec<_>>();
+        assert_eq!(xp_keywords(&bytes), ["dog", "park"]);
+    }
+
+    #[tokio::test]
+    async fn only_the_ends_are_read() {
+        let xmp = |subject: &str| {
+            format!(
+                "<x:xmpmeta><dc:subject><rdf:Bag><rdf:li>{subject}</rdf:li></rdf:Bag></dc:subject></x:xmpmeta>"
+            )
+        };
+
+        // like a big WebP, with its XMP at the very end
+        let mut file = vec![0_u8; HEAD_LEN as usize * 2];
+        file.extend(xmp("at the end").as_bytes());
+        let path = std::env::temp_dir().join(format!("{}.webp", uuid::Uuid::new_v4()));
+        tokio::fs::write(&path, &file).await.unwrap();
+
+        let path = Utf8Path::from_path(&path).unwrap();
+        let mut builder = MediaBuilder::default();
+        builder.apply_keywords(path).await.unwrap();
+        assert_eq!(builder.keywords, ["at the end"]);
+
+        // the middle of the file is skipped
+        let (head, tail) = read_ends(path).await.unwrap();
+        assert_eq!(head.len() as u64, HEAD_LEN);
+        assert_eq!(tail.len() as u64, TAIL_LEN);
+
+        tokio::fs::remove_file(path).await.unwrap();
+    }
+}
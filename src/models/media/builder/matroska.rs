@@ -49,6 +49,7 @@ impl MediaBuilder {
             if let Some(duration) = mkv.info.duration {
                 self.specific_metadata = Some(Json(SpecificMetadata::Video {
                     length: duration.as_secs_f64(),
+                    framerate: None,
                 }));
                 tracing::debug!(
                     "got video duration from matroska: length {}",
@@ -6,9 +6,11 @@
 //! with full support for all these types!
 
 pub mod avif;
+pub mod compression;
 pub mod generic;
 pub mod image_crate;
 pub mod kamadak;
+pub mod keywords;
 pub mod matroska;
 pub mod mp4parse;
 pub mod nom;
@@ -19,7 +21,7 @@ use sqlx::types::Json;
 use uuid::Uuid;
 
 use crate::{
-    database::{DATABASE, HASHES_TABLE, INFO_TABLE},
+    database::{DATABASE, HASHES_TABLE, INFO_TABLE, MEDIA_COLUMNS},
     error::RavesError,
     models::{
         media::{metadata::MediaKind, Media},
@@ -29,13 +31,13 @@ use crate::{
 
 use super::{
     hash::MediaHash,
-    metadata::{Format, OtherMetadataMap, SpecificMetadata},
+    metadata::{Compression, Format, Framerate, OtherMetadataMap, SpecificMetadata},
 };
 
 /// A media file's metadata. Common metadata is always present, while the `other`
 /// field represents that which isn't standard in a dictionary (string, string)
 /// form.
-#[derive(Clone, Debug, PartialEq, PartialOrd, serde::Serialize, serde::Deserialize)]
+#[derive(Clone, Debug, Default, PartialEq, PartialOrd, serde::Serialize, serde::Deserialize)]
 pub struct MediaBuilder {
     /// How large the file is, in bytes.
     pub filesize: Option<i64>,
@@ -70,18 +72,24 @@ pub struct MediaBuilder {
     /// This is stored as `Json` for the database.
     pub other_metadata: Option<Json<OtherMetadataMap>>,
 
-    /// The tags of a media file. Note that these can come from the file's EXIF
-    /// metadata or Rave's internals.
-    pub tags: Json<Vec<Tag>>,
+    /// Whether the media is compressed losslessly.
+    pub compression: Option<Compression>,
+
+    /// Keywords found in the file's own metadata. These become tags once the
+    /// media is saved.
+    pub keywords: Vec<String>,
 }
 
 impl MediaBuilder {
     /// Constructs a [`Media`] file representation from this [`MediaBuilder`].
+    ///
+    /// This also gives the keywords found in the file. Once the media is
+    /// saved, pass them to [`keywords::import_keywords`] to make them tags.
     #[tracing::instrument(skip(self))]
     pub(super) async fn build<P: AsRef<Utf8Path> + std::fmt::Debug>(
         self,
         path: P,
-    ) -> Result<Media, RavesError> {
+    ) -> Result<(Media, Vec<String>), RavesError> {
         let path = path.as_ref();
         self.build_internal(path).await
     }
@@ -101,16 +109,19 @@ impl MediaBuilder {
     ///         - AVIF only: apply `avif_parse` crate
     ///         - TIFF/JPEG/HEIF/PNG/WebP: apply `kamadak_exif` crate
     ///         - anything: apply `image` crate
+    ///         - anything: find its compression from the format and header
+    ///         - anything: read XMP and IPTC keywords
     ///     - If we're a video,
     ///         - MP4/MOV only: apply `nom_exif` crate
     ///         - MP4 only: apply `mp4parse` crate
     ///         - MOV/MKV/WebM: apply `matroska` crate
+    ///         - anything: find its compression from the codec
     /// 4. Check for a previous cache of the media.
-    /// 5. If present, steal its UUID and first-seen datetime.
+    /// 5. If present, steal its UUID, first-seen datetime, and tags.
     /// 6. Unwrap all fields and stick into a new `Media`.
-    /// 7. Return it.
+    /// 7. Return it, alongside the keywords we found.
     #[tracing::instrument(skip(self))]
-    async fn build_internal(mut self, path: &Utf8Path) -> Result<Media, RavesError> {
+    async fn build_internal(mut self, path: &Utf8Path) -> Result<(Media, Vec<String>), RavesError> {
         // grab format and apply it to self
         let format = format(path).await?;
         let mime_type = format.mime_type();
@@ -151,14 +162,25 @@ impl MediaBuilder {
                         .await
                         .map_err(|e| tracing::error!("Failed to parse with `image`! err: {e}"));
                 }
+
+                _ = self
+                    .apply_image_compression(path, &mime_type.to_lowercase())
+                    .await
+                    .inspect_err(|e| tracing::warn!("Failed to find image compression. err: {e}"));
+
+                _ = self
+                    .apply_keywords(path)
+                    .await
+                    .inspect_err(|e| tracing::warn!("Failed to read keywords. err: {e}"));
             }
 
             MediaKind::Video => {
-                // ffmpeg: get video length
-                let specific_metadata = get_video_len(path)
+                // ffmpeg: get video length and the codec's compression
+                let (specific_metadata, compression) = get_video_info(path)
                     .inspect_err(|e| tracing::error!("Failed to get video length. err: {e}"))?;
 
                 self.specific_metadata = Some(Json(specific_metadata));
+                self.compression = compression;
 
                 // apply `mp4`
                 _ = self.apply_mp4parse(path, media_kind).await.map_err(|e| {
@@ -179,9 +201,10 @@ impl MediaBuilder {
         let StaticFields {
             id,
             first_seen_date,
+            tags,
         } = get_static_fields(path).await?;
 
-        Ok(Media {
+        let media = Media {
             id,
 
             path: path.to_string(),
@@ -214,8 +237,11 @@ impl MediaBuilder {
 
             first_seen_date,
 
-            tags: self.tags,
-        })
+            tags: Json(tags),
+            compression: self.compression,
+        };
+
+        Ok((media, self.keywords))
     }
 }
 
@@ -249,22 +275,23 @@ async fn format(path: &Utf8Path) -> Result<Format, RavesError> {
 #[tracing::instrument]
 async fn get_static_fields(path: &Utf8Path) -> Result<StaticFields, RavesError> {
     // if the media was previously saved in the database, we'll need to use
-    // its id and 'first seen date'
-    let (id, first_seen_date) = 'a: {
+    // its id, 'first seen date', and tags
+    let (id, first_seen_date, tags) = 'a: {
         let mut conn = DATABASE.acquire().await.inspect_err(|e| {
             tracing::error!("Failed to connect to database in metadata builder! err: {e}")
         })?;
 
         // if we find our path in there, we can just use the old stuff
-        let old_media_path_query =
-            sqlx::query_as::<_, Media>(&format!("SELECT * FROM {INFO_TABLE} WHERE path = $1"))
-                .bind(path.to_string())
-                .fetch_optional(&mut *conn)
-                .await
-                .inspect_err(|e| tracing::error!("(path) Failed to query database! err: {e}"))?;
+        let old_media_path_query = sqlx::query_as::<_, Media>(&format!(
+            "SELECT {MEDIA_COLUMNS} FROM {INFO_TABLE} WHERE path = $1"
+        ))
+        .bind(path.to_string())
+        .fetch_optional(&mut *conn)
+        .await
+        .inspect_err(|e| tracing::error!("(path) Failed to query database! err: {e}"))?;
 
         if let Some(old_media) = old_media_path_query {
-            break 'a (old_media.id, old_media.first_seen_date);
+            break 'a (old_media.id, old_media.first_seen_date, old_media.tags.0);
         }
 
         // we can also check for duplicate photos, as that's fair game for
@@ -279,16 +306,17 @@ async fn get_static_fields(path: &Utf8Path) -> Result<StaticFields, RavesError>
             .inspect_err(|e| tracing::error!("(hash) Failed to query database! err: {e}"))?;
 
             if let Some(old_media) = old_media_hash_query {
-                break 'a (Uuid::new_v4(), old_media.first_seen_date);
+                break 'a (Uuid::new_v4(), old_media.first_seen_date, Vec::new());
             }
         }
 
-        (Uuid::new_v4(), Utc::now())
+        (Uuid::new_v4(), Utc::now(), Vec::new())
     };
 
     Ok(StaticFields {
         id,
         first_seen_date,
+        tags,
     })
 }
 
@@ -296,26 +324,22 @@ async fn get_static_fields(path: &Utf8Path) -> Result<StaticFields, RavesError>
 struct StaticFields {
     id: Uuid,
     first_seen_date: DateTime<Utc>,
+    /// The tags the media had last time, if any.
+    tags: Vec<Tag>,
 }
 
-impl Default for MediaBuilder {
-    fn default() -> Self {
-        Self {
-            filesize: None,
-            format: None,
-            creation_date: None,
-            modification_date: None,
-            width_px: None,
-            height_px: None,
-            specific_metadata: None,
-            other_metadata: None,
-            tags: Json(vec![]),
-        }
-    }
+/// Grabs the video length (and framerate) of a media file using FFmpeg.
+pub fn get_video_len(path: &Utf8Path) -> Result<SpecificMetadata, RavesError> {
+    get_video_info(path).map(|(specific_metadata, _)| specific_metadata)
 }
 
-/// Grabs the video length of a media file using FFmpeg.
-pub fn get_video_len(path: &Utf8Path) -> Result<SpecificMetadata, RavesError> {
+/// Grabs the video length (and framerate) of a media file, along with the
+/// compression of its codec.
+///
+/// FFmpeg only opens the file once for all of these.
+pub fn get_video_info(
+    path: &Utf8Path,
+) -> Result<(SpecificMetadata, Option<Compression>), RavesError> {
     let path_str = path.to_string();
 
     // let's ask ffmpeg what it thinks
@@ -327,17 +351,34 @@ pub fn get_video_len(path: &Utf8Path) -> Result<SpecificMetadata, RavesError> {
     })?;
 
     // grab the first video stream and see how long it is
-    let video_length = t
+    let stream = t
         .streams()
-        .find(|s| s.parameters().medium() == ffmpeg_next::media::Type::Video)
+        .find(|s| s.parameters().medium() == ffmpeg_next::media::Type::Video);
+    let video_length = stream
+        .as_ref()
         .map(|s| (ffmpeg_next::Rational::new(s.duration() as i32, 1)) * s.time_base())
         .map(|s| s.0 as f64 / s.1 as f64)
         .unwrap_or(0_f64);
     tracing::trace!("video len is {video_length}.");
 
-    Ok(SpecificMetadata::Video {
-        length: video_length,
-    })
+    // ffmpeg says `0/0` when it doesn't know
+    let framerate = stream
+        .as_ref()
+        .map(|s| s.avg_frame_rate())
+        .filter(|r| r.numerator() > 0 && r.denominator() > 0)
+        .map(|r| Framerate::new(r.numerator() as u64, r.denominator() as u64));
+    tracing::trace!("video framerate is {framerate:?}.");
+
+    let codec = stream.map(|s| s.parameters().id().name());
+    tracing::trace!("video codec is {codec:?}.");
+
+    Ok((
+        SpecificMetadata::Video {
+            length: video_length,
+            framerate,
+        },
+        codec.and_then(compression::video_compression),
+    ))
 }
 
 #[cfg(test)]
@@ -350,7 +391,7 @@ mod tests {
     use uuid::Uuid;
 
     use crate::{
-        database::{self, InsertIntoTable as _, DATABASE, INFO_TABLE},
+        database::{self, InsertIntoTable as _, DATABASE, INFO_TABLE, MEDIA_COLUMNS},
         models::media::{
             metadata::{Format, SpecificMetadata},
             Media,
@@ -385,6 +426,7 @@ mod tests {
             specific_metadata: Json(SpecificMetadata::Image {}),
             other_metadata: None,
             tags: Json(vec![]),
+            compression: None,
         };
 
         // insert into db
@@ -396,7 +438,7 @@ mod tests {
             .unwrap();
 
         // now run the media builder on a real file...
-        let new_media = MediaBuilder::default().build(&path).await.unwrap();
+        let (new_media, _keywords) = MediaBuilder::default().build(&path).await.unwrap();
 
         assert_eq!(old_media.id, new_media.id, "same uuids");
         assert_eq!(
@@ -410,11 +452,12 @@ mod tests {
             .execute(&mut *conn)
             .await
             .unwrap();
-        let inserted_new_media =
-            sqlx::query_as::<_, Media>(&format!("SELECT * FROM {INFO_TABLE} LIMIT 1"))
-                .fetch_one(&mut *conn)
-                .await
-                .unwrap();
+        let inserted_new_media = sqlx::query_as::<_, Media>(&format!(
+            "SELECT {MEDIA_COLUMNS} FROM {INFO_TABLE} LIMIT 1"
+        ))
+        .fetch_one(&mut *conn)
+        .await
+        .unwrap();
 
         assert_eq!(
             old_media.id, inserted_new_media.id,
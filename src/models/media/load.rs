@@ -1,9 +1,12 @@
 use camino::{Utf8Path, Utf8PathBuf};
 
 use crate::{
-    database::{InsertIntoTable, DATABASE, INFO_TABLE},
+    database::{InsertIntoTable, DATABASE, INFO_TABLE, MEDIA_COLUMNS},
     error::{DatabaseError, RavesError},
-    models::media::{builder::MediaBuilder, hash::MediaHash},
+    models::media::{
+        builder::{keywords::import_keywords, MediaBuilder},
+        hash::MediaHash,
+    },
 };
 
 use super::Media;
@@ -131,7 +134,7 @@ async fn from_database(path: &Utf8Path) -> Result<Option<Media>, RavesError> {
 
     // query for an entry with matching path
     sqlx::query_as::<_, Media>(&format!(
-        "SELECT * FROM {INFO_TABLE} WHERE path = $1 LIMIT 1"
+        "SELECT {MEDIA_COLUMNS} FROM {INFO_TABLE} WHERE path = $1 LIMIT 1"
     ))
     .bind(path.to_string())
     .fetch_optional(&mut *conn)
@@ -153,7 +156,7 @@ async fn from_database(path: &Utf8Path) -> Result<Option<Media>, RavesError> {
 async fn from_disk(path: &Utf8Path) -> Result<Media, RavesError> {
     // grab the media file metadata
     tracing::trace!("Feeding media file path to MediaBuilder...");
-    let media = MediaBuilder::default().build(path).await?;
+    let (mut media, keywords) = MediaBuilder::default().build(path).await?;
 
     // cache in database
     {
@@ -172,6 +175,11 @@ async fn from_disk(path: &Utf8Path) -> Result<Media, RavesError> {
             .map_err(|e| DatabaseError::InsertionFailed(e.to_string()))?;
     }
 
+    // now that it's saved, its keywords can become tags
+    _ = import_keywords(&mut media, &keywords)
+        .await
+        .inspect_err(|e| tracing::warn!("Failed to import keywords as tags. err: {e}"));
+
     // return the media
     Ok(media)
 }
@@ -13,7 +13,12 @@ pub enum SpecificMetadata {
     },
 
     #[non_exhaustive]
-    Video { length: f64 },
+    Video {
+        length: f64,
+        /// Older caches don't have this, so it might be missing.
+        #[serde(default)]
+        framerate: Option<Framerate>,
+    },
 }
 
 #[derive(Clone, Debug, PartialEq, PartialOrd, serde::Serialize, serde::Deserialize)]
@@ -144,6 +149,28 @@ impl std::fmt::Display for Format {
     }
 }
 
+/// How a media file's pixels are compressed.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    serde::Serialize,
+    serde::Deserialize,
+    sqlx::Type,
+)]
+#[sqlx(rename_all = "lowercase")]
+pub enum Compression {
+    /// Every pixel is kept exactly, like in PNG or FFV1.
+    Lossless,
+    /// Some detail is thrown away to save space, like in JPEG or H.264.
+    Lossy,
+}
+
 /// A video's framerate, represented as a fraction.
 pub type Framerate = fraction::Fraction;
 
@@ -9,7 +9,7 @@ use sqlx::{
 
 use super::tags::Tag;
 use crate::{database::InsertIntoTable, error::RavesError};
-use metadata::{Format, OtherMetadataMap, SpecificMetadata};
+use metadata::{Compression, Format, OtherMetadataMap, SpecificMetadata};
 
 mod builder;
 pub mod hash;
@@ -75,7 +75,14 @@ pub struct Media {
 
     /// The tags of a media file. Note that these can come from the file's EXIF
     /// metadata or Rave's internals.
+    ///
+    /// These live in the [`MEDIA_TAGS_TABLE`](crate::database::MEDIA_TAGS_TABLE),
+    /// so they're only read here. Saving the media doesn't change them.
     pub tags: Json<Vec<Tag>>,
+
+    /// Whether the media is compressed losslessly. This is `None` when we
+    /// can't tell.
+    pub compression: Option<Compression>,
 }
 
 impl Media {
@@ -94,7 +101,7 @@ impl InsertIntoTable for Media {
         sqlx::query!(
             r#"
         INSERT INTO info 
-        (id, path, filesize, format, creation_date, modification_date, first_seen_date, width_px, height_px, specific_metadata, other_metadata, tags)
+        (id, path, filesize, format, creation_date, modification_date, first_seen_date, width_px, height_px, specific_metadata, other_metadata, compression)
         VALUES
         ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
         ON CONFLICT(id)
@@ -107,7 +114,7 @@ impl InsertIntoTable for Media {
             height_px = excluded.height_px,
             specific_metadata = excluded.specific_metadata,
             other_metadata = excluded.other_metadata,
-            tags = excluded.tags;
+            compression = excluded.compression;
         "#,
             self.id,
             self.path,
@@ -120,7 +127,7 @@ impl InsertIntoTable for Media {
             self.height_px,
             self.specific_metadata,
             self.other_metadata,
-            self.tags
+            self.compression
         )
     }
 }
@@ -1,3 +1,7 @@
 pub mod media;
+pub mod rating;
+pub mod tag_edits;
+pub mod tag_stats;
 pub mod tags;
 pub mod thumbnail;
+pub mod xmp;
@@ -0,0 +1,141 @@
+//! Favorites and star ratings that users give their media.
+//!
+//! These live in their own table, keyed by the media's `id`, so rescanning a
+//! media file never resets them. Deleting one removes its rating, though.
+
+use uuid::Uuid;
+
+use crate::{
+    database::{DATABASE, RATING_TABLE},
+    error::{DatabaseError, RavesError},
+    models::tags::ensure_media_exists,
+};
+
+/// How much a user likes a media file.
+///
+/// Media that's never been rated uses the default: not a favorite, with zero
+/// stars.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    serde::Serialize,
+    serde::Deserialize,
+    sqlx::FromRow,
+)]
+pub struct Rating {
+    pub favorite: bool,
+    /// From `0` (unrated) to [`Rating::MAX_STARS`].
+    pub stars: u8,
+}
+
+impl Rating {
+    pub const MAX_STARS: u8 = 5;
+
+    /// Grabs the rating of a media file.
+    #[tracing::instrument]
+    pub async fn get(media_id: Uuid) -> Result<Self, RavesError> {
+        let mut conn = DATABASE.acquire().await.inspect_err(|e| {
+            tracing::error!("Failed to connect to database for ratings. err: {e}")
+        })?;
+
+        let rating = sqlx::query_as::<_, Rating>(&format!(
+            "SELECT favorite, stars FROM {RATING_TABLE} WHERE media_id = $1"
+        ))
+        .bind(media_id)
+        .fetch_optional(&mut *conn)
+        .await
+        .inspect_err(|e| tracing::error!("Failed to get rating! err: {e}"))
+        .map_err(DatabaseError::QueryFailed)?;
+
+        Ok(rating.unwrap_or_default())
+    }
+
+    /// Replaces the rating of a media file.
+    #[tracing::instrument]
+    pub async fn set(media_id: Uuid, rating: Rating) -> Result<(), RavesError> {
+        check_stars(rating.stars)?;
+        upsert(
+            &[media_id],
+            "favorite, stars",
+            "$2, $3",
+            "favorite = excluded.favorite, stars = excluded.stars",
+            |q| q.bind(rating.favorite).bind(rating.stars),
+        )
+        .await
+    }
+
+    /// Marks (or unmarks) all the given media as favorites. Their stars stay
+    /// the same.
+    #[tracing::instrument]
+    pub async fn set_favorite(media_ids: &[Uuid], favorite: bool) -> Result<(), RavesError> {
+        upsert(
+            media_ids,
+            "favorite",
+            "$2",
+            "favorite = excluded.favorite",
+            |q| q.bind(favorite),
+        )
+        .await
+    }
+
+    /// Gives all the given media the same number of stars. Whether they're
+    /// favorites stays the same.
+    #[tracing::instrument]
+    pub async fn set_stars(media_ids: &[Uuid], stars: u8) -> Result<(), RavesError> {
+        check_stars(stars)?;
+        upsert(media_ids, "stars", "$2", "stars = excluded.stars", |q| {
+            q.bind(stars)
+        })
+        .await
+    }
+}
+
+fn check_stars(stars: u8) -> Result<(), RavesError> {
+    if stars > Rating::MAX_STARS {
+        return Err(RavesError::InvalidRating { stars });
+    }
+    Ok(())
+}
+
+type Query<'q> = sqlx::query::Query<'q, sqlx::Sqlite, sqlx::sqlite::SqliteArguments<'q>>;
+
+/// Updates the given columns for each media file, all at once.
+///
+/// If anything fails, or any of the media don't exist, nothing is changed.
+async fn upsert(
+    media_ids: &[Uuid],
+    columns: &str,
+    values: &str,
+    update: &str,
+    bind: impl for<'q> Fn(Query<'q>) -> Query<'q>,
+) -> Result<(), RavesError> {
+    let mut tx = DATABASE
+        .begin()
+        .await
+        .inspect_err(|e| tracing::error!("Failed to start a transaction for ratings. err: {e}"))?;
+
+    let query = format!(
+        "INSERT INTO {RATING_TABLE} (media_id, {columns}) VALUES ($1, {values}) \
+        ON CONFLICT(media_id) DO UPDATE SET {update}"
+    );
+    for media_id in media_ids {
+        ensure_media_exists(&mut tx, *media_id).await?;
+        bind(sqlx::query(&query).bind(*media_id))
+            .execute(&mut *tx)
+            .await
+            .inspect_err(|e| tracing::error!("Failed to set rating! err: {e}"))
+            .map_err(DatabaseError::QueryFailed)?;
+    }
+
+    tx.commit()
+        .await
+        .inspect_err(|e| tracing::error!("Failed to save ratings! err: {e}"))
+        .map_err(|e| DatabaseError::QueryFailed(e).into())
+}
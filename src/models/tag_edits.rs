@@ -0,0 +1,265 @@
+//! Adds or removes tags across many media files at once.
+//!
+//! Each bulk edit is recorded, alongside the media files it actually changed,
+//! so it can be undone later with [`TagEdit::undo`].
+//!
+//! Edits only touch the [`MEDIA_TAGS_TABLE`], so they never rewrite any media
+//! file's row in the [`INFO_TABLE`].
+
+use std::collections::HashSet;
+
+use chrono::{DateTime, Utc};
+use sqlx::SqliteConnection;
+use uuid::Uuid;
+
+use crate::{
+    database::{DATABASE, INFO_TABLE, MEDIA_TAGS_TABLE, TAG_EDITS_TABLE, TAG_EDIT_CHANGES_TABLE},
+    error::{DatabaseError, RavesError},
+    models::tags::{Tag, TagIdent, TagSource},
+    search::{
+        modifiers::Expr,
+        query::{Clause, Param},
+    },
+};
+
+/// The media files that a bulk edit applies to.
+#[derive(Clone, Debug, PartialEq, PartialOrd)]
+pub enum MediaSelection {
+    /// Everything matching a search.
+    Search(Vec<Expr>),
+    /// Specific media files, by `id`.
+    Ids(Vec<Uuid>),
+}
+
+/// Whether an edit added tags or removed them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, sqlx::Type)]
+#[sqlx(rename_all = "lowercase")]
+pub enum TagEditKind {
+    Add,
+    Remove,
+}
+
+/// A bulk tag edit that's been made.
+#[derive(Clone, Debug, PartialEq, PartialOrd)]
+pub struct TagEdit {
+    /// Pass this to [`TagEdit::undo`] to undo the edit.
+    pub id: Uuid,
+    pub kind: TagEditKind,
+    pub tags: Vec<TagIdent>,
+    pub made_at: DateTime<Utc>,
+    /// How many media files were changed. Those that already had (or didn't
+    /// have) the tags aren't counted.
+    pub affected: u64,
+}
+
+impl TagEdit {
+    /// Adds the given tags to every selected media file, all at once.
+    #[tracing::instrument]
+    pub async fn add(selection: &MediaSelection, tags: &[TagIdent]) -> Result<Self, RavesError> {
+        TagEdit::make(TagEditKind::Add, selection, tags).await
+    }
+
+    /// Removes the given tags from every selected media file, all at once.
+    ///
+    /// Only tags that media were given directly are removed. To hide an
+    /// implied tag, exclude it instead with [`Tag::exclude`].
+    #[tracing::instrument]
+    pub async fn remove(selection: &MediaSelection, tags: &[TagIdent]) -> Result<Self, RavesError> {
+        TagEdit::make(TagEditKind::Remove, selection, tags).await
+    }
+
+    /// Undoes an edit, returning how many media files changed back.
+    ///
+    /// Media that were changed again since then are left alone. For example,
+    /// undoing an add doesn't remove a tag that's already been removed.
+    #[tracing::instrument]
+    pub async fn undo(id: Uuid) -> Result<u64, RavesError> {
+        let mut tx = begin().await?;
+
+        let (kind, undone) = sqlx::query_as::<_, (TagEditKind, bool)>(&format!(
+            "SELECT kind, undone FROM {TAG_EDITS_TABLE} WHERE id = $1"
+        ))
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await
+        .inspect_err(|e| tracing::error!("Failed to get tag edit! err: {e}"))
+        .map_err(DatabaseError::QueryFailed)?
+        .ok_or(RavesError::TagEditNotFound { id })?;
+        if undone {
+            return Err(RavesError::TagEditAlreadyUndone { id });
+        }
+
+        let changed = match kind {
+            TagEditKind::Add => remove_changed(&mut tx, id).await?,
+            TagEditKind::Remove => add_changed(&mut tx, id).await?,
+        };
+
+        sqlx::query(&format!(
+            "UPDATE {TAG_EDITS_TABLE} SET undone = 1 WHERE id = $1"
+        ))
+        .bind(id)
+        .execute(&mut *tx)
+        .await
+        .inspect_err(|e| tracing::error!("Failed to mark tag edit as undone! err: {e}"))
+        .map_err(DatabaseError::QueryFailed)?;
+
+        commit(tx).await?;
+        Ok(changed.len() as u64)
+    }
+
+    async fn make(
+        kind: TagEditKind,
+        selection: &MediaSelection,
+        tags: &[TagIdent],
+    ) -> Result<Self, RavesError> {
+        // asking for a tag twice is the same as asking once
+        let mut seen = HashSet::new();
+        let tags = tags
+            .iter()
+            .filter(|tag| seen.insert(*tag))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let mut tx = begin().await?;
+        for tag in &tags {
+            Tag::get_in(&mut tx, tag).await?;
+        }
+
+        let id = Uuid::new_v4();
+        let made_at = Utc::now();
+        sqlx::query(&format!(
+            "INSERT INTO {TAG_EDITS_TABLE} (id, kind, made_at) VALUES ($1, $2, $3)"
+        ))
+        .bind(id)
+        .bind(kind)
+        .bind(made_at)
+        .execute(&mut *tx)
+        .await
+        .inspect_err(|e| tracing::error!("Failed to record tag edit! err: {e}"))
+        .map_err(DatabaseError::QueryFailed)?;
+
+        let Clause { sql, params } = selection.clause();
+        // write down which media will actually change. this happens before
+        // changing any, so each tag sees the same search results
+        for tag in &tags {
+            let has_tag = match kind {
+                TagEditKind::Add => format!("NOT {}", has_tag()),
+                TagEditKind::Remove => has_tag(),
+            };
+            let insert = format!(
+                "INSERT INTO {TAG_EDIT_CHANGES_TABLE} (edit_id, media_id, tag_id, source) \
+                SELECT ?, id, ?, ifnull((SELECT source FROM {MEDIA_TAGS_TABLE} \
+                    WHERE media_id = {INFO_TABLE}.id AND tag_id = ?), ?) \
+                FROM {INFO_TABLE} WHERE ({sql}) AND {has_tag}"
+            );
+            let mut query = sqlx::query(&insert)
+                .bind(id)
+                .bind(tag)
+                .bind(tag)
+                .bind(TagSource::User);
+            for param in &params {
+                query = query.bind(param);
+            }
+            query
+                .bind(tag)
+                .execute(&mut *tx)
+                .await
+                .inspect_err(|e| tracing::error!("Failed to record tag edit changes! err: {e}"))
+                .map_err(DatabaseError::QueryFailed)?;
+        }
+
+        let changed = match kind {
+            TagEditKind::Add => add_changed(&mut tx, id).await?,
+            TagEditKind::Remove => remove_changed(&mut tx, id).await?,
+        };
+
+        commit(tx).await?;
+        Ok(TagEdit {
+            id,
+            kind,
+            tags,
+            made_at,
+            affected: changed.len() as u64,
+        })
+    }
+}
+
+impl MediaSelection {
+    /// Turns the selection into a piece of a `WHERE` clause over the
+    /// [`INFO_TABLE`].
+    fn clause(&self) -> Clause {
+        match self {
+            MediaSelection::Search(exprs) => Clause::all(exprs),
+            MediaSelection::Ids(ids) if ids.is_empty() => Clause {
+                sql: "0".into(),
+                params: Vec::new(),
+            },
+            MediaSelection::Ids(ids) => Clause {
+                sql: format!("{INFO_TABLE}.id IN ({})", vec!["?"; ids.len()].join(", ")),
+                params: ids.iter().copied().map(Param::Uuid).collect(),
+            },
+        }
+    }
+}
+
+/// Matches media that were given the tag with the UUID bound to `?`.
+fn has_tag() -> String {
+    format!(
+        "EXISTS (SELECT 1 FROM {MEDIA_TAGS_TABLE} \
+        WHERE media_id = {INFO_TABLE}.id AND tag_id = ?)"
+    )
+}
+
+/// Gives the media that the edit changed their tags (back), unless they
+/// already have them. Returns the media that changed.
+async fn add_changed(
+    conn: &mut SqliteConnection,
+    edit_id: Uuid,
+) -> Result<HashSet<Uuid>, RavesError> {
+    let changed = sqlx::query_scalar::<_, Uuid>(&format!(
+        "INSERT OR IGNORE INTO {MEDIA_TAGS_TABLE} (media_id, tag_id, source) \
+        SELECT media_id, tag_id, source FROM {TAG_EDIT_CHANGES_TABLE} \
+        WHERE edit_id = $1 ORDER BY rowid \
+        RETURNING media_id"
+    ))
+    .bind(edit_id)
+    .fetch_all(&mut *conn)
+    .await
+    .inspect_err(|e| tracing::error!("Failed to add tags to media! err: {e}"))
+    .map_err(DatabaseError::QueryFailed)?;
+
+    Ok(changed.into_iter().collect())
+}
+
+/// Takes the tags that the edit changed away from its media, if they still
+/// have them. Returns the media that changed.
+async fn remove_changed(
+    conn: &mut SqliteConnection,
+    edit_id: Uuid,
+) -> Result<HashSet<Uuid>, RavesError> {
+    let changed = sqlx::query_scalar::<_, Uuid>(&format!(
+        "DELETE FROM {MEDIA_TAGS_TABLE} WHERE (media_id, tag_id) IN \
+        (SELECT media_id, tag_id FROM {TAG_EDIT_CHANGES_TABLE} WHERE edit_id = $1) \
+        RETURNING media_id"
+    ))
+    .bind(edit_id)
+    .fetch_all(&mut *conn)
+    .await
+    .inspect_err(|e| tracing::error!("Failed to remove tags from media! err: {e}"))
+    .map_err(DatabaseError::QueryFailed)?;
+
+    Ok(changed.into_iter().collect())
+}
+
+async fn begin() -> Result<sqlx::Transaction<'static, sqlx::Sqlite>, RavesError> {
+    Ok(DATABASE.begin().await.inspect_err(|e| {
+        tracing::error!("Failed to start a transaction for tag edits. err: {e}")
+    })?)
+}
+
+async fn commit(tx: sqlx::Transaction<'static, sqlx::Sqlite>) -> Result<(), RavesError> {
+    tx.commit()
+        .await
+        .inspect_err(|e| tracing::error!("Failed to save tag edit! err: {e}"))
+        .map_err(|e| DatabaseError::QueryFailed(e).into())
+}
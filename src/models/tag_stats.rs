@@ -0,0 +1,171 @@
+//! Which tags go together, and the tag suggestions built on that.
+//!
+//! Nothing here is stored. Each call reads the [`MEDIA_TAGS_TABLE`] as it is
+//! right now, so the stats always match the library.
+//!
+//! Only the tags that media were given count. Implied tags always show up
+//! with the tags that imply them, so they'd just add noise.
+
+use std::collections::{HashMap, HashSet};
+
+use uuid::Uuid;
+
+use crate::{
+    database::{DATABASE, INFO_TABLE, MEDIA_TAGS_TABLE},
+    error::{DatabaseError, RavesError},
+    models::tags::{Tag, TagIdent},
+    search::query::{CAMERA_MODEL, CAPTURE_DATE, PARENT_FOLDER},
+};
+
+/// How much tags on media with the same tags count toward a suggestion.
+const PAIR_WEIGHT: f64 = 1.0;
+/// How much tags on media from the same day count.
+const DAY_WEIGHT: f64 = 0.75;
+/// How much tags on media in the same folder count.
+const FOLDER_WEIGHT: f64 = 0.5;
+/// How much tags on media from the same camera count. Most photos come from
+/// one or two cameras, so this one's pretty weak.
+const CAMERA_WEIGHT: f64 = 0.25;
+
+/// Two tags that media often have together.
+#[derive(Clone, Debug, PartialEq, PartialOrd)]
+pub struct TagPair {
+    pub tags: (Tag, Tag),
+    /// How many media files have both tags.
+    pub together: u64,
+    /// How alike the tags are, from `0.0` to `1.0`: the media with both, out
+    /// of the media with either.
+    pub similarity: f64,
+}
+
+/// Suggests tags for a media file, with the best ones first.
+///
+/// Each tag's score comes from how often it's used on:
+///
+/// - media with the same tags as this one,
+/// - media captured on the same day,
+/// - media in the same folder, and
+/// - media from the same camera.
+///
+/// Scores are only useful for comparing suggestions for the same media file.
+/// Tags that the media already has, including implied and excluded ones, aren't
+/// suggested.
+#[tracing::instrument]
+pub async fn suggest_tags(media_id: Uuid) -> Result<Vec<(TagIdent, f64)>, RavesError> {
+    // (these also make sure the media exists)
+    let mut has = Tag::effective_tags(media_id)
+        .await?
+        .into_iter()
+        .map(|t| t.tag.uuid)
+        .collect::<HashSet<_>>();
+    has.extend(
+        Tag::suppressed_tags(media_id)
+            .await?
+            .into_iter()
+            .map(|t| t.tag.uuid),
+    );
+
+    let mut conn = DATABASE.acquire().await.inspect_err(|e| {
+        tracing::error!("Failed to connect to database for tag suggestions. err: {e}")
+    })?;
+    let mut scores = HashMap::<TagIdent, f64>::new();
+
+    // for each of our tags, how often media with it have each other tag. these
+    // are averaged, so media with many tags don't get huge scores
+    let pairs = sqlx::query_as::<_, (TagIdent, f64)>(&format!(
+        "SELECT other.tag_id, \
+            SUM(1.0 / (SELECT COUNT(*) FROM {MEDIA_TAGS_TABLE} WHERE tag_id = mine.tag_id)) \
+            / (SELECT COUNT(*) FROM {MEDIA_TAGS_TABLE} WHERE media_id = $1) \
+        FROM {MEDIA_TAGS_TABLE} AS mine \
+        JOIN {MEDIA_TAGS_TABLE} AS theirs \
+            ON theirs.tag_id = mine.tag_id AND theirs.media_id != mine.media_id \
+        JOIN {MEDIA_TAGS_TABLE} AS other ON other.media_id = theirs.media_id \
+        WHERE mine.media_id = $1 \
+        GROUP BY other.tag_id"
+    ))
+    .bind(media_id)
+    .fetch_all(&mut *conn)
+    .await
+    .inspect_err(|e| tracing::error!("Failed to find tag pairs! err: {e}"))
+    .map_err(DatabaseError::QueryFailed)?;
+    for (tag, score) in pairs {
+        *scores.entry(tag).or_default() += PAIR_WEIGHT * score;
+    }
+
+    // then, for media near this one, the share of them with each tag
+    // (the local day from its EXIF, or else the day it was created in UTC)
+    let day = format!("COALESCE(substr({CAPTURE_DATE}, 1, 10), date({INFO_TABLE}.creation_date))");
+    let camera = format!("NULLIF({CAMERA_MODEL}, '')");
+    for (context, weight) in [
+        (day.as_str(), DAY_WEIGHT),
+        (PARENT_FOLDER, FOLDER_WEIGHT),
+        (camera.as_str(), CAMERA_WEIGHT),
+    ] {
+        let nearby = format!("{context} = (SELECT {context} FROM {INFO_TABLE} WHERE id = $1)");
+        let shares = sqlx::query_as::<_, (TagIdent, f64)>(&format!(
+            "SELECT mt.tag_id, COUNT(*) * 1.0 / \
+                (SELECT COUNT(*) FROM {INFO_TABLE} WHERE {nearby} AND id != $1) \
+            FROM {INFO_TABLE} JOIN {MEDIA_TAGS_TABLE} AS mt ON mt.media_id = {INFO_TABLE}.id \
+            WHERE {nearby} AND {INFO_TABLE}.id != $1 \
+            GROUP BY mt.tag_id"
+        ))
+        .bind(media_id)
+        .fetch_all(&mut *conn)
+        .await
+        .inspect_err(|e| tracing::error!("Failed to find tags on nearby media! err: {e}"))
+        .map_err(DatabaseError::QueryFailed)?;
+
+        for (tag, share) in shares {
+            *scores.entry(tag).or_default() += weight * share;
+        }
+    }
+
+    let mut suggestions = scores
+        .into_iter()
+        .filter(|(tag, _)| !has.contains(tag))
+        .collect::<Vec<_>>();
+    suggestions.sort_by(|(a, a_score), (b, b_score)| b_score.total_cmp(a_score).then(a.cmp(b)));
+    Ok(suggestions)
+}
+
+/// Lists the pairs of tags that are used together most across the library,
+/// up to `limit` of them.
+///
+/// Pairs on more media come first. Ties go to the pair that's more alike.
+#[tracing::instrument]
+pub async fn tags_used_together(limit: u32) -> Result<Vec<TagPair>, RavesError> {
+    let mut conn = DATABASE.acquire().await.inspect_err(|e| {
+        tracing::error!("Failed to connect to database for tag pairs. err: {e}")
+    })?;
+
+    let rows = sqlx::query_as::<_, (TagIdent, TagIdent, i64, f64)>(&format!(
+        "SELECT a.tag_id, b.tag_id, COUNT(*) AS together, COUNT(*) * 1.0 / (\
+            (SELECT COUNT(*) FROM {MEDIA_TAGS_TABLE} WHERE tag_id = a.tag_id) + \
+            (SELECT COUNT(*) FROM {MEDIA_TAGS_TABLE} WHERE tag_id = b.tag_id) - COUNT(*)\
+        ) AS similarity \
+        FROM {MEDIA_TAGS_TABLE} AS a \
+        JOIN {MEDIA_TAGS_TABLE} AS b ON b.media_id = a.media_id AND b.tag_id > a.tag_id \
+        GROUP BY a.tag_id, b.tag_id \
+        ORDER BY together DESC, similarity DESC, a.tag_id, b.tag_id \
+        LIMIT $1"
+    ))
+    .bind(limit as i64)
+    .fetch_all(&mut *conn)
+    .await
+    .inspect_err(|e| tracing::error!("Failed to find tag pairs! err: {e}"))
+    .map_err(DatabaseError::QueryFailed)?;
+
+    let mut pairs = Vec::with_capacity(rows.len());
+    for (a, b, together, similarity) in rows {
+        pairs.push(TagPair {
+            tags: (
+                Tag::get_in(&mut conn, &a).await?,
+                Tag::get_in(&mut conn, &b).await?,
+            ),
+            together: together as u64,
+            similarity,
+        });
+    }
+
+    Ok(pairs)
+}
@@ -1,7 +1,38 @@
 //! Represents tags in all their glory.
+//!
+//! Every tag lives in the catalog (the [`TAGS_TABLE`]), optionally inside a
+//! section. Media only point at their tags (in the [`MEDIA_TAGS_TABLE`]), so
+//! any edits here reach each media file that has the tag right away.
+//!
+//! Tags can imply others. Media with an implying tag also have the tags it
+//! implies, and the tags *those* imply, and so on. See
+//! [`Tag::effective_tags`].
+//!
+//! When an implied tag is wrong for a media file, it can be excluded from
+//! that file. Then, the media doesn't have it, or any tags that only came
+//! through it.
+//!
+//! Keywords that media files carry themselves are imported into the catalog,
+//! too. See [`Tag::import_keywords`].
+//!
+//! Duplicate tags can be merged into one with [`Tag::merge`]. Their old names
+//! stay around as aliases, so searching or importing them still works.
+//!
+//! To tag (or untag) lots of media at once, see [`crate::models::tag_edits`].
 
+use std::collections::{HashMap, HashSet};
+
+use sqlx::{types::Json, SqliteConnection};
 use uuid::Uuid;
 
+use crate::{
+    database::{
+        DATABASE, INFO_TABLE, MEDIA_TAGS_TABLE, TAGS_TABLE, TAG_ALIASES_TABLE,
+        TAG_EDIT_CHANGES_TABLE, TAG_EXCLUSIONS_TABLE, TAG_IMPLICATIONS_TABLE, TAG_SECTIONS_TABLE,
+    },
+    error::{DatabaseError, RavesError},
+};
+
 pub type TagIdent = String;
 
 /// A "section" for tags. When a tag has a section, it is separated from others
@@ -14,7 +45,7 @@ pub type TagIdent = String;
 /// meaning to any vacation-loving neckbeard. 🤓🫵
 #[derive(Clone, Debug, PartialEq, PartialOrd, serde::Deserialize, serde::Serialize)]
 pub struct TagSection {
-    name: String,
+    pub name: String,
 }
 
 impl Default for TagSection {
@@ -32,16 +63,16 @@ pub struct Tag {
     ///
     /// Don't use this to find the tag - EVER.
     /// The name can change, but a tag's UUID is forever static.
-    name: String,
+    pub name: String,
     /// A unique identifier.
     ///
     /// Always use this when referencing the tag externally.
-    uuid: TagIdent,
+    pub uuid: TagIdent,
     /// The section this tag belongs to.
-    tag_section: Option<TagSection>,
+    pub tag_section: Option<TagSection>,
     /// The other tags this tag "implies". For example, tags "christmas" and
     /// "halloween" would both imply the "holiday" tag.
-    implies: Vec<TagIdent>,
+    pub implies: Vec<TagIdent>,
 }
 
 #[derive(Clone, Debug, PartialEq, PartialOrd, serde::Deserialize)]
@@ -55,3 +86,1027 @@ pub struct TagSectionRecord {
     pub section: TagSection,
     pub id: Uuid,
 }
+
+/// One of a media file's tags, alongside why it has it.
+#[derive(Clone, Debug, PartialEq, PartialOrd)]
+pub struct EffectiveTag {
+    pub tag: Tag,
+    pub origin: TagOrigin,
+}
+
+/// Why a media file has a tag.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TagOrigin {
+    /// The media was given the tag directly.
+    Explicit,
+    /// Another tag implies this one. `by` is the explicit tag it came from,
+    /// even when there are other tags in between.
+    Implied { by: TagIdent },
+}
+
+/// A tag that a media file would have, but was excluded from it.
+#[derive(Clone, Debug, PartialEq, PartialOrd)]
+pub struct SuppressedTag {
+    pub tag: Tag,
+    /// The explicit tag that implies it.
+    pub by: TagIdent,
+}
+
+/// Where a media file got one of its explicit tags.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, sqlx::Type)]
+#[sqlx(rename_all = "lowercase")]
+pub enum TagSource {
+    /// It was given in the app.
+    User,
+    /// It was imported from the media file's own keywords.
+    File,
+}
+
+impl Tag {
+    /// Adds a new tag to the catalog, optionally inside a section.
+    ///
+    /// If the name was an alias of another tag, the new tag takes it over.
+    #[tracing::instrument]
+    pub async fn create(name: &str, section: Option<Uuid>) -> Result<Tag, RavesError> {
+        let name = check_name(name)?;
+        let mut tx = begin().await?;
+
+        if let Some(section) = section {
+            TagSection::get_in(&mut tx, section).await?;
+        }
+        check_tag_name(&mut tx, name, section, None).await?;
+        drop_alias(&mut tx, name, section).await?;
+
+        let id = insert_tag(&mut tx, name, section).await?;
+        let tag = Tag::get_in(&mut tx, &id).await?;
+        commit(tx).await?;
+        Ok(tag)
+    }
+
+    /// Grabs a tag from the catalog.
+    #[tracing::instrument]
+    pub async fn get(id: &TagIdent) -> Result<Tag, RavesError> {
+        let mut conn = connect().await?;
+        Tag::get_in(&mut conn, id).await
+    }
+
+    /// Lists every tag in the catalog, by section, then by name.
+    #[tracing::instrument]
+    pub async fn list() -> Result<Vec<Tag>, RavesError> {
+        let mut conn = connect().await?;
+
+        let rows = sqlx::query_as::<_, TagRow>(&format!("{SELECT_TAG} ORDER BY s.name, t.name"))
+            .fetch_all(&mut *conn)
+            .await
+            .inspect_err(|e| tracing::error!("Failed to list tags! err: {e}"))
+            .map_err(DatabaseError::QueryFailed)?;
+
+        Ok(rows.into_iter().map(Tag::from_row).collect())
+    }
+
+    /// Renames a tag. Its UUID stays the same, and every media file with the
+    /// tag sees the new name.
+    ///
+    /// If the new name was an alias of another tag, this tag takes it over.
+    #[tracing::instrument]
+    pub async fn rename(id: &TagIdent, new_name: &str) -> Result<Tag, RavesError> {
+        let new_name = check_name(new_name)?;
+        let mut tx = begin().await?;
+
+        let section = sqlx::query_scalar::<_, Option<Uuid>>(&format!(
+            "SELECT section_id FROM {TAGS_TABLE} WHERE id = $1"
+        ))
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(DatabaseError::QueryFailed)?
+        .ok_or_else(|| RavesError::TagNotFound { id: id.clone() })?;
+        check_tag_name(&mut tx, new_name, section, Some(id)).await?;
+        drop_alias(&mut tx, new_name, section).await?;
+
+        sqlx::query(&format!("UPDATE {TAGS_TABLE} SET name = $1 WHERE id = $2"))
+            .bind(new_name)
+            .bind(id)
+            .execute(&mut *tx)
+            .await
+            .inspect_err(|e| tracing::error!("Failed to rename tag! err: {e}"))
+            .map_err(DatabaseError::QueryFailed)?;
+
+        let tag = Tag::get_in(&mut tx, id).await?;
+        commit(tx).await?;
+        Ok(tag)
+    }
+
+    /// Removes a tag from the catalog and from every media file that has it.
+    #[tracing::instrument]
+    pub async fn delete(id: &TagIdent) -> Result<(), RavesError> {
+        let mut tx = begin().await?;
+        Tag::get_in(&mut tx, id).await?;
+
+        // tags that implied this one don't anymore
+        sqlx::query(&format!(
+            "DELETE FROM {TAG_IMPLICATIONS_TABLE} WHERE tag_id = $1 OR implied_id = $1"
+        ))
+        .bind(id)
+        .execute(&mut *tx)
+        .await
+        .inspect_err(|e| tracing::error!("Failed to remove deleted tag's implications! err: {e}"))
+        .map_err(DatabaseError::QueryFailed)?;
+
+        for table in [
+            MEDIA_TAGS_TABLE,
+            TAG_EXCLUSIONS_TABLE,
+            TAG_ALIASES_TABLE,
+            TAG_EDIT_CHANGES_TABLE,
+        ] {
+            sqlx::query(&format!("DELETE FROM {table} WHERE tag_id = $1"))
+                .bind(id)
+                .execute(&mut *tx)
+                .await
+                .map_err(DatabaseError::QueryFailed)?;
+        }
+
+        sqlx::query(&format!("DELETE FROM {TAGS_TABLE} WHERE id = $1"))
+            .bind(id)
+            .execute(&mut *tx)
+            .await
+            .inspect_err(|e| tracing::error!("Failed to delete tag! err: {e}"))
+            .map_err(DatabaseError::QueryFailed)?;
+
+        commit(tx).await
+    }
+
+    /// Grabs a tag from the catalog, using an existing connection.
+    pub(crate) async fn get_in(
+        conn: &mut SqliteConnection,
+        id: &TagIdent,
+    ) -> Result<Tag, RavesError> {
+        let row = sqlx::query_as::<_, TagRow>(&format!("{SELECT_TAG} WHERE t.id = $1"))
+            .bind(id)
+            .fetch_optional(&mut *conn)
+            .await
+            .inspect_err(|e| tracing::error!("Failed to get tag! err: {e}"))
+            .map_err(DatabaseError::QueryFailed)?;
+
+        row.map(Tag::from_row)
+            .ok_or_else(|| RavesError::TagNotFound { id: id.clone() })
+    }
+
+    fn from_row((uuid, name, section, implies): TagRow) -> Tag {
+        Tag {
+            name,
+            uuid,
+            tag_section: section.map(|name| TagSection { name }),
+            implies: implies.0,
+        }
+    }
+
+    /// Replaces the tags that this tag directly implies.
+    ///
+    /// This fails if it'd make a cycle, like "christmas" implying "holiday"
+    /// implying "christmas".
+    #[tracing::instrument]
+    pub async fn set_implies(id: &TagIdent, implies: &[TagIdent]) -> Result<Tag, RavesError> {
+        let mut tx = begin().await?;
+        Tag::get_in(&mut tx, id).await?;
+        for implied in implies {
+            Tag::get_in(&mut tx, implied).await?;
+        }
+
+        // check the graph with the new edges before changing anything
+        let mut graph = implication_graph(&mut tx).await?;
+        graph.insert(id.clone(), implies.to_vec());
+        if let Some(cycle) = find_cycle(&graph, id) {
+            return Err(cycle_error(&mut tx, &cycle).await);
+        }
+
+        sqlx::query(&format!(
+            "DELETE FROM {TAG_IMPLICATIONS_TABLE} WHERE tag_id = $1"
+        ))
+        .bind(id)
+        .execute(&mut *tx)
+        .await
+        .map_err(DatabaseError::QueryFailed)?;
+
+        for implied in implies {
+            sqlx::query(&format!(
+                "INSERT OR IGNORE INTO {TAG_IMPLICATIONS_TABLE} (tag_id, implied_id) VALUES ($1, $2)"
+            ))
+            .bind(id)
+            .bind(implied)
+            .execute(&mut *tx)
+            .await
+            .inspect_err(|e| tracing::error!("Failed to add tag implication! err: {e}"))
+            .map_err(DatabaseError::QueryFailed)?;
+        }
+
+        let tag = Tag::get_in(&mut tx, id).await?;
+        commit(tx).await?;
+        Ok(tag)
+    }
+
+    /// Merges the `sources` into the `target` tag, all at once.
+    ///
+    /// Media with a source tag get the target instead, and implications to or
+    /// from a source now use the target. The sources are then deleted, but
+    /// their names become aliases of the target, so searches and imports
+    /// still find it.
+    ///
+    /// This fails (changing nothing) if the merged implications would make a
+    /// cycle.
+    #[tracing::instrument]
+    pub async fn merge(sources: &[TagIdent], target: &TagIdent) -> Result<Tag, RavesError> {
+        let mut tx = begin().await?;
+        Tag::get_in(&mut tx, target).await?;
+
+        for source in sources.iter().filter(|source| *source != target) {
+            Tag::get_in(&mut tx, source).await?;
+
+            // media with the source swap it for the target. (media that
+            // already have the target just keep it, so they're cleaned up
+            // below)
+            for table in [
+                MEDIA_TAGS_TABLE,
+                TAG_EXCLUSIONS_TABLE,
+                TAG_ALIASES_TABLE,
+                TAG_EDIT_CHANGES_TABLE,
+            ] {
+                sqlx::query(&format!(
+                    "UPDATE OR IGNORE {table} SET tag_id = $2 WHERE tag_id = $1"
+                ))
+                .bind(source)
+                .bind(target)
+                .execute(&mut *tx)
+                .await
+                .map_err(DatabaseError::QueryFailed)?;
+            }
+
+            // the target implies what the source did, and is implied by
+            // what implied the source
+            sqlx::query(&format!(
+                "INSERT OR IGNORE INTO {TAG_IMPLICATIONS_TABLE} (tag_id, implied_id) \
+                SELECT $2, implied_id FROM {TAG_IMPLICATIONS_TABLE} \
+                WHERE tag_id = $1 AND implied_id IS NOT $2 \
+                UNION ALL \
+                SELECT tag_id, $2 FROM {TAG_IMPLICATIONS_TABLE} \
+                WHERE implied_id = $1 AND tag_id IS NOT $2"
+            ))
+            .bind(source)
+            .bind(target)
+            .execute(&mut *tx)
+            .await
+            .inspect_err(|e| tracing::error!("Failed to merge tag implications! err: {e}"))
+            .map_err(DatabaseError::QueryFailed)?;
+            sqlx::query(&format!(
+                "DELETE FROM {TAG_IMPLICATIONS_TABLE} WHERE tag_id = $1 OR implied_id = $1"
+            ))
+            .bind(source)
+            .execute(&mut *tx)
+            .await
+            .map_err(DatabaseError::QueryFailed)?;
+
+            // finally, the source's name becomes an alias
+            sqlx::query(&format!(
+                "INSERT OR REPLACE INTO {TAG_ALIASES_TABLE} (name, section_id, tag_id) \
+                SELECT name, section_id, $2 FROM {TAGS_TABLE} WHERE id = $1"
+            ))
+            .bind(source)
+            .bind(target)
+            .execute(&mut *tx)
+            .await
+            .inspect_err(|e| tracing::error!("Failed to add tag alias! err: {e}"))
+            .map_err(DatabaseError::QueryFailed)?;
+
+            for table in [
+                MEDIA_TAGS_TABLE,
+                TAG_EXCLUSIONS_TABLE,
+                TAG_EDIT_CHANGES_TABLE,
+            ] {
+                sqlx::query(&format!("DELETE FROM {table} WHERE tag_id = $1"))
+                    .bind(source)
+                    .execute(&mut *tx)
+                    .await
+                    .map_err(DatabaseError::QueryFailed)?;
+            }
+            sqlx::query(&format!("DELETE FROM {TAGS_TABLE} WHERE id = $1"))
+                .bind(source)
+                .execute(&mut *tx)
+                .await
+                .inspect_err(|e| tracing::error!("Failed to delete merged tag! err: {e}"))
+                .map_err(DatabaseError::QueryFailed)?;
+        }
+
+        let graph = implication_graph(&mut tx).await?;
+        if let Some(cycle) = find_cycle(&graph, target) {
+            return Err(cycle_error(&mut tx, &cycle).await);
+        }
+
+        let tag = Tag::get_in(&mut tx, target).await?;
+        commit(tx).await?;
+        Ok(tag)
+    }
+
+    /// Lists the old names of tags that were merged into this one.
+    #[tracing::instrument]
+    pub async fn aliases(id: &TagIdent) -> Result<Vec<String>, RavesError> {
+        let mut conn = connect().await?;
+        Tag::get_in(&mut conn, id).await?;
+
+        sqlx::query_scalar::<_, String>(&format!(
+            "SELECT name FROM {TAG_ALIASES_TABLE} WHERE tag_id = $1 ORDER BY name"
+        ))
+        .bind(id)
+        .fetch_all(&mut *conn)
+        .await
+        .inspect_err(|e| tracing::error!("Failed to list tag aliases! err: {e}"))
+        .map_err(|e| DatabaseError::QueryFailed(e).into())
+    }
+
+    /// Finds every tag that this one implies, directly or not.
+    #[tracing::instrument]
+    pub async fn implied(id: &TagIdent) -> Result<Vec<Tag>, RavesError> {
+        let mut conn = connect().await?;
+        Tag::get_in(&mut conn, id).await?;
+
+        let ids = sqlx::query_scalar::<_, String>(&format!(
+            "WITH RECURSIVE implied(id) AS (\
+                SELECT implied_id FROM {TAG_IMPLICATIONS_TABLE} WHERE tag_id = $1 \
+                UNION \
+                SELECT i.implied_id FROM {TAG_IMPLICATIONS_TABLE} AS i JOIN implied ON i.tag_id = implied.id\
+            ) SELECT id FROM implied"
+        ))
+        .bind(id)
+        .fetch_all(&mut *conn)
+        .await
+        .inspect_err(|e| tracing::error!("Failed to find implied tags! err: {e}"))
+        .map_err(DatabaseError::QueryFailed)?;
+
+        let mut tags = Vec::with_capacity(ids.len());
+        for id in ids {
+            tags.push(Tag::get_in(&mut conn, &id).await?);
+        }
+        Ok(tags)
+    }
+
+    /// Finds every tag a media file has: the ones it was given, then the
+    /// ones they imply.
+    ///
+    /// Implied tags that were excluded from the media are skipped. (Tags that
+    /// were given to the media directly can't be excluded.)
+    #[tracing::instrument]
+    pub async fn effective_tags(media_id: Uuid) -> Result<Vec<EffectiveTag>, RavesError> {
+        let mut conn = connect().await?;
+        let explicit = explicit_tags(&mut conn, media_id).await?;
+
+        let mut seen = explicit
+            .iter()
+            .map(|tag| tag.uuid.clone())
+            .collect::<HashSet<_>>();
+        let mut effective = explicit
+            .into_iter()
+            .map(|tag| EffectiveTag {
+                tag,
+                origin: TagOrigin::Explicit,
+            })
+            .collect::<Vec<_>>();
+
+        for (id, by, excluded) in implications(&mut conn, media_id).await? {
+            if !excluded && seen.insert(id.clone()) {
+                effective.push(EffectiveTag {
+                    tag: Tag::get_in(&mut conn, &id).await?,
+                    origin: TagOrigin::Implied { by },
+                });
+            }
+        }
+
+        Ok(effective)
+    }
+
+    /// Finds the implied tags that were excluded from a media file, with the
+    /// tags that implied them.
+    #[tracing::instrument]
+    pub async fn suppressed_tags(media_id: Uuid) -> Result<Vec<SuppressedTag>, RavesError> {
+        let mut conn = connect().await?;
+        let explicit = explicit_tags(&mut conn, media_id).await?;
+
+        let mut seen = explicit
+            .into_iter()
+            .map(|tag| tag.uuid)
+            .collect::<HashSet<_>>();
+        let mut suppressed = Vec::new();
+        for (id, by, excluded) in implications(&mut conn, media_id).await? {
+            if excluded && seen.insert(id.clone()) {
+                suppressed.push(SuppressedTag {
+                    tag: Tag::get_in(&mut conn, &id).await?,
+                    by,
+                });
+            }
+        }
+
+        Ok(suppressed)
+    }
+
+    /// Says that the given media shouldn't have this tag, even when their
+    /// other tags imply it.
+    ///
+    /// If any of the media don't exist, nothing is excluded.
+    #[tracing::instrument]
+    pub async fn exclude(id: &TagIdent, media_ids: &[Uuid]) -> Result<(), RavesError> {
+        let mut tx = begin().await?;
+        Tag::get_in(&mut tx, id).await?;
+
+        for media_id in media_ids {
+            ensure_media_exists(&mut tx, *media_id).await?;
+            sqlx::query(&format!(
+                "INSERT OR IGNORE INTO {TAG_EXCLUSIONS_TABLE} (media_id, tag_id) VALUES ($1, $2)"
+            ))
+            .bind(media_id)
+            .bind(id)
+            .execute(&mut *tx)
+            .await
+            .inspect_err(|e| tracing::error!("Failed to exclude tag! err: {e}"))
+            .map_err(DatabaseError::QueryFailed)?;
+        }
+
+        commit(tx).await
+    }
+
+    /// Undoes [`Tag::exclude`], so the given media have this tag again when
+    /// it's implied.
+    ///
+    /// If any of the media don't exist, nothing is unexcluded.
+    #[tracing::instrument]
+    pub async fn unexclude(id: &TagIdent, media_ids: &[Uuid]) -> Result<(), RavesError> {
+        let mut tx = begin().await?;
+        Tag::get_in(&mut tx, id).await?;
+
+        for media_id in media_ids {
+            ensure_media_exists(&mut tx, *media_id).await?;
+            sqlx::query(&format!(
+                "DELETE FROM {TAG_EXCLUSIONS_TABLE} WHERE media_id = $1 AND tag_id = $2"
+            ))
+            .bind(media_id)
+            .bind(id)
+            .execute(&mut *tx)
+            .await
+            .inspect_err(|e| tracing::error!("Failed to unexclude tag! err: {e}"))
+            .map_err(DatabaseError::QueryFailed)?;
+        }
+
+        commit(tx).await
+    }
+}
+
+impl Tag {
+    /// Finds a tag for each keyword read from a media file, making any that
+    /// aren't in the catalog yet. New tags go in the section with the given
+    /// name, which is made too, if needed.
+    ///
+    /// The media remembers which tags came from its file (see
+    /// [`TagSource::File`]). Importing the same file again swaps those for its
+    /// current keywords, so nothing is doubled up.
+    ///
+    /// Returns the imported tags, then the ids of tags that the media
+    /// imported last time, but its file no longer has.
+    #[tracing::instrument]
+    pub async fn import_keywords(
+        media_id: Uuid,
+        keywords: &[String],
+        section: Option<&TagSection>,
+    ) -> Result<(Vec<Tag>, Vec<TagIdent>), RavesError> {
+        let mut tx = begin().await?;
+
+        let section = match section {
+            Some(section) if !keywords.is_empty() => {
+                Some(section_named(&mut tx, &section.name).await?)
+            }
+            _ => None,
+        };
+
+        let mut imported: Vec<Tag> = Vec::new();
+        for keyword in keywords {
+            let Ok(name) = check_name(keyword) else {
+                continue;
+            };
+
+            // real names win over aliases
+            let existing = sqlx::query_scalar::<_, String>(&format!(
+                "SELECT id FROM {TAGS_TABLE} WHERE name = $1 AND section_id IS $2 \
+                UNION ALL \
+                SELECT tag_id FROM {TAG_ALIASES_TABLE} WHERE name = $1 AND section_id IS $2 \
+                LIMIT 1"
+            ))
+            .bind(name)
+            .bind(section)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(DatabaseError::QueryFailed)?;
+            let id = match existing {
+                Some(id) => id,
+                None => insert_tag(&mut tx, name, section).await?,
+            };
+
+            if !imported.iter().any(|tag| tag.uuid == id) {
+                imported.push(Tag::get_in(&mut tx, &id).await?);
+            }
+        }
+
+        let mut forgotten = sqlx::query_scalar::<_, String>(&format!(
+            "SELECT tag_id FROM {MEDIA_TAGS_TABLE} WHERE media_id = $1 AND source = $2"
+        ))
+        .bind(media_id)
+        .bind(TagSource::File)
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(DatabaseError::QueryFailed)?;
+        forgotten.retain(|id| !imported.iter().any(|tag| tag.uuid == *id));
+
+        for id in &forgotten {
+            sqlx::query(&format!(
+                "DELETE FROM {MEDIA_TAGS_TABLE} WHERE media_id = $1 AND tag_id = $2"
+            ))
+            .bind(media_id)
+            .bind(id)
+            .execute(&mut *tx)
+            .await
+            .inspect_err(|e| tracing::error!("Failed to forget imported tag! err: {e}"))
+            .map_err(DatabaseError::QueryFailed)?;
+        }
+
+        // tags the media already had stay where they were (and if they were
+        // given in the app, they still count as that)
+        for tag in &imported {
+            sqlx::query(&format!(
+                "INSERT OR IGNORE INTO {MEDIA_TAGS_TABLE} (media_id, tag_id, source) \
+                VALUES ($1, $2, $3)"
+            ))
+            .bind(media_id)
+            .bind(&tag.uuid)
+            .bind(TagSource::File)
+            .execute(&mut *tx)
+            .await
+            .inspect_err(|e| tracing::error!("Failed to remember imported tag! err: {e}"))
+            .map_err(DatabaseError::QueryFailed)?;
+        }
+
+        commit(tx).await?;
+        Ok((imported, forgotten))
+    }
+}
+
+/// Fails with [`RavesError::MediaDoesntExist`] unless the media is in the
+/// [`INFO_TABLE`].
+pub(crate) async fn ensure_media_exists(
+    conn: &mut SqliteConnection,
+    media_id: Uuid,
+) -> Result<(), RavesError> {
+    let exists = sqlx::query_scalar::<_, bool>(&format!(
+        "SELECT EXISTS (SELECT 1 FROM {INFO_TABLE} WHERE id = $1)"
+    ))
+    .bind(media_id)
+    .fetch_one(&mut *conn)
+    .await
+    .inspect_err(|e| tracing::error!("Failed to check that media exists! err: {e}"))
+    .map_err(DatabaseError::QueryFailed)?;
+
+    match exists {
+        true => Ok(()),
+        false => Err(RavesError::MediaDoesntExist {
+            path: media_id.to_string(),
+        }),
+    }
+}
+
+/// The tags that a media file was given directly, in order.
+async fn explicit_tags(
+    conn: &mut SqliteConnection,
+    media_id: Uuid,
+) -> Result<Vec<Tag>, RavesError> {
+    ensure_media_exists(conn, media_id).await?;
+
+    let rows = sqlx::query_as::<_, TagRow>(&format!(
+        "{SELECT_TAG} JOIN {MEDIA_TAGS_TABLE} AS mt ON mt.tag_id = t.id \
+        WHERE mt.media_id = $1 ORDER BY mt.rowid"
+    ))
+    .bind(media_id)
+    .fetch_all(&mut *conn)
+    .await
+    .inspect_err(|e| tracing::error!("Failed to get media tags! err: {e}"))
+    .map_err(DatabaseError::QueryFailed)?;
+
+    Ok(rows.into_iter().map(Tag::from_row).collect())
+}
+
+/// Walks the implications of a media file's tags, giving each implied tag,
+/// the explicit tag it came from, and whether it was excluded from the media.
+///
+/// The walk stops at excluded tags. Shorter paths come first, then tags are
+/// in the order they were implied.
+async fn implications(
+    conn: &mut SqliteConnection,
+    media_id: Uuid,
+) -> Result<Vec<(TagIdent, TagIdent, bool)>, RavesError> {
+    // (no path is longer than the number of implications, so that stops it
+    // from looping forever)
+    sqlx::query_as::<_, (String, String, bool)>(&format!(
+        "WITH RECURSIVE implied(id, by, depth, excluded, position) AS (\
+            SELECT i.implied_id, mt.tag_id, 1, {EXCLUDED}, i.rowid \
+            FROM {MEDIA_TAGS_TABLE} AS mt \
+            JOIN {TAG_IMPLICATIONS_TABLE} AS i ON i.tag_id = mt.tag_id \
+            WHERE mt.media_id = $1 \
+            UNION \
+            SELECT i.implied_id, implied.by, implied.depth + 1, {EXCLUDED}, i.rowid \
+            FROM {TAG_IMPLICATIONS_TABLE} AS i JOIN implied ON i.tag_id = implied.id \
+            WHERE NOT implied.excluded \
+            AND implied.depth < (SELECT COUNT(*) FROM {TAG_IMPLICATIONS_TABLE})\
+        ) SELECT id, by, excluded FROM implied ORDER BY depth, position"
+    ))
+    .bind(media_id)
+    .fetch_all(&mut *conn)
+    .await
+    .inspect_err(|e| tracing::error!("Failed to find implied tags! err: {e}"))
+    .map_err(|e| DatabaseError::QueryFailed(e).into())
+}
+
+/// Whether the implied tag, `i.implied_id`, was excluded from media `$1`.
+const EXCLUDED: &str = "i.implied_id IN (SELECT tag_id FROM tag_exclusions WHERE media_id = $1)";
+
+impl TagSection {
+    /// Adds a new, empty section to the catalog.
+    #[tracing::instrument]
+    pub async fn create(name: &str) -> Result<TagSectionRecord, RavesError> {
+        let name = check_name(name)?;
+        let mut tx = begin().await?;
+        check_section_name(&mut tx, name).await?;
+
+        let id = insert_section(&mut tx, name).await?;
+        commit(tx).await?;
+        Ok(TagSectionRecord {
+            section: TagSection { name: name.into() },
+            id,
+        })
+    }
+
+    /// Lists every section in the catalog, by name.
+    #[tracing::instrument]
+    pub async fn list() -> Result<Vec<TagSectionRecord>, RavesError> {
+        let mut conn = connect().await?;
+
+        let rows = sqlx::query_as::<_, (Uuid, String)>(&format!(
+            "SELECT id, name FROM {TAG_SECTIONS_TABLE} ORDER BY name"
+        ))
+        .fetch_all(&mut *conn)
+        .await
+        .inspect_err(|e| tracing::error!("Failed to list tag sections! err: {e}"))
+        .map_err(DatabaseError::QueryFailed)?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(id, name)| TagSectionRecord {
+                section: TagSection { name },
+                id,
+            })
+            .collect())
+    }
+
+    /// Renames a section. Its tags (and the media that have them) follow
+    /// along.
+    #[tracing::instrument]
+    pub async fn rename(id: Uuid, new_name: &str) -> Result<TagSectionRecord, RavesError> {
+        let new_name = check_name(new_name)?;
+        let mut tx = begin().await?;
+
+        let old = TagSection::get_in(&mut tx, id).await?;
+        if !old.name.eq_ignore_ascii_case(new_name) {
+            check_section_name(&mut tx, new_name).await?;
+        }
+
+        sqlx::query(&format!(
+            "UPDATE {TAG_SECTIONS_TABLE} SET name = $1 WHERE id = $2"
+        ))
+        .bind(new_name)
+        .bind(id)
+        .execute(&mut *tx)
+        .await
+        .inspect_err(|e| tracing::error!("Failed to rename tag section! err: {e}"))
+        .map_err(DatabaseError::QueryFailed)?;
+
+        commit(tx).await?;
+        Ok(TagSectionRecord {
+            section: TagSection {
+                name: new_name.into(),
+            },
+            id,
+        })
+    }
+
+    /// Removes an empty section from the catalog.
+    #[tracing::instrument]
+    pub async fn delete(id: Uuid) -> Result<(), RavesError> {
+        let mut tx = begin().await?;
+        let section = TagSection::get_in(&mut tx, id).await?;
+
+        let tags = sqlx::query_scalar::<_, i64>(&format!(
+            "SELECT COUNT(*) FROM {TAGS_TABLE} WHERE section_id = $1"
+        ))
+        .bind(id)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(DatabaseError::QueryFailed)?;
+        if tags > 0 {
+            return Err(RavesError::TagSectionNotEmpty {
+                name: section.name,
+                tags: tags as u64,
+            });
+        }
+
+        sqlx::query(&format!(
+            "DELETE FROM {TAG_ALIASES_TABLE} WHERE section_id = $1"
+        ))
+        .bind(id)
+        .execute(&mut *tx)
+        .await
+        .map_err(DatabaseError::QueryFailed)?;
+
+        sqlx::query(&format!("DELETE FROM {TAG_SECTIONS_TABLE} WHERE id = $1"))
+            .bind(id)
+            .execute(&mut *tx)
+            .await
+            .inspect_err(|e| tracing::error!("Failed to delete tag section! err: {e}"))
+            .map_err(DatabaseError::QueryFailed)?;
+
+        commit(tx).await
+    }
+
+    async fn get_in(conn: &mut SqliteConnection, id: Uuid) -> Result<TagSection, RavesError> {
+        sqlx::query_scalar::<_, String>(&format!(
+            "SELECT name FROM {TAG_SECTIONS_TABLE} WHERE id = $1"
+        ))
+        .bind(id)
+        .fetch_optional(&mut *conn)
+        .await
+        .inspect_err(|e| tracing::error!("Failed to get tag section! err: {e}"))
+        .map_err(DatabaseError::QueryFailed)?
+        .map(|name| TagSection { name })
+        .ok_or_else(|| RavesError::TagSectionNotFound { id: id.to_string() })
+    }
+}
+
+/// A tag's id, name, section name, and the ids of the tags it implies.
+type TagRow = (String, String, Option<String>, Json<Vec<TagIdent>>);
+
+/// Selects a [`TagRow`] for each tag, as `t`.
+const SELECT_TAG: &str = "SELECT t.id, t.name, s.name, \
+    (SELECT json_group_array(implied_id) FROM \
+        (SELECT implied_id FROM tag_implications WHERE tag_id = t.id ORDER BY rowid)) \
+    FROM tags AS t LEFT JOIN tag_sections AS s ON s.id = t.section_id";
+
+/// Adds a tag to the catalog without any checks, returning its new id.
+async fn insert_tag(
+    conn: &mut SqliteConnection,
+    name: &str,
+    section: Option<Uuid>,
+) -> Result<TagIdent, RavesError> {
+    let id = Uuid::new_v4().to_string();
+    sqlx::query(&format!(
+        "INSERT INTO {TAGS_TABLE} (id, name, section_id) VALUES ($1, $2, $3)"
+    ))
+    .bind(&id)
+    .bind(name)
+    .bind(section)
+    .execute(&mut *conn)
+    .await
+    .inspect_err(|e| tracing::error!("Failed to create tag! err: {e}"))
+    .map_err(DatabaseError::QueryFailed)?;
+
+    Ok(id)
+}
+
+/// Finds the id of the section with the given name, making it if it doesn't
+/// exist.
+async fn section_named(conn: &mut SqliteConnection, name: &str) -> Result<Uuid, RavesError> {
+    let name = check_name(name)?;
+    let existing = sqlx::query_scalar::<_, Uuid>(&format!(
+        "SELECT id FROM {TAG_SECTIONS_TABLE} WHERE name = $1"
+    ))
+    .bind(name)
+    .fetch_optional(&mut *conn)
+    .await
+    .map_err(DatabaseError::QueryFailed)?;
+
+    match existing {
+        Some(id) => Ok(id),
+        None => insert_section(conn, name).await,
+    }
+}
+
+/// Adds a section to the catalog without any checks, returning its new id.
+async fn insert_section(conn: &mut SqliteConnection, name: &str) -> Result<Uuid, RavesError> {
+    let id = Uuid::new_v4();
+    sqlx::query(&format!(
+        "INSERT INTO {TAG_SECTIONS_TABLE} (id, name) VALUES ($1, $2)"
+    ))
+    .bind(id)
+    .bind(name)
+    .execute(&mut *conn)
+    .await
+    .inspect_err(|e| tracing::error!("Failed to create tag section! err: {e}"))
+    .map_err(DatabaseError::QueryFailed)?;
+
+    Ok(id)
+}
+
+/// Grabs every implication, as a map from each tag to the tags it directly
+/// implies.
+async fn implication_graph(
+    conn: &mut SqliteConnection,
+) -> Result<HashMap<TagIdent, Vec<TagIdent>>, RavesError> {
+    let edges = sqlx::query_as::<_, (String, String)>(&format!(
+        "SELECT tag_id, implied_id FROM {TAG_IMPLICATIONS_TABLE}"
+    ))
+    .fetch_all(&mut *conn)
+    .await
+    .inspect_err(|e| tracing::error!("Failed to get tag implications! err: {e}"))
+    .map_err(DatabaseError::QueryFailed)?;
+
+    let mut graph = HashMap::<_, Vec<_>>::new();
+    for (tag, implied) in edges {
+        graph.entry(tag).or_default().push(implied);
+    }
+    Ok(graph)
+}
+
+/// Explains a cycle of tag ids with their names.
+async fn cycle_error(conn: &mut SqliteConnection, cycle: &[TagIdent]) -> RavesError {
+    let mut names = Vec::with_capacity(cycle.len());
+    for tag in cycle {
+        match Tag::get_in(conn, tag).await {
+            Ok(tag) => names.push(format!("`{}`", tag.name)),
+            Err(e) => return e,
+        }
+    }
+
+    RavesError::TagImplicationCycle {
+        cycle: names.join(" -> "),
+    }
+}
+
+/// Looks for a path of implications from `start` back to itself. When there
+/// is one, it's returned, starting and ending with `start`.
+fn find_cycle(graph: &HashMap<TagIdent, Vec<TagIdent>>, start: &TagIdent) -> Option<Vec<TagIdent>> {
+    // depth-first, keeping the path to each tag we're looking at
+    let mut path = vec![start.clone()];
+    let mut stack = vec![graph.get(start).map_or(&[][..], Vec::as_slice).iter()];
+    let mut visited = HashSet::new();
+
+    while let Some(next) = stack.last_mut() {
+        let Some(tag) = next.next() else {
+            stack.pop();
+            path.pop();
+            continue;
+        };
+
+        if tag == start {
+            path.push(tag.clone());
+            return Some(path);
+        }
+        if visited.insert(tag) {
+            path.push(tag.clone());
+            stack.push(graph.get(tag).map_or(&[][..], Vec::as_slice).iter());
+        }
+    }
+
+    None
+}
+
+/// Names can't be blank. Whitespace around them is ignored.
+fn check_name(name: &str) -> Result<&str, RavesError> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(RavesError::EmptyTagName);
+    }
+    Ok(name)
+}
+
+/// Makes sure no other tag in the section has this name.
+async fn check_tag_name(
+    conn: &mut SqliteConnection,
+    name: &str,
+    section: Option<Uuid>,
+    except: Option<&TagIdent>,
+) -> Result<(), RavesError> {
+    let taken = sqlx::query_scalar::<_, bool>(&format!(
+        "SELECT EXISTS (SELECT 1 FROM {TAGS_TABLE} \
+        WHERE name = $1 AND section_id IS $2 AND id IS NOT $3)"
+    ))
+    .bind(name)
+    .bind(section)
+    .bind(except)
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(DatabaseError::QueryFailed)?;
+
+    if taken {
+        return Err(RavesError::TagNameTaken { name: name.into() });
+    }
+    Ok(())
+}
+
+/// Removes an alias from the section, so a real tag can have its name.
+///
+/// Otherwise, the name would find both tags.
+async fn drop_alias(
+    conn: &mut SqliteConnection,
+    name: &str,
+    section: Option<Uuid>,
+) -> Result<(), RavesError> {
+    sqlx::query(&format!(
+        "DELETE FROM {TAG_ALIASES_TABLE} WHERE name = $1 AND section_id IS $2"
+    ))
+    .bind(name)
+    .bind(section)
+    .execute(&mut *conn)
+    .await
+    .inspect_err(|e| tracing::error!("Failed to remove tag alias! err: {e}"))
+    .map_err(DatabaseError::QueryFailed)?;
+    Ok(())
+}
+
+/// Makes sure no other section has this name.
+async fn check_section_name(conn: &mut SqliteConnection, name: &str) -> Result<(), RavesError> {
+    let taken = sqlx::query_scalar::<_, bool>(&format!(
+        "SELECT EXISTS (SELECT 1 FROM {TAG_SECTIONS_TABLE} WHERE name = $1)"
+    ))
+    .bind(name)
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(DatabaseError::QueryFailed)?;
+
+    if taken {
+        return Err(RavesError::TagSectionNameTaken { name: name.into() });
+    }
+    Ok(())
+}
+
+async fn connect() -> Result<sqlx::pool::PoolConnection<sqlx::Sqlite>, RavesError> {
+    Ok(DATABASE
+        .acquire()
+        .await
+        .inspect_err(|e| tracing::error!("Failed to connect to database for tags. err: {e}"))?)
+}
+
+async fn begin() -> Result<sqlx::Transaction<'static, sqlx::Sqlite>, RavesError> {
+    Ok(DATABASE
+        .begin()
+        .await
+        .inspect_err(|e| tracing::error!("Failed to start a transaction for tags. err: {e}"))?)
+}
+
+async fn commit(tx: sqlx::Transaction<'static, sqlx::Sqlite>) -> Result<(), RavesError> {
+    tx.commit()
+        .await
+        .inspect_err(|e| tracing::error!("Failed to save tag changes! err: {e}"))
+        .map_err(|e| DatabaseError::QueryFailed(e).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph(edges: &[(&str, &str)]) -> HashMap<TagIdent, Vec<TagIdent>> {
+        let mut graph = HashMap::<_, Vec<_>>::new();
+        for (tag, implied) in edges {
+            graph
+                .entry(tag.to_string())
+                .or_default()
+                .push(implied.to_string());
+        }
+        graph
+    }
+
+    #[test]
+    fn cycles_are_found() {
+        let g = graph(&[("a", "b"), ("b", "c"), ("c", "a"), ("c", "d")]);
+        assert_eq!(find_cycle(&g, &"a".into()).unwrap(), ["a", "b", "c", "a"]);
+
+        // self-implication is the smallest cycle
+        let g = graph(&[("a", "a")]);
+        assert_eq!(find_cycle(&g, &"a".into()).unwrap(), ["a", "a"]);
+    }
+
+    #[test]
+    fn diamonds_arent_cycles() {
+        // christmas -> {holiday, winter} -> season
+        let g = graph(&[
+            ("christmas", "holiday"),
+            ("christmas", "winter"),
+            ("holiday", "season"),
+            ("winter", "season"),
+        ]);
+        assert_eq!(find_cycle(&g, &"christmas".into()), None);
+
+        // cycles that don't go through the start aren't its problem
+        let g = graph(&[("a", "b"), ("b", "c"), ("c", "b")]);
+        assert_eq!(find_cycle(&g, &"a".into()), None);
+    }
+}
@@ -18,7 +18,7 @@ use ffmpeg_next::util::frame::video::Video;
 
 use crate::{
     config::Config,
-    database::DATABASE,
+    database::{DATABASE, INFO_TABLE, MEDIA_COLUMNS},
     error::{RavesError, ThumbnailError},
     models::media::{metadata::SpecificMetadata, Media},
 };
@@ -226,10 +226,12 @@ impl Thumbnail {
     async fn get_media(&self) -> Result<Media, RavesError> {
         let mut conn = DATABASE.acquire().await?;
 
-        let media = sqlx::query_as::<_, Media>("SELECT * FROM info WHERE id = $1")
-            .bind(self.media_id)
-            .fetch_one(&mut *conn)
-            .await?;
+        let media = sqlx::query_as::<_, Media>(&format!(
+            "SELECT {MEDIA_COLUMNS} FROM {INFO_TABLE} WHERE id = $1"
+        ))
+        .bind(self.media_id)
+        .fetch_one(&mut *conn)
+        .await?;
 
         Ok(media)
     }
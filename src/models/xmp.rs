@@ -0,0 +1,635 @@
+//! Reads and writes XMP, the XML metadata that photo apps share.
+//!
+//! We export what users say about their media into XMP sidecars (like
+//! `photo.jpg.xmp`), so apps like digiKam and Lightroom see it too. When a
+//! sidecar already exists, it's merged with: the properties we write are
+//! replaced or added to, and everything else is left alone.
+
+use camino::{Utf8Path, Utf8PathBuf};
+use uuid::Uuid;
+
+use crate::{
+    database::{DATABASE, INFO_TABLE},
+    error::{DatabaseError, RavesError},
+    models::{rating::Rating, tags::Tag},
+};
+
+/// What we write into a media file's sidecar.
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Sidecar {
+    /// Tag names, as `dc:subject`.
+    pub subjects: Vec<String>,
+    /// Tags with their section in front, like `animals|cat`, as
+    /// `lr:hierarchicalSubject`.
+    pub hierarchical_subjects: Vec<String>,
+    /// Stars, as `xmp:Rating`. When this is `None`, the media is unrated, so
+    /// any rating the sidecar already has is kept.
+    pub rating: Option<u8>,
+    /// A caption, as `dc:description`.
+    pub description: Option<String>,
+    /// Names of the people in the media, as `Iptc4xmpExt:PersonInImage`.
+    pub people: Vec<String>,
+}
+
+/// Namespaces of the properties we write.
+const NAMESPACES: &[(&str, &str)] = &[
+    ("dc", "http://purl.org/dc/elements/1.1/"),
+    ("xmp", "http://ns.adobe.com/xap/1.0/"),
+    ("lr", "http://ns.adobe.com/lightroom/1.0/"),
+    ("Iptc4xmpExt", "http://iptc.org/std/Iptc4xmpExt/2008-02-29/"),
+];
+
+/// Every property we write. These are replaced when merging.
+const PROPERTIES: &[&str] = &[
+    "dc:subject",
+    "lr:hierarchicalSubject",
+    "xmp:Rating",
+    "dc:description",
+    "Iptc4xmpExt:PersonInImage",
+];
+
+/// An empty XMP document, for media without a sidecar.
+const EMPTY: &str = "<x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\n \
+    <rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">\n  \
+    <rdf:Description rdf:about=\"\">\n  \
+    </rdf:Description>\n \
+    </rdf:RDF>\n\
+    </x:xmpmeta>\n";
+
+impl Sidecar {
+    /// Gathers everything we know about a media file: its effective tags
+    /// (so, with implied tags, but not excluded ones) and its rating.
+    #[tracing::instrument]
+    pub async fn for_media(media_id: Uuid) -> Result<Sidecar, RavesError> {
+        let tags = Tag::effective_tags(media_id).await?;
+        let rating = Rating::get(media_id).await?;
+
+        Ok(Sidecar {
+            subjects: tags.iter().map(|t| t.tag.name.clone()).collect(),
+            hierarchical_subjects: tags
+                .iter()
+                .map(|t| match t.tag.tag_section {
+                    Some(ref section) => format!("{}|{}", section.name, t.tag.name),
+                    None => t.tag.name.clone(),
+                })
+                .collect(),
+            rating: (rating.stars > 0).then_some(rating.stars),
+            // NOTE: media don't have descriptions or people yet, so the app
+            // has to fill these in
+            description: None,
+            people: Vec::new(),
+        })
+    }
+
+    /// Merges this into an existing XMP document, or a new one when there
+    /// isn't one.
+    ///
+    /// Lists keep what the document had, then add ours. The rating and
+    /// description are ours if we have them, and the document's otherwise.
+    /// Our description only replaces the default language's, so captions in
+    /// other languages stay.
+    ///
+    /// Returns `None` when the existing document isn't XMP we can read.
+    pub fn merge(&self, existing: Option<&str>) -> Option<String> {
+        let existing = match existing {
+            Some(xmp) if xmp.contains("<rdf:Description") => xmp,
+            Some(_) => return None,
+            None => EMPTY,
+        };
+
+        let union = |property: &str, ours: &[String]| {
+            let mut items = rdf_items(existing, property);
+            for item in ours {
+                if !items.contains(item) {
+                    items.push(item.clone());
+                }
+            }
+            items
+        };
+        let mut merged = Sidecar {
+            subjects: union("dc:subject", &self.subjects),
+            hierarchical_subjects: union("lr:hierarchicalSubject", &self.hierarchical_subjects),
+            rating: self.rating,
+            description: self.description.clone(),
+            people: union("Iptc4xmpExt:PersonInImage", &self.people),
+        };
+
+        let mut xmp = existing.to_string();
+        for property in PROPERTIES {
+            // without a rating or description of our own, theirs stays where
+            // it is
+            match *property {
+                "xmp:Rating" if self.rating.is_none() => continue,
+                "dc:description" => match self.description {
+                    None => continue,
+                    Some(ref description) => {
+                        if let Some(replaced) = replace_default_item(&xmp, property, description) {
+                            xmp = replaced;
+                            merged.description = None;
+                            continue;
+                        }
+                    }
+                },
+                _ => (),
+            }
+            xmp = remove_property(&xmp, property);
+        }
+
+        // our properties go at the top of the first description
+        let start = xmp.find("<rdf:Description").unwrap_or_default();
+        let end = start + xmp[start..].find('>').unwrap_or_default();
+        let mut description = xmp[start..end].trim_end_matches('/').to_string();
+        for (prefix, uri) in NAMESPACES {
+            if !xmp.contains(&format!("xmlns:{prefix}=")) {
+                description.push_str(&format!(" xmlns:{prefix}=\"{uri}\""));
+            }
+        }
+        description.push('>');
+        description.push_str(&merged.properties());
+        if xmp[start..end].ends_with('/') {
+            description.push_str("\n  </rdf:Description>");
+        }
+
+        xmp.replace_range(start..=end, &description);
+        Some(xmp)
+    }
+
+    /// Writes our properties as XML elements.
+    fn properties(&self) -> String {
+        let mut out = String::new();
+
+        let mut list = |property: &str, kind: &str, items: &[String]| {
+            if items.is_empty() {
+                return;
+            }
+            out.push_str(&format!("\n   <{property}>\n    <rdf:{kind}>\n"));
+            for item in items {
+                out.push_str(&format!("     <rdf:li>{}</rdf:li>\n", escape(item)));
+            }
+            out.push_str(&format!("    </rdf:{kind}>\n   </{property}>"));
+        };
+        list("dc:subject", "Bag", &self.subjects);
+        list("lr:hierarchicalSubject", "Bag", &self.hierarchical_subjects);
+        list("Iptc4xmpExt:PersonInImage", "Bag", &self.people);
+
+        if let Some(ref description) = self.description {
+            out.push_str(&format!(
+                "\n   <dc:description>\n    <rdf:Alt>\n     \
+                <rdf:li xml:lang=\"x-default\">{}</rdf:li>\n    \
+                </rdf:Alt>\n   </dc:description>",
+                escape(description)
+            ));
+        }
+        if let Some(rating) = self.rating {
+            out.push_str(&format!("\n   <xmp:Rating>{rating}</xmp:Rating>"));
+        }
+
+        out
+    }
+}
+
+/// Where the sidecar for the media at `path` goes: right next to it, with
+/// `.xmp` on the end.
+pub fn sidecar_path(path: &Utf8Path) -> Utf8PathBuf {
+    Utf8PathBuf::from(format!("{path}.xmp"))
+}
+
+/// Writes a media file's tags and rating to its sidecar, merging with any
+/// sidecar that's already there. Returns the sidecar's path.
+#[tracing::instrument]
+pub async fn export_sidecar(media_id: Uuid) -> Result<Utf8PathBuf, RavesError> {
+    let mut conn = DATABASE.acquire().await.inspect_err(|e| {
+        tracing::error!("Failed to connect to database to export sidecar. err: {e}")
+    })?;
+    let path =
+        sqlx::query_scalar::<_, String>(&format!("SELECT path FROM {INFO_TABLE} WHERE id = $1"))
+            .bind(media_id)
+            .fetch_optional(&mut *conn)
+            .await
+            .inspect_err(|e| tracing::error!("Failed to get media path! err: {e}"))
+            .map_err(DatabaseError::QueryFailed)?
+            .ok_or_else(|| RavesError::MediaDoesntExist {
+                path: media_id.to_string(),
+            })?;
+    drop(conn);
+
+    let sidecar = Sidecar::for_media(media_id).await?;
+    let path = sidecar_path(Utf8Path::new(&path));
+
+    let existing = match tokio::fs::read_to_string(&path).await {
+        Ok(xmp) => Some(xmp),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(err) => return Err(RavesError::SidecarReadFailed { path, err }),
+    };
+
+    let Some(xmp) = sidecar.merge(existing.as_deref()) else {
+        tracing::warn!("Not overwriting a sidecar we can't read at `{path}`.");
+        return Err(RavesError::SidecarUnreadable { path });
+    };
+    if let Err(err) = tokio::fs::write(&path, xmp).await {
+        return Err(RavesError::SidecarWriteFailed { path, err });
+    }
+
+    tracing::debug!("exported sidecar to `{path}`");
+    Ok(path)
+}
+
+/// Grabs each `rdf:li` in the given XMP property, like `dc:subject`.
+///
+/// When a document has the property more than once (say, in two
+/// `rdf:Description`s), the items from each are included.
+pub(crate) fn rdf_items(xmp: &str, property: &str) -> Vec<String> {
+    let mut items = Vec::new();
+    for range in elements(xmp, property) {
+        let mut rest = &xmp[range];
+        while let Some(li) = rest.find("<rdf:li") {
+            rest = &rest[li..];
+            let Some(open_end) = rest.find('>') else {
+                break;
+            };
+
+            // `<rdf:li/>` is empty
+            if rest[..open_end].ends_with('/') {
+                rest = &rest[open_end..];
+                continue;
+            }
+
+            let Some(close) = rest.find("</rdf:li>") else {
+                break;
+            };
+            items.push(unescape(&rest[open_end + 1..close]));
+            rest = &rest[close..];
+        }
+    }
+
+    items
+}
+
+/// Finds each element for the given property, from its start tag to the end
+/// of its end tag. Self-closing elements are skipped, as they're empty.
+fn elements(xmp: &str, property: &str) -> Vec<core::ops::Range<usize>> {
+    let open = format!("<{property}");
+    let close = format!("</{property}>");
+
+    let mut ranges = Vec::new();
+    let mut from = 0;
+    while let Some(found) = xmp[from..].find(&open) {
+        let start = from + found;
+        let after = &xmp[start + open.len()..];
+        from = start + open.len();
+
+        // make sure it's not just a property that starts the same way
+        if !after.starts_with(['>', '/', ' ', '\n', '\t', '\r']) {
+            continue;
+        }
+        let Some(tag_end) = after.find('>').map(|at| from + at) else {
+            break;
+        };
+        if xmp[..tag_end].ends_with('/') {
+            continue;
+        }
+
+        let Some(len) = xmp[tag_end..].find(&close) else {
+            break;
+        };
+        ranges.push(start..tag_end + len + close.len());
+        from = tag_end + len + close.len();
+    }
+
+    ranges
+}
+
+/// Replaces the text of the `x-default` `rdf:li` in the first element for a
+/// language alternative property, like `dc:description`, leaving the other
+/// languages alone.
+///
+/// Returns `None` when there's no such item to replace.
+fn replace_default_item(xmp: &str, property: &str, text: &str) -> Option<String> {
+    let range = elements(xmp, property).into_iter().next()?;
+
+    let mut from = range.start;
+    while let Some(found) = xmp[from..range.end].find("<rdf:li") {
+        let start = from + found;
+        let open_end = start + xmp[start..range.end].find('>')?;
+        from = open_end;
+
+        let tag = &xmp[start..open_end];
+        if tag.ends_with('/')
+            || !(tag.contains("xml:lang=\"x-default\"") || tag.contains("xml:lang='x-default'"))
+        {
+            continue;
+        }
+
+        let close = open_end + xmp[open_end..range.end].find("</rdf:li>")?;
+        let mut xmp = xmp.to_string();
+        xmp.replace_range(open_end + 1..close, &escape(text));
+        return Some(xmp);
+    }
+
+    None
+}
+
+/// Removes a property from an XMP document, whether it's an element or an
+/// attribute.
+fn remove_property(xmp: &str, property: &str) -> String {
+    let mut xmp = xmp.to_string();
+
+    // elements, like `<xmp:Rating>3</xmp:Rating>`
+    let open = format!("<{property}");
+    let mut from = 0;
+    while let Some(found) = xmp[from..].find(&open) {
+        let start = from + found;
+        let after = &xmp[start + open.len()..];
+
+        // make sure it's not just a property that starts the same way
+        if !after.starts_with(['>', '/', ' ', '\n', '\t', '\r']) {
+            from = start + open.len();
+            continue;
+        }
+
+        let tag_end = start + open.len() + after.find('>').unwrap_or(after.len());
+        let end = if xmp[..tag_end].ends_with('/') {
+            tag_end + 1
+        } else {
+            let close = format!("</{property}>");
+            match xmp[tag_end..].find(&close) {
+                Some(close_at) => tag_end + close_at + close.len(),
+                None => break,
+            }
+        };
+
+        // take the whitespace before it, too
+        let start = xmp[..start].trim_end().len();
+        xmp.replace_range(start..end.min(xmp.len()), "");
+        from = start;
+    }
+
+    // attributes, like `xmp:Rating="3"`
+    for quote in ['"', '\''] {
+        let attribute = format!(" {property}={quote}");
+        while let Some(start) = xmp.find(&attribute) {
+            let value = start + attribute.len();
+            let Some(len) = xmp[value..].find(quote) else {
+                break;
+            };
+            let start = xmp[..start].trim_end().len();
+            xmp.replace_range(start..value + len + 1, "");
+        }
+    }
+
+    xmp
+}
+
+/// Escapes text for XML.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Undoes XML escapes, like `&amp;`.
+pub(crate) fn unescape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+
+        let Some(semi) = rest.find(';') else {
+            break;
+        };
+        let entity = &rest[1..semi];
+        let c = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity
+                .strip_prefix("#x")
+                .map(|hex| u32::from_str_radix(hex, 16))
+                .or_else(|| entity.strip_prefix('#').map(str::parse))
+                .and_then(Result::ok)
+                .and_then(char::from_u32),
+        };
+
+        match c {
+            Some(c) => {
+                out.push(c);
+                rest = &rest[semi + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sidecar() -> Sidecar {
+        Sidecar {
+            subjects: vec!["cat".into(), "fish & chips".into()],
+            hierarchical_subjects: vec!["animals|cat".into(), "fish & chips".into()],
+            rating: Some(4),
+            description: None,
+            people: vec!["Barrett".into()],
+        }
+    }
+
+    #[test]
+    fn new_sidecars() {
+        let xmp = sidecar().merge(None).unwrap();
+
+        assert_eq!(rdf_items(&xmp, "dc:subject"), ["cat", "fish & chips"]);
+        assert_eq!(
+            rdf_items(&xmp, "lr:hierarchicalSubject"),
+            ["animals|cat", "fish & chips"]
+        );
+        assert_eq!(rdf_items(&xmp, "Iptc4xmpExt:PersonInImage"), ["Barrett"]);
+        assert!(xmp.contains("<xmp:Rating>4</xmp:Rating>"));
+        assert!(!xmp.contains("dc:description"));
+        for (prefix, uri) in NAMESPACES {
+            assert!(xmp.contains(&format!("xmlns:{prefix}=\"{uri}\"")), "{xmp}");
+        }
+
+        // merging into itself changes nothing
+        assert_eq!(sidecar().merge(Some(&xmp)).unwrap(), xmp);
+    }
+
+    #[test]
+    fn existing_sidecars_are_merged() {
+        // like what digiKam writes
+        let existing = r#"<?xpacket begin="" id="W5M0MpCehiHzreSzNTczkc9d"?>
+<x:xmpmeta xmlns:x="adobe:ns:meta/" x:xmptk="XMP Core 4.4.0-Exiv2">
+ <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+  <rdf:Description rdf:about=""
+    xmlns:xmp="http://ns.adobe.com/xap/1.0/"
+    xmlns:dc="http://purl.org/dc/elements/1.1/"
+    xmlns:digiKam="http://www.digikam.org/ns/1.0/"
+   xmp:Rating="1"
+   digiKam:ColorLabel="3">
+   <dc:subject>
+    <rdf:Bag>
+     <rdf:li>dog</rdf:li>
+     <rdf:li>cat</rdf:li>
+    </rdf:Bag>
+   </dc:subject>
+   <dc:description>
+    <rdf:Alt>
+     <rdf:li xml:lang="x-default">a cat &amp; a dog</rdf:li>
+    </rdf:Alt>
+   </dc:description>
+   <dc:subjectArea>untouched</dc:subjectArea>
+  </rdf:Description>
+ </rdf:RDF>
+</x:xmpmeta>
+<?xpacket end="w"?>"#;
+
+        let xmp = sidecar().merge(Some(existing)).unwrap();
+
+        // lists keep what they had
+        assert_eq!(
+            rdf_items(&xmp, "dc:subject"),
+            ["dog", "cat", "fish & chips"]
+        );
+        assert_eq!(rdf_items(&xmp, "dc:description"), ["a cat & a dog"]);
+
+        // our rating wins, and isn't written twice
+        assert!(xmp.contains("<xmp:Rating>4</xmp:Rating>"));
+        assert!(!xmp.contains("xmp:Rating=\"1\""));
+        assert_eq!(xmp.matches("xmlns:dc=").count(), 1);
+
+        // everything else stays
+        assert!(xmp.contains(r#"digiKam:ColorLabel="3""#));
+        assert!(xmp.contains("<dc:subjectArea>untouched</dc:subjectArea>"));
+        assert!(xmp.starts_with("<?xpacket") && xmp.ends_with("<?xpacket end=\"w\"?>"));
+
+        // and a description of our own replaces theirs
+        let with_description = Sidecar {
+            description: Some("just a cat".into()),
+            ..sidecar()
+        };
+        let xmp = with_description.merge(Some(&xmp)).unwrap();
+        assert_eq!(rdf_items(&xmp, "dc:description"), ["just a cat"]);
+        assert_eq!(xmp.matches("<dc:description>").count(), 1);
+    }
+
+    #[test]
+    fn self_closing_descriptions() {
+        let existing = r#"<x:xmpmeta xmlns:x="adobe:ns:meta/"><rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#"><rdf:Description rdf:about="" xmp:Rating="2" xmlns:xmp="http://ns.adobe.com/xap/1.0/"/></rdf:RDF></x:xmpmeta>"#;
+
+        let xmp = sidecar().merge(Some(existing)).unwrap();
+        assert!(xmp.contains("<xmp:Rating>4</xmp:Rating>"));
+        assert!(xmp.contains("</rdf:Description></rdf:RDF>"), "{xmp}");
+        assert_eq!(rdf_items(&xmp, "dc:subject"), ["cat", "fish & chips"]);
+    }
+
+    #[test]
+    fn unrated_media_keep_existing_ratings() {
+        let unrated = Sidecar {
+            rating: None,
+            ..sidecar()
+        };
+
+        // as an attribute...
+        let existing = r#"<x:xmpmeta xmlns:x="adobe:ns:meta/"><rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#"><rdf:Description rdf:about="" xmp:Rating="2" xmlns:xmp="http://ns.adobe.com/xap/1.0/"/></rdf:RDF></x:xmpmeta>"#;
+        let xmp = unrated.merge(Some(existing)).unwrap();
+        assert!(xmp.contains(r#"xmp:Rating="2""#), "{xmp}");
+        assert_eq!(xmp.matches("xmp:Rating").count(), 1);
+
+        // ...or an element
+        let xmp = unrated
+            .merge(Some(&sidecar().merge(None).unwrap()))
+            .unwrap();
+        assert!(xmp.contains("<xmp:Rating>4</xmp:Rating>"), "{xmp}");
+        assert_eq!(xmp.matches("<xmp:Rating>").count(), 1);
+
+        // and without one, nothing is made up
+        assert!(!unrated.merge(None).unwrap().contains("xmp:Rating"));
+    }
+
+    #[test]
+    fn other_languages_are_kept() {
+        let existing = r#"<x:xmpmeta xmlns:x="adobe:ns:meta/">
+ <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+  <rdf:Description rdf:about="" xmlns:dc="http://purl.org/dc/elements/1.1/">
+   <dc:description>
+    <rdf:Alt>
+     <rdf:li xml:lang="x-default">a cat</rdf:li>
+     <rdf:li xml:lang="de-DE">eine Katze</rdf:li>
+    </rdf:Alt>
+   </dc:description>
+  </rdf:Description>
+ </rdf:RDF>
+</x:xmpmeta>"#;
+
+        // without a description, theirs isn't touched
+        let xmp = sidecar().merge(Some(existing)).unwrap();
+        assert!(xmp.contains(
+            r#"<rdf:li xml:lang="x-default">a cat</rdf:li>
+     <rdf:li xml:lang="de-DE">eine Katze</rdf:li>"#
+        ));
+
+        // and with one, only the default language changes
+        let with_description = Sidecar {
+            description: Some("just a cat".into()),
+            ..sidecar()
+        };
+        let xmp = with_description.merge(Some(existing)).unwrap();
+        assert_eq!(
+            rdf_items(&xmp, "dc:description"),
+            ["just a cat", "eine Katze"]
+        );
+        assert!(xmp.contains(r#"<rdf:li xml:lang="de-DE">eine Katze</rdf:li>"#));
+        assert_eq!(xmp.matches("<dc:description>").count(), 1);
+
+        // a lone German caption isn't relabelled as the default
+        let german = existing.replace("     <rdf:li xml:lang=\"x-default\">a cat</rdf:li>\n", "");
+        let xmp = with_description.merge(Some(&german)).unwrap();
+        assert!(xmp.contains(r#"<rdf:li xml:lang="x-default">just a cat</rdf:li>"#));
+        assert_eq!(rdf_items(&xmp, "dc:description"), ["just a cat"]);
+    }
+
+    #[test]
+    fn lists_from_every_description_are_kept() {
+        let existing = r#"<x:xmpmeta xmlns:x="adobe:ns:meta/">
+ <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+  <rdf:Description rdf:about="" xmlns:dc="http://purl.org/dc/elements/1.1/">
+   <dc:subject><rdf:Bag><rdf:li>dog</rdf:li></rdf:Bag></dc:subject>
+  </rdf:Description>
+  <rdf:Description rdf:about="" xmlns:dc="http://purl.org/dc/elements/1.1/">
+   <dc:subject><rdf:Bag><rdf:li>bird</rdf:li><rdf:li>cat</rdf:li></rdf:Bag></dc:subject>
+  </rdf:Description>
+ </rdf:RDF>
+</x:xmpmeta>"#;
+        assert_eq!(rdf_items(existing, "dc:subject"), ["dog", "bird", "cat"]);
+
+        let xmp = sidecar().merge(Some(existing)).unwrap();
+        assert_eq!(
+            rdf_items(&xmp, "dc:subject"),
+            ["dog", "bird", "cat", "fish & chips"]
+        );
+        assert_eq!(xmp.matches("<dc:subject>").count(), 1);
+    }
+
+    #[test]
+    fn unreadable_sidecars_are_left_alone() {
+        assert_eq!(sidecar().merge(Some("")), None);
+        assert_eq!(sidecar().merge(Some("<x:xmpmeta><rdf:RDF>")), None);
+    }
+
+    #[test]
+    fn escapes() {
+        assert_eq!(unescape("caf&#xE9; &#38; &bogus; &"), "café & &bogus; &");
+        assert_eq!(unescape(&escape("<a & \"b\">")), "<a & \"b\">");
+    }
+}
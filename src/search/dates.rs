@@ -0,0 +1,253 @@
+//! Turns date details into concrete times.
+//!
+//! Partial and relative dates don't mean anything on their own. They're
+//! resolved against "now", in its time zone, right before a search runs.
+
+use jiff::{
+    civil::{time, Date, Time},
+    Span, ToSpan as _, Zoned,
+};
+
+use super::details::{CalendarUnit, DateValue, PartialDate, RelativeDate, TimeOfDay};
+
+impl DateValue {
+    /// The stretch of time this value covers, from `start` (inclusive) until
+    /// `end` (exclusive).
+    ///
+    /// An instant starts and ends at the same moment. Times of day happen on
+    /// every date, so they don't have a range.
+    ///
+    /// Returns `None` for times of day, or when a date is out of range.
+    pub fn range(&self, now: &Zoned) -> Option<(Zoned, Zoned)> {
+        match self {
+            DateValue::Instant(zoned) => Some((zoned.clone(), zoned.clone())),
+            DateValue::Partial(partial) => partial.range(now),
+            DateValue::Relative(relative) => relative.range(now),
+            DateValue::TimeOfDay(_) => None,
+        }
+    }
+}
+
+impl PartialDate {
+    /// The whole year, month, or day, in the time zone of `now`.
+    pub fn range(&self, now: &Zoned) -> Option<(Zoned, Zoned)> {
+        let start = Date::new(self.year, self.month.unwrap_or(1), self.day.unwrap_or(1)).ok()?;
+        let length = match (self.month, self.day) {
+            (_, Some(_)) => 1.day(),
+            (Some(_), None) => 1.month(),
+            (None, None) => 1.year(),
+        };
+
+        days(start, start.checked_add(length).ok()?, now)
+    }
+}
+
+impl RelativeDate {
+    /// The whole days this covers, counting from today in the time zone of
+    /// `now`.
+    pub fn range(&self, now: &Zoned) -> Option<(Zoned, Zoned)> {
+        let today = now.date();
+
+        let (start, end) = match self {
+            RelativeDate::Today => (today, today.tomorrow().ok()?),
+            RelativeDate::Yesterday => (today.yesterday().ok()?, today),
+            RelativeDate::This(unit) => {
+                let start = unit.start_of(today)?;
+                (start, start.checked_add(unit.length()).ok()?)
+            }
+            RelativeDate::Last(unit) => {
+                let end = unit.start_of(today)?;
+                (end.checked_sub(unit.length()).ok()?, end)
+            }
+            RelativeDate::LastDays(n) => (
+                today
+                    .checked_sub(i64::from(n.saturating_sub(1)).days())
+                    .ok()?,
+                today.tomorrow().ok()?,
+            ),
+        };
+
+        days(start, end, now)
+    }
+}
+
+impl CalendarUnit {
+    /// The first day of the week, month, or year that `date` is in.
+    fn start_of(&self, date: Date) -> Option<Date> {
+        match self {
+            CalendarUnit::Week => date
+                .checked_sub(i64::from(date.weekday().to_monday_zero_offset()).days())
+                .ok(),
+            CalendarUnit::Month => Some(date.first_of_month()),
+            CalendarUnit::Year => Some(date.first_of_year()),
+        }
+    }
+
+    fn length(&self) -> Span {
+        match self {
+            CalendarUnit::Week => 1.week(),
+            CalendarUnit::Month => 1.month(),
+            CalendarUnit::Year => 1.year(),
+        }
+    }
+}
+
+impl TimeOfDay {
+    pub const MORNING: Self = Self::new(time(5, 0, 0, 0), time(12, 0, 0, 0));
+    pub const AFTERNOON: Self = Self::new(time(12, 0, 0, 0), time(17, 0, 0, 0));
+    pub const EVENING: Self = Self::new(time(17, 0, 0, 0), time(21, 0, 0, 0));
+    pub const NIGHT: Self = Self::new(time(21, 0, 0, 0), time(5, 0, 0, 0));
+
+    pub const fn new(start: Time, end: Time) -> Self {
+        Self { start, end }
+    }
+
+    /// Whether this window wraps around midnight, like `21:00..05:00`.
+    pub fn wraps(&self) -> bool {
+        self.end < self.start
+    }
+}
+
+/// The whole day that an instant is on, in its own time zone.
+pub(crate) fn day_of(zoned: &Zoned) -> Option<(Zoned, Zoned)> {
+    let date = zoned.date();
+    days(date, date.tomorrow().ok()?, zoned)
+}
+
+/// Formats a time of day the way SQLite's `time()` does: `HH:MM:SS`.
+pub(crate) fn sql_time(t: Time) -> String {
+    format!("{:02}:{:02}:{:02}", t.hour(), t.minute(), t.second())
+}
+
+/// SQL for the UTC offset, in seconds, that `now`'s time zone had at the
+/// UTC date in `column`.
+///
+/// Each date gets its own offset, so dates from the other side of a DST
+/// change aren't off by an hour. Newer changes are checked first, since most
+/// media are recent.
+pub(crate) fn local_offset(column: &str, now: &Zoned) -> String {
+    let tz = now.time_zone();
+    let ts = now.timestamp();
+
+    // changes until a year from now, then back to 1970 (file dates can't be
+    // older than that)
+    let mut changes = tz
+        .following(ts)
+        .take_while(|t| t.timestamp().as_second() < ts.as_second() + 366 * 86_400)
+        .map(|t| (t.timestamp(), t.offset()))
+        .collect::<Vec<_>>();
+    changes.reverse();
+    let mut before = now.offset();
+    for t in tz.preceding(ts) {
+        before = t.offset();
+        if t.timestamp().as_second() < 0 {
+            break;
+        }
+        changes.push((t.timestamp(), t.offset()));
+    }
+
+    if changes.is_empty() {
+        return before.seconds().to_string();
+    }
+    let whens = changes
+        .iter()
+        .map(|(at, offset)| {
+            format!(
+                "WHEN datetime({column}) >= '{}' THEN {}",
+                at.strftime("%Y-%m-%d %H:%M:%S"),
+                offset.seconds()
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+    format!("CASE {whens} ELSE {} END", before.seconds())
+}
+
+/// Starts both dates at midnight in the time zone of `now`.
+fn days(start: Date, end: Date, now: &Zoned) -> Option<(Zoned, Zoned)> {
+    let tz = now.time_zone().clone();
+    Some((start.to_zoned(tz.clone()).ok()?, end.to_zoned(tz).ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A Thursday afternoon in Chicago.
+    fn now() -> Zoned {
+        "2024-03-14T15:30[America/Chicago]".parse().unwrap()
+    }
+
+    fn range(value: DateValue) -> (String, String) {
+        let (start, end) = value.range(&now()).unwrap();
+        (start.to_string(), end.to_string())
+    }
+
+    #[test]
+    fn partial_dates() {
+        let partial = |year, month, day| DateValue::Partial(PartialDate { year, month, day });
+
+        assert_eq!(
+            range(partial(2023, None, None)),
+            (
+                "2023-01-01T00:00:00-06:00[America/Chicago]".into(),
+                "2024-01-01T00:00:00-06:00[America/Chicago]".into()
+            )
+        );
+        assert_eq!(
+            range(partial(2023, Some(6), None)),
+            (
+                "2023-06-01T00:00:00-05:00[America/Chicago]".into(),
+                "2023-07-01T00:00:00-05:00[America/Chicago]".into()
+            )
+        );
+        assert_eq!(
+            range(partial(2023, Some(6), Some(14))).1,
+            "2023-06-15T00:00:00-05:00[America/Chicago]"
+        );
+        assert!(partial(2023, Some(2), Some(30)).range(&now()).is_none());
+    }
+
+    #[test]
+    fn relative_dates() {
+        let relative = |r| range(DateValue::Relative(r));
+
+        assert_eq!(
+            relative(RelativeDate::Yesterday),
+            (
+                "2024-03-13T00:00:00-05:00[America/Chicago]".into(),
+                "2024-03-14T00:00:00-05:00[America/Chicago]".into()
+            )
+        );
+        // weeks start on monday
+        assert_eq!(
+            relative(RelativeDate::Last(CalendarUnit::Week)),
+            (
+                "2024-03-04T00:00:00-06:00[America/Chicago]".into(),
+                "2024-03-11T00:00:00-05:00[America/Chicago]".into()
+            )
+        );
+        assert_eq!(
+            relative(RelativeDate::This(CalendarUnit::Month)).0,
+            "2024-03-01T00:00:00-06:00[America/Chicago]"
+        );
+        // today counts as one of them
+        assert_eq!(
+            relative(RelativeDate::LastDays(30)),
+            (
+                "2024-02-14T00:00:00-06:00[America/Chicago]".into(),
+                "2024-03-15T00:00:00-05:00[America/Chicago]".into()
+            )
+        );
+    }
+
+    #[test]
+    fn times_of_day() {
+        assert!(TimeOfDay::NIGHT.wraps());
+        assert!(!TimeOfDay::MORNING.wraps());
+        assert_eq!(sql_time(TimeOfDay::EVENING.start), "17:00:00");
+        assert!(DateValue::TimeOfDay(TimeOfDay::NIGHT)
+            .range(&now())
+            .is_none());
+    }
+}
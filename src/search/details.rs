@@ -6,20 +6,72 @@ use std::path::PathBuf;
 
 use crate::models::media::metadata::Framerate;
 
-use jiff::Zoned;
+use jiff::{civil::Time, Zoned};
 
 /// the location of media
 #[derive(Clone, Debug, PartialEq, PartialOrd)]
 pub struct PathDetail(pub PathBuf);
 
-/// date, time, created dt, modified dt, accessed dt, first seen dt
+/// created dt, modified dt, accessed dt, first seen dt, capture dt
 #[derive(Clone, Debug, PartialEq, PartialOrd)]
 pub enum DateDetail {
-    // TODO: allow dates, times, or both. for now, assume manual conversion
-    Created(Zoned),
-    Modified(Zoned),
-    Accessed(Zoned),
-    FirstSeen(Zoned),
+    Created(DateValue),
+    Modified(DateValue),
+    Accessed(DateValue),
+    FirstSeen(DateValue),
+    /// When the media was captured, according to its EXIF `DateTimeOriginal`.
+    Captured(DateValue),
+}
+
+/// date, time, or both
+#[derive(Clone, Debug, PartialEq, PartialOrd)]
+pub enum DateValue {
+    /// An exact moment, like `2023-06-14T09:30`.
+    Instant(Zoned),
+    /// A whole year, month, or day, like `2023`, `2023-06`, or `2023-06-14`.
+    Partial(PartialDate),
+    /// A stretch of time relative to now, like `yesterday` or `last 30 days`.
+    Relative(RelativeDate),
+    /// A time of day, on any date, like `at night` or `09:00..12:00`.
+    TimeOfDay(TimeOfDay),
+}
+
+/// a year, optionally narrowed down to a month, then a day
+#[derive(Clone, Debug, PartialEq, PartialOrd)]
+pub struct PartialDate {
+    pub year: i16,
+    pub month: Option<i8>,
+    pub day: Option<i8>,
+}
+
+/// "today", "last week", "last 30 days", etc.
+#[derive(Clone, Debug, PartialEq, PartialOrd)]
+pub enum RelativeDate {
+    Today,
+    Yesterday,
+    /// The current calendar week, month, or year.
+    This(CalendarUnit),
+    /// The calendar week, month, or year before this one.
+    Last(CalendarUnit),
+    /// The past `n` days, including today.
+    LastDays(u16),
+}
+
+/// weeks start on Monday
+#[derive(Clone, Debug, PartialEq, PartialOrd)]
+pub enum CalendarUnit {
+    Week,
+    Month,
+    Year,
+}
+
+/// A window of time on any day, from `start` until `end`.
+///
+/// When `end` is before `start`, the window wraps around midnight.
+#[derive(Clone, Debug, PartialEq, PartialOrd)]
+pub struct TimeOfDay {
+    pub start: Time,
+    pub end: Time,
 }
 
 /// "webm", "avif", etc.
@@ -53,6 +105,15 @@ pub enum TagDetail {
     Count(u8, Comparison),
 }
 
+/// A value to compare against, or an inclusive range of them.
+///
+/// "`>10MiB`", "`10s..2m`"
+#[derive(Clone, Debug, PartialEq, PartialOrd)]
+pub enum RangeDetail<T> {
+    Compare(Comparison, T),
+    Between(T, T),
+}
+
 #[derive(Clone, Debug, PartialEq, PartialOrd)]
 pub enum Comparison {
     Less,
@@ -80,3 +141,14 @@ pub enum CompressionDetail {
     Lossless, // :D
     Lossy,    // >:P
 }
+
+/// A field from the media's EXIF, by its canonical name, like `Model` or
+/// `GPSLatitude`.
+#[derive(Clone, Debug, PartialEq, PartialOrd)]
+pub enum ExifDetail {
+    /// The field is there, with any value.
+    Has(String),
+    /// The field's value compares to this one. When both parse as numbers,
+    /// they're compared as numbers. Otherwise, they're compared as text.
+    Compare(String, Comparison, String),
+}
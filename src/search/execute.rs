@@ -0,0 +1,365 @@
+//! Runs searches against the database.
+//!
+//! Results come back in pages. Each page is found with a "keyset" (the sort
+//! key and `id` of the last result), so scrolling deep into a huge library is
+//! just as fast as looking at the first page.
+
+use sqlx::{sqlite::SqliteRow, Row as _, TypeInfo as _, ValueRef as _};
+use uuid::Uuid;
+
+use crate::{
+    database::{DATABASE, INFO_FTS_TABLE, INFO_TABLE, MEDIA_COLUMNS},
+    error::{DatabaseError, RavesError},
+    models::media::Media,
+};
+
+use super::{
+    modifiers::{BooleanModifier, CollectionModifier, Expr},
+    query::{fts_query, Clause, Param},
+    sort::{FinishedQuery, SortOrder, SortSpec, SortType},
+};
+
+/// A search, ready to run against the database.
+#[derive(Clone, Debug, PartialEq, PartialOrd)]
+pub struct Search {
+    /// The expressions that all results must match.
+    pub exprs: Vec<Expr>,
+    /// How to sort the results.
+    pub sort: SortSpec,
+}
+
+/// Marks where a page ended. Give it back to [`Search::page`] to get the
+/// next one.
+#[derive(Clone, Debug, PartialEq, PartialOrd, serde::Serialize, serde::Deserialize)]
+pub struct Cursor {
+    /// The sort keys of the last result, one for each sort.
+    keys: Vec<Param>,
+    /// The id of the last result, which breaks ties between equal keys.
+    id: Uuid,
+}
+
+/// One page of search results.
+#[derive(Clone, Debug, PartialEq, PartialOrd)]
+pub struct Page {
+    pub results: FinishedQuery,
+    /// Where the next page starts. When this is `None`, there are no more
+    /// results.
+    pub next: Option<Cursor>,
+}
+
+impl Search {
+    pub fn new(exprs: Vec<Expr>, sort: impl Into<SortSpec>) -> Self {
+        Self {
+            exprs,
+            sort: sort.into(),
+        }
+    }
+
+    /// Grabs up to `limit` results, starting right after the given cursor.
+    ///
+    /// Pass `None` to get the first page.
+    #[tracing::instrument(skip(self))]
+    pub async fn page(&self, after: Option<&Cursor>, limit: u32) -> Result<Page, RavesError> {
+        let keys = self.sort_keys();
+        let Clause { mut sql, params } = Clause::all(&self.exprs);
+
+        // the keys' parameters come first, since they're in the `SELECT`
+        let mut params = keys
+            .iter()
+            .flat_map(|(key, _)| key.params.iter().cloned())
+            .chain(params)
+            .collect::<Vec<_>>();
+
+        // only grab stuff after the cursor
+        if let Some(cursor) = after {
+            let later = comes_after(&keys, cursor)?;
+            sql = format!("({sql}) AND ({})", later.sql);
+            params.extend(later.params);
+        }
+
+        let columns = keys
+            .iter()
+            .enumerate()
+            .map(|(i, (key, _))| format!(", {} AS sort_key_{i}", key.sql))
+            .collect::<String>();
+        let order_by = keys
+            .iter()
+            .enumerate()
+            .map(|(i, (_, order))| format!("sort_key_{i} {}, ", direction(order)))
+            .collect::<String>();
+
+        let query = format!(
+            "SELECT {MEDIA_COLUMNS}{columns} FROM {INFO_TABLE} WHERE {sql} \
+            ORDER BY {order_by}{INFO_TABLE}.id ASC LIMIT ?"
+        );
+        params.push(Param::Integer(limit as i64));
+
+        let mut conn = DATABASE.acquire().await.inspect_err(|e| {
+            tracing::error!("Failed to connect to database for search. err: {e}")
+        })?;
+
+        let mut q = sqlx::query(&query);
+        for param in params {
+            q = q.bind(param);
+        }
+        let rows = q
+            .fetch_all(&mut *conn)
+            .await
+            .inspect_err(|e| tracing::error!("Search query failed! err: {e}"))
+            .map_err(DatabaseError::QueryFailed)?;
+
+        // the last row tells us where to continue from
+        let next = match rows.last() {
+            Some(last) if rows.len() == limit as usize => Some(Cursor {
+                keys: sort_keys(last, keys.len())?,
+                id: last.try_get("id").map_err(DatabaseError::QueryFailed)?,
+            }),
+            _ => None,
+        };
+
+        let media = rows
+            .iter()
+            .map(<Media as sqlx::FromRow<SqliteRow>>::from_row)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(DatabaseError::QueryFailed)?;
+
+        Ok(Page {
+            results: FinishedQuery::from(media),
+            next,
+        })
+    }
+
+    /// Counts all results for this search.
+    #[tracing::instrument(skip(self))]
+    pub async fn count(&self) -> Result<u64, RavesError> {
+        let Clause { sql, params } = Clause::all(&self.exprs);
+        self.count_where(&sql, params).await
+    }
+
+    /// Finds where the given media file is in this search's results.
+    ///
+    /// That's the number of results that come before it, so the first result
+    /// is at position `0`. If the media isn't in the results, this returns
+    /// `None`.
+    #[tracing::instrument(skip(self))]
+    pub async fn position(&self, media_id: Uuid) -> Result<Option<u64>, RavesError> {
+        let keys = self.sort_keys();
+        let Clause { sql, mut params } = Clause::all(&self.exprs);
+
+        // grab the media's sort keys (which also checks that it's a result)
+        let target = {
+            let mut conn = DATABASE.acquire().await.inspect_err(|e| {
+                tracing::error!("Failed to connect to database for search. err: {e}")
+            })?;
+
+            let columns = keys
+                .iter()
+                .enumerate()
+                .map(|(i, (key, _))| format!("{} AS sort_key_{i}, ", key.sql))
+                .collect::<String>();
+            let query = format!(
+                "SELECT {columns}{INFO_TABLE}.id FROM {INFO_TABLE} \
+                WHERE ({sql}) AND {INFO_TABLE}.id = ?"
+            );
+            let mut q = sqlx::query(&query);
+            for param in keys
+                .iter()
+                .flat_map(|(key, _)| &key.params)
+                .chain(&params)
+                .cloned()
+            {
+                q = q.bind(param);
+            }
+
+            q.bind(media_id)
+                .fetch_optional(&mut *conn)
+                .await
+                .inspect_err(|e| tracing::error!("Failed to find media in search! err: {e}"))
+                .map_err(DatabaseError::QueryFailed)?
+        };
+        let Some(target) = target else {
+            return Ok(None);
+        };
+
+        // count everything that sorts before it
+        let target = Cursor {
+            keys: sort_keys(&target, keys.len())?,
+            id: media_id,
+        };
+        let earlier = comes_before(&keys, &target)?;
+        params.extend(earlier.params);
+        self.count_where(&format!("({sql}) AND ({})", earlier.sql), params)
+            .await
+            .map(Some)
+    }
+
+    async fn count_where(&self, sql: &str, params: Vec<Param>) -> Result<u64, RavesError> {
+        let mut conn = DATABASE.acquire().await.inspect_err(|e| {
+            tracing::error!("Failed to connect to database for search. err: {e}")
+        })?;
+
+        let query = format!("SELECT COUNT(*) FROM {INFO_TABLE} WHERE {sql}");
+        let mut q = sqlx::query_scalar::<_, i64>(&query);
+        for param in params {
+            q = q.bind(param);
+        }
+
+        q.fetch_one(&mut *conn)
+            .await
+            .inspect_err(|e| tracing::error!("Failed to count search results! err: {e}"))
+            .map_err(|e| DatabaseError::QueryFailed(e).into())
+            .map(|ct| ct as u64)
+    }
+
+    /// The terms to put in an `ORDER BY` to sort like this search, ending
+    /// with the `id`.
+    pub(crate) fn order_by(&self) -> Clause {
+        let keys = self.sort_keys();
+
+        Clause {
+            sql: keys
+                .iter()
+                .map(|(key, order)| format!("{} {}, ", key.sql, direction(order)))
+                .chain([format!("{INFO_TABLE}.id ASC")])
+                .collect(),
+            params: keys.into_iter().flat_map(|(key, _)| key.params).collect(),
+        }
+    }
+
+    /// The SQL for each of the results' sort keys, alongside their order.
+    fn sort_keys(&self) -> Vec<(Clause, &SortOrder)> {
+        // rank the results against any of the search's text
+        let text = search_text(&self.exprs)
+            .into_iter()
+            .filter_map(fts_query)
+            .map(|fts| format!("({fts})"))
+            .collect::<Vec<_>>();
+
+        self.sort
+            .keys
+            .iter()
+            .map(|(ty, order)| {
+                let key = if *ty == SortType::Relevance && !text.is_empty() {
+                    Clause {
+                        sql: format!(
+                            "COALESCE((SELECT -rank FROM {INFO_FTS_TABLE} \
+                            WHERE {INFO_FTS_TABLE} MATCH ? AND {INFO_FTS_TABLE}.media_id = {INFO_TABLE}.id), 0.0)"
+                        ),
+                        params: vec![Param::Text(text.join(" OR "))],
+                    }
+                } else {
+                    Clause {
+                        sql: ty.sql_key(),
+                        params: Vec::new(),
+                    }
+                };
+
+                (key, order)
+            })
+            .collect()
+    }
+}
+
+fn direction(order: &SortOrder) -> &'static str {
+    match order {
+        SortOrder::Ascending => "ASC",
+        SortOrder::Descending => "DESC",
+    }
+}
+
+/// Matches the results that come later than the cursor.
+fn comes_after(keys: &[(Clause, &SortOrder)], cursor: &Cursor) -> Result<Clause, RavesError> {
+    keyset(keys, cursor, |order| match order {
+        SortOrder::Ascending => ">",
+        SortOrder::Descending => "<",
+    })
+}
+
+/// Matches the results that come earlier than the cursor.
+fn comes_before(keys: &[(Clause, &SortOrder)], cursor: &Cursor) -> Result<Clause, RavesError> {
+    keyset(keys, cursor, |order| match order {
+        SortOrder::Ascending => "<",
+        SortOrder::Descending => ">",
+    })
+}
+
+/// Compares each result to the cursor, key by key.
+///
+/// Keys can go in different directions, so a row value comparison won't
+/// work. Instead, a result passes if it ties on the first few keys, then
+/// passes `op` on the next one. The `id` is the very last key, and it always
+/// goes up.
+fn keyset(
+    keys: &[(Clause, &SortOrder)],
+    cursor: &Cursor,
+    op: impl Fn(&SortOrder) -> &'static str,
+) -> Result<Clause, RavesError> {
+    if cursor.keys.len() != keys.len() {
+        return Err(RavesError::MismatchedCursor {
+            expected: keys.len(),
+            got: cursor.keys.len(),
+        });
+    }
+
+    let compare = |key: &Clause, op: &str, value: Param| Clause {
+        sql: format!("{} {op} ?", key.sql),
+        params: key.params.iter().cloned().chain([value]).collect(),
+    };
+    let id = Clause {
+        sql: format!("{INFO_TABLE}.id"),
+        params: Vec::new(),
+    };
+
+    let branches = (0..=keys.len()).map(|i| {
+        let ties = keys[..i]
+            .iter()
+            .zip(&cursor.keys)
+            .map(|((key, _), value)| compare(key, "=", value.clone()));
+
+        let next = match keys.get(i) {
+            Some((key, order)) => compare(key, op(order), cursor.keys[i].clone()),
+            None => compare(&id, op(&SortOrder::Ascending), Param::Uuid(cursor.id)),
+        };
+
+        Clause::join(ties.chain([next]), "AND", "1")
+    });
+
+    Ok(Clause::join(branches, "OR", "0"))
+}
+
+/// Finds the text that results should be ranked against.
+///
+/// Text under a `NOT` is skipped, since results won't match it anyway.
+fn search_text(exprs: &[Expr]) -> Vec<&str> {
+    exprs
+        .iter()
+        .flat_map(|expr| match expr {
+            Expr::Collection(CollectionModifier::Literal(text)) => vec![text.as_str()],
+            Expr::Boolean(BooleanModifier::Any(exprs) | BooleanModifier::All(exprs)) => {
+                search_text(exprs)
+            }
+            _ => Vec::new(),
+        })
+        .collect()
+}
+
+/// Reads the `sort_key_*` columns from a result row.
+fn sort_keys(row: &SqliteRow, count: usize) -> Result<Vec<Param>, DatabaseError> {
+    (0..count)
+        .map(|i| {
+            let column = format!("sort_key_{i}");
+            let raw = row
+                .try_get_raw(column.as_str())
+                .map_err(DatabaseError::QueryFailed)?;
+
+            let key = match raw.type_info().name() {
+                "INTEGER" => row.try_get(column.as_str()).map(Param::Integer),
+                "REAL" => row.try_get(column.as_str()).map(Param::Real),
+                "BLOB" => row.try_get(column.as_str()).map(Param::Uuid),
+                _ => row.try_get(column.as_str()).map(Param::Text),
+            };
+
+            key.map_err(DatabaseError::QueryFailed)
+        })
+        .collect()
+}
@@ -0,0 +1,267 @@
+//! Finds EXIF fields in a media file's `other_metadata`.
+//!
+//! Each metadata extractor stores its fields under different keys:
+//! `kamadak-exif` uses the tag's name (like `Model`), while `nom-exif` uses
+//! its number (like `272`). Searches always use the names, so this module
+//! maps them onto every key they might be stored under.
+
+/// Every EXIF tag that `kamadak-exif` knows about, by name, with the number
+/// that `nom-exif` stores it under.
+///
+/// GPS and interoperability tags don't have a number here, since they reuse
+/// numbers from each other. (`nom-exif` can't tell them apart!)
+const TAGS: &[(&str, Option<u16>)] = &[
+    ("ExifIFDPointer", Some(0x8769)),
+    ("GPSInfoIFDPointer", Some(0x8825)),
+    ("InteropIFDPointer", Some(0xa005)),
+    ("ImageWidth", Some(0x100)),
+    ("ImageLength", Some(0x101)),
+    ("BitsPerSample", Some(0x102)),
+    ("Compression", Some(0x103)),
+    ("PhotometricInterpretation", Some(0x106)),
+    ("ImageDescription", Some(0x10e)),
+    ("Make", Some(0x10f)),
+    ("Model", Some(0x110)),
+    ("StripOffsets", Some(0x111)),
+    ("Orientation", Some(0x112)),
+    ("SamplesPerPixel", Some(0x115)),
+    ("RowsPerStrip", Some(0x116)),
+    ("StripByteCounts", Some(0x117)),
+    ("XResolution", Some(0x11a)),
+    ("YResolution", Some(0x11b)),
+    ("PlanarConfiguration", Some(0x11c)),
+    ("ResolutionUnit", Some(0x128)),
+    ("TransferFunction", Some(0x12d)),
+    ("Software", Some(0x131)),
+    ("DateTime", Some(0x132)),
+    ("Artist", Some(0x13b)),
+    ("WhitePoint", Some(0x13e)),
+    ("PrimaryChromaticities", Some(0x13f)),
+    ("TileOffsets", Some(0x144)),
+    ("TileByteCounts", Some(0x145)),
+    ("JPEGInterchangeFormat", Some(0x201)),
+    ("JPEGInterchangeFormatLength", Some(0x202)),
+    ("YCbCrCoefficients", Some(0x211)),
+    ("YCbCrSubSampling", Some(0x212)),
+    ("YCbCrPositioning", Some(0x213)),
+    ("ReferenceBlackWhite", Some(0x214)),
+    ("Copyright", Some(0x8298)),
+    ("ExposureTime", Some(0x829a)),
+    ("FNumber", Some(0x829d)),
+    ("ExposureProgram", Some(0x8822)),
+    ("SpectralSensitivity", Some(0x8824)),
+    ("PhotographicSensitivity", Some(0x8827)),
+    ("OECF", Some(0x8828)),
+    ("SensitivityType", Some(0x8830)),
+    ("StandardOutputSensitivity", Some(0x8831)),
+    ("RecommendedExposureIndex", Some(0x8832)),
+    ("ISOSpeed", Some(0x8833)),
+    ("ISOSpeedLatitudeyyy", Some(0x8834)),
+    ("ISOSpeedLatitudezzz", Some(0x8835)),
+    ("ExifVersion", Some(0x9000)),
+    ("DateTimeOriginal", Some(0x9003)),
+    ("DateTimeDigitized", Some(0x9004)),
+    ("OffsetTime", Some(0x9010)),
+    ("OffsetTimeOriginal", Some(0x9011)),
+    ("OffsetTimeDigitized", Some(0x9012)),
+    ("ComponentsConfiguration", Some(0x9101)),
+    ("CompressedBitsPerPixel", Some(0x9102)),
+    ("ShutterSpeedValue", Some(0x9201)),
+    ("ApertureValue", Some(0x9202)),
+    ("BrightnessValue", Some(0x9203)),
+    ("ExposureBiasValue", Some(0x9204)),
+    ("MaxApertureValue", Some(0x9205)),
+    ("SubjectDistance", Some(0x9206)),
+    ("MeteringMode", Some(0x9207)),
+    ("LightSource", Some(0x9208)),
+    ("Flash", Some(0x9209)),
+    ("FocalLength", Some(0x920a)),
+    ("SubjectArea", Some(0x9214)),
+    ("MakerNote", Some(0x927c)),
+    ("UserComment", Some(0x9286)),
+    ("SubSecTime", Some(0x9290)),
+    ("SubSecTimeOriginal", Some(0x9291)),
+    ("SubSecTimeDigitized", Some(0x9292)),
+    ("Temperature", Some(0x9400)),
+    ("Humidity", Some(0x9401)),
+    ("Pressure", Some(0x9402)),
+    ("WaterDepth", Some(0x9403)),
+    ("Acceleration", Some(0x9404)),
+    ("CameraElevationAngle", Some(0x9405)),
+    ("FlashpixVersion", Some(0xa000)),
+    ("ColorSpace", Some(0xa001)),
+    ("PixelXDimension", Some(0xa002)),
+    ("PixelYDimension", Some(0xa003)),
+    ("RelatedSoundFile", Some(0xa004)),
+    ("FlashEnergy", Some(0xa20b)),
+    ("SpatialFrequencyResponse", Some(0xa20c)),
+    ("FocalPlaneXResolution", Some(0xa20e)),
+    ("FocalPlaneYResolution", Some(0xa20f)),
+    ("FocalPlaneResolutionUnit", Some(0xa210)),
+    ("SubjectLocation", Some(0xa214)),
+    ("ExposureIndex", Some(0xa215)),
+    ("SensingMethod", Some(0xa217)),
+    ("FileSource", Some(0xa300)),
+    ("SceneType", Some(0xa301)),
+    ("CFAPattern", Some(0xa302)),
+    ("CustomRendered", Some(0xa401)),
+    ("ExposureMode", Some(0xa402)),
+    ("WhiteBalance", Some(0xa403)),
+    ("DigitalZoomRatio", Some(0xa404)),
+    ("FocalLengthIn35mmFilm", Some(0xa405)),
+    ("SceneCaptureType", Some(0xa406)),
+    ("GainControl", Some(0xa407)),
+    ("Contrast", Some(0xa408)),
+    ("Saturation", Some(0xa409)),
+    ("Sharpness", Some(0xa40a)),
+    ("DeviceSettingDescription", Some(0xa40b)),
+    ("SubjectDistanceRange", Some(0xa40c)),
+    ("ImageUniqueID", Some(0xa420)),
+    ("CameraOwnerName", Some(0xa430)),
+    ("BodySerialNumber", Some(0xa431)),
+    ("LensSpecification", Some(0xa432)),
+    ("LensMake", Some(0xa433)),
+    ("LensModel", Some(0xa434)),
+    ("LensSerialNumber", Some(0xa435)),
+    ("CompositeImage", Some(0xa460)),
+    ("SourceImageNumberOfCompositeImage", Some(0xa461)),
+    ("SourceExposureTimesOfCompositeImage", Some(0xa462)),
+    ("Gamma", Some(0xa500)),
+    ("GPSVersionID", None),
+    ("GPSLatitudeRef", None),
+    ("GPSLatitude", None),
+    ("GPSLongitudeRef", None),
+    ("GPSLongitude", None),
+    ("GPSAltitudeRef", None),
+    ("GPSAltitude", None),
+    ("GPSTimeStamp", None),
+    ("GPSSatellites", None),
+    ("GPSStatus", None),
+    ("GPSMeasureMode", None),
+    ("GPSDOP", None),
+    ("GPSSpeedRef", None),
+    ("GPSSpeed", None),
+    ("GPSTrackRef", None),
+    ("GPSTrack", None),
+    ("GPSImgDirectionRef", None),
+    ("GPSImgDirection", None),
+    ("GPSMapDatum", None),
+    ("GPSDestLatitudeRef", None),
+    ("GPSDestLatitude", None),
+    ("GPSDestLongitudeRef", None),
+    ("GPSDestLongitude", None),
+    ("GPSDestBearingRef", None),
+    ("GPSDestBearing", None),
+    ("GPSDestDistanceRef", None),
+    ("GPSDestDistance", None),
+    ("GPSProcessingMethod", None),
+    ("GPSAreaInformation", None),
+    ("GPSDateStamp", None),
+    ("GPSDifferential", None),
+    ("GPSHPositioningError", None),
+    ("InteroperabilityIndex", None),
+    ("InteroperabilityVersion", None),
+    ("RelatedImageFileFormat", None),
+    ("RelatedImageWidth", None),
+    ("RelatedImageLength", None),
+];
+
+/// Friendlier names for some tags, with the tags they search.
+///
+/// Some tags were renamed between EXIF versions, so they search both.
+const ALIASES: &[(&str, &[&str])] = &[
+    ("ISO", &["PhotographicSensitivity", "ISOSpeed"]),
+    ("ISOSpeed", &["ISOSpeed", "PhotographicSensitivity"]),
+    ("ISOSpeedRatings", &["PhotographicSensitivity"]),
+    ("Lens", &["LensModel"]),
+    ("Aperture", &["FNumber"]),
+    ("ShutterSpeed", &["ExposureTime"]),
+];
+
+/// Finds the canonical name of an EXIF tag, ignoring case.
+///
+/// Tags we don't know about (like ones from other extractors) are returned
+/// as they were typed.
+pub(crate) fn canonical_name(name: &str) -> String {
+    ALIASES
+        .iter()
+        .map(|(alias, _)| *alias)
+        .chain(TAGS.iter().map(|(tag, _)| *tag))
+        .find(|known| known.eq_ignore_ascii_case(name))
+        .unwrap_or(name)
+        .to_string()
+}
+
+/// The JSON paths that the named tag's value might be at, in order.
+///
+/// Use [`canonical_name`] first!
+pub(crate) fn json_paths(name: &str) -> Vec<String> {
+    let name = [name];
+    let tags = ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == name[0])
+        .map_or(&name[..], |(_, tags)| *tags);
+
+    let mut paths = Vec::new();
+    for tag in tags {
+        paths.push(format!("$.\"{tag}\".value"));
+
+        let code = TAGS.iter().find(|(known, _)| known == tag);
+        if let Some((_, Some(code))) = code {
+            paths.push(format!("$.\"{code}\".value"));
+        }
+    }
+    paths
+}
+
+/// Parses a number as EXIF writes them, like `800`, `1.8`, or `1/100`.
+pub(crate) fn number(value: &str) -> Option<f64> {
+    let value = value.trim();
+    match value.split_once('/') {
+        Some((num, denom)) => {
+            let denom = denom.trim().parse::<f64>().ok().filter(|d| *d != 0.0)?;
+            Some(num.trim().parse::<f64>().ok()? / denom)
+        }
+        None => value.parse::<f64>().ok().filter(|n| n.is_finite()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_are_canonical() {
+        assert_eq!(canonical_name("model"), "Model");
+        assert_eq!(canonical_name("gpslatitude"), "GPSLatitude");
+        assert_eq!(canonical_name("iso"), "ISO");
+        assert_eq!(canonical_name("MakerThing"), "MakerThing");
+    }
+
+    #[test]
+    fn paths_cover_each_extractor() {
+        assert_eq!(
+            json_paths("Model"),
+            [r#"$."Model".value"#, r#"$."272".value"#]
+        );
+        assert_eq!(json_paths("GPSLatitude"), [r#"$."GPSLatitude".value"#]);
+        assert_eq!(
+            json_paths("ISO"),
+            [
+                r#"$."PhotographicSensitivity".value"#,
+                r#"$."34855".value"#,
+                r#"$."ISOSpeed".value"#,
+                r#"$."34867".value"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn numbers_can_be_fractions() {
+        assert_eq!(number("800"), Some(800.0));
+        assert_eq!(number("1/100"), Some(0.01));
+        assert_eq!(number("1/0"), None);
+        assert_eq!(number("Pixel 6"), None);
+        assert_eq!(number("inf"), None);
+    }
+}
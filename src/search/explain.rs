@@ -0,0 +1,264 @@
+//! Shows how a search runs, for when its results are surprising.
+//!
+//! [`explain`] compiles and runs an expression, then reports each step: the
+//! expression it ended up with, the SQL and its parameters, SQLite's query
+//! plan, and how long everything took. Its [`Display`](core::fmt::Display)
+//! output is meant for logs, and each field can be checked in tests.
+
+use core::{fmt, time::Duration};
+use std::time::Instant;
+
+use sqlx::FromRow as _;
+
+use crate::{
+    database::DATABASE,
+    error::{DatabaseError, RavesError},
+    models::media::Media,
+};
+
+use super::{
+    modifiers::{BooleanModifier, Expr, PreExecutionQuery},
+    query::Param,
+};
+
+/// Everything that happened when running an expression.
+#[derive(Clone, Debug, PartialEq, PartialOrd)]
+pub struct Explanation {
+    /// The expression after [`normalize`] tidied it up. This is what was
+    /// compiled.
+    pub normalized: Expr,
+    /// The generated SQL.
+    pub sql: String,
+    /// Values for each `?` placeholder in `sql`, in order.
+    pub params: Vec<Param>,
+    /// SQLite's `EXPLAIN QUERY PLAN` for the SQL.
+    pub plan: Vec<PlanStep>,
+    /// How many media files matched.
+    pub rows: usize,
+    /// How long SQLite took to find the results.
+    pub execution: Duration,
+    /// How long it took to turn the results into [`Media`].
+    pub decoding: Duration,
+}
+
+/// One line of SQLite's query plan, like `SCAN info`.
+///
+/// Steps form a tree: each one belongs to the step with its `parent` id, or
+/// to the top of the plan when `parent` is `0`.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PlanStep {
+    pub id: i64,
+    pub parent: i64,
+    pub detail: String,
+}
+
+/// Compiles and runs the given expression, explaining how it went.
+#[tracing::instrument]
+pub async fn explain(expr: &Expr) -> Result<Explanation, RavesError> {
+    let normalized = normalize(expr.clone());
+    let PreExecutionQuery { query, parameters } =
+        PreExecutionQuery::new(core::slice::from_ref(&normalized));
+
+    let mut conn = DATABASE.acquire().await.inspect_err(|e| {
+        tracing::error!("Failed to connect to database to explain search. err: {e}")
+    })?;
+
+    // ask for the plan first. this doesn't run the query
+    let plan_query = format!("EXPLAIN QUERY PLAN {query}");
+    let mut q = sqlx::query_as::<_, (i64, i64, i64, String)>(&plan_query);
+    for param in parameters.iter().cloned() {
+        q = q.bind(param);
+    }
+    let plan = q
+        .fetch_all(&mut *conn)
+        .await
+        .inspect_err(|e| tracing::error!("Failed to get query plan! err: {e}"))
+        .map_err(DatabaseError::QueryFailed)?
+        .into_iter()
+        .map(|(id, parent, _unused, detail)| PlanStep { id, parent, detail })
+        .collect();
+
+    // then run it for real, timing the database and decoding separately
+    let start = Instant::now();
+    let mut q = sqlx::query(&query);
+    for param in parameters.iter().cloned() {
+        q = q.bind(param);
+    }
+    let rows = q
+        .fetch_all(&mut *conn)
+        .await
+        .inspect_err(|e| tracing::error!("Failed to run explained search! err: {e}"))
+        .map_err(DatabaseError::QueryFailed)?;
+    let execution = start.elapsed();
+
+    let start = Instant::now();
+    let media = rows
+        .iter()
+        .map(Media::from_row)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(DatabaseError::QueryFailed)?;
+    let decoding = start.elapsed();
+
+    let explanation = Explanation {
+        normalized,
+        sql: query,
+        params: parameters,
+        plan,
+        rows: media.len(),
+        execution,
+        decoding,
+    };
+    tracing::debug!("explained search:\n{explanation}");
+    Ok(explanation)
+}
+
+/// Tidies up an expression without changing what it matches.
+///
+/// - Groups with one expression are replaced by that expression.
+/// - Groups nested in the same kind of group are merged into it, so
+///   `a OR (b OR c)` becomes `a OR b OR c`.
+/// - Double negatives cancel out.
+pub fn normalize(expr: Expr) -> Expr {
+    match expr {
+        Expr::Boolean(BooleanModifier::Not(inner)) => match normalize(*inner) {
+            Expr::Boolean(BooleanModifier::Not(inner)) => *inner,
+            inner => Expr::Boolean(BooleanModifier::Not(Box::new(inner))),
+        },
+
+        Expr::Boolean(BooleanModifier::Any(exprs)) => {
+            group(exprs, BooleanModifier::Any, |expr| match expr {
+                Expr::Boolean(BooleanModifier::Any(exprs)) => Ok(exprs),
+                other => Err(other),
+            })
+        }
+
+        Expr::Boolean(BooleanModifier::All(exprs)) => {
+            group(exprs, BooleanModifier::All, |expr| match expr {
+                Expr::Boolean(BooleanModifier::All(exprs)) => Ok(exprs),
+                other => Err(other),
+            })
+        }
+
+        other => other,
+    }
+}
+
+/// Normalizes a group's expressions, merging in any that `nested` says are
+/// the same kind of group.
+fn group(
+    exprs: Vec<Expr>,
+    make: fn(Vec<Expr>) -> BooleanModifier,
+    nested: fn(Expr) -> Result<Vec<Expr>, Expr>,
+) -> Expr {
+    let mut flat = Vec::with_capacity(exprs.len());
+    for expr in exprs.into_iter().map(normalize) {
+        match nested(expr) {
+            Ok(inner) => flat.extend(inner),
+            Err(expr) => flat.push(expr),
+        }
+    }
+
+    if flat.len() == 1 {
+        flat.remove(0)
+    } else {
+        Expr::Boolean(make(flat))
+    }
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "expression:")?;
+        write_expr(f, &self.normalized, 1)?;
+
+        writeln!(f, "sql: {}", self.sql)?;
+        writeln!(f, "params: {:?}", self.params)?;
+
+        writeln!(f, "plan:")?;
+        write_plan(f, &self.plan, 0, 1)?;
+
+        write!(
+            f,
+            "{} row(s) in {:?} (+ {:?} decoding)",
+            self.rows, self.execution, self.decoding
+        )
+    }
+}
+
+/// Writes an expression as an indented tree.
+fn write_expr(f: &mut fmt::Formatter<'_>, expr: &Expr, depth: usize) -> fmt::Result {
+    let indent = "  ".repeat(depth);
+    let (name, children) = match expr {
+        Expr::Boolean(BooleanModifier::Not(inner)) => ("NOT", core::slice::from_ref(&**inner)),
+        Expr::Boolean(BooleanModifier::Any(exprs)) => ("OR", exprs.as_slice()),
+        Expr::Boolean(BooleanModifier::All(exprs)) => ("AND", exprs.as_slice()),
+        Expr::Collection(modifier) => return writeln!(f, "{indent}{modifier:?}"),
+        Expr::Other(modifier) => return writeln!(f, "{indent}{modifier:?}"),
+    };
+
+    writeln!(f, "{indent}{name}")?;
+    children
+        .iter()
+        .try_for_each(|child| write_expr(f, child, depth + 1))
+}
+
+/// Writes the plan steps under `parent` as an indented tree.
+fn write_plan(
+    f: &mut fmt::Formatter<'_>,
+    plan: &[PlanStep],
+    parent: i64,
+    depth: usize,
+) -> fmt::Result {
+    for step in plan.iter().filter(|step| step.parent == parent) {
+        writeln!(f, "{}{}", "  ".repeat(depth), step.detail)?;
+        write_plan(f, plan, step.id, depth + 1)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::search::parse::parse;
+
+    use super::*;
+
+    /// Parses a query into one expression.
+    fn expr(input: &str) -> Expr {
+        let mut exprs = parse(input).unwrap();
+        if exprs.len() == 1 {
+            exprs.remove(0)
+        } else {
+            Expr::Boolean(BooleanModifier::All(exprs))
+        }
+    }
+
+    #[test]
+    fn groups_are_flattened() {
+        assert_eq!(
+            normalize(expr("tag:a (tag:b (tag:c tag:d))")),
+            expr("tag:a tag:b tag:c tag:d")
+        );
+        assert_eq!(
+            normalize(expr("tag:a OR (tag:b OR tag:c) OR (tag:d tag:e)")),
+            Expr::Boolean(BooleanModifier::Any(vec![
+                expr("tag:a"),
+                expr("tag:b"),
+                expr("tag:c"),
+                expr("tag:d tag:e"),
+            ]))
+        );
+        assert_eq!(
+            normalize(Expr::Boolean(BooleanModifier::Any(vec![expr("tag:a")]))),
+            expr("tag:a")
+        );
+    }
+
+    #[test]
+    fn double_negatives_cancel() {
+        assert_eq!(normalize(expr("NOT -tag:a")), expr("tag:a"));
+        assert_eq!(normalize(expr("NOT NOT -tag:a")), expr("-tag:a"));
+        assert_eq!(
+            normalize(expr("-(-tag:a OR -tag:a)")),
+            expr("-(-tag:a OR -tag:a)")
+        );
+    }
+}
@@ -0,0 +1,183 @@
+//! Counts search results by group, like how many are videos or were taken
+//! in 2023.
+//!
+//! Facets are counted in the database using the same `WHERE` clause as the
+//! search itself, so their numbers always match the results.
+
+use jiff::Zoned;
+
+use crate::{
+    database::{DATABASE, INFO_TABLE, TAGS_TABLE},
+    error::{DatabaseError, RavesError},
+};
+
+use super::{
+    execute::Search,
+    groups::local_creation_date,
+    query::{tagged, Clause, Param, CAMERA_MODEL},
+};
+
+/// A way to group search results.
+///
+/// Implement this to add your own facets!
+pub trait Facet {
+    /// What this facet is called, like `"year"`.
+    fn name(&self) -> &str;
+
+    /// SQL for a result's value in this facet.
+    ///
+    /// Results where this is `NULL` aren't counted.
+    fn value(&self) -> String;
+
+    /// Parameters for any `?` in the [`Facet::value`].
+    fn params(&self) -> Vec<Param> {
+        Vec::new()
+    }
+
+    /// Something to join onto the [`INFO_TABLE`] when a result can have many
+    /// values, like its tags.
+    fn join(&self) -> Option<String> {
+        None
+    }
+}
+
+/// The facets that Raves comes with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum BuiltinFacet {
+    /// `Photo`, `AnimatedPhoto`, or `Video`.
+    Kind,
+    /// The full MIME type, like `image/jpeg`.
+    MimeType,
+    /// The local year the media was created, like [`GroupBy::Year`].
+    ///
+    /// [`GroupBy::Year`]: super::groups::GroupBy::Year
+    Year,
+    /// `portrait`, `landscape`, or `square`.
+    Orientation,
+    /// Each tag's name. Like `tag:` searches, this includes implied tags,
+    /// but not excluded ones.
+    Tag,
+    /// The camera model from EXIF.
+    Camera,
+}
+
+impl BuiltinFacet {
+    pub const ALL: [BuiltinFacet; 6] = [
+        BuiltinFacet::Kind,
+        BuiltinFacet::MimeType,
+        BuiltinFacet::Year,
+        BuiltinFacet::Orientation,
+        BuiltinFacet::Tag,
+        BuiltinFacet::Camera,
+    ];
+}
+
+impl Facet for BuiltinFacet {
+    fn name(&self) -> &str {
+        match self {
+            BuiltinFacet::Kind => "kind",
+            BuiltinFacet::MimeType => "mime_type",
+            BuiltinFacet::Year => "year",
+            BuiltinFacet::Orientation => "orientation",
+            BuiltinFacet::Tag => "tag",
+            BuiltinFacet::Camera => "camera",
+        }
+    }
+
+    fn value(&self) -> String {
+        match self {
+            BuiltinFacet::Kind => format!("json_extract({INFO_TABLE}.format, '$.media_kind')"),
+            // we only store the part after the slash
+            BuiltinFacet::MimeType => format!(
+                "CASE json_extract({INFO_TABLE}.format, '$.media_kind') \
+                WHEN 'Video' THEN 'video/' ELSE 'image/' END \
+                || json_extract({INFO_TABLE}.format, '$.mime_type')"
+            ),
+            BuiltinFacet::Year => local_creation_date("%Y", &Zoned::now()).sql,
+            BuiltinFacet::Orientation => format!(
+                "CASE \
+                WHEN {INFO_TABLE}.width_px < {INFO_TABLE}.height_px THEN 'portrait' \
+                WHEN {INFO_TABLE}.width_px > {INFO_TABLE}.height_px THEN 'landscape' \
+                ELSE 'square' END"
+            ),
+            BuiltinFacet::Tag => "facet_tag.name".into(),
+            BuiltinFacet::Camera => format!("NULLIF({CAMERA_MODEL}, '')"),
+        }
+    }
+
+    fn params(&self) -> Vec<Param> {
+        match self {
+            BuiltinFacet::Year => local_creation_date("%Y", &Zoned::now()).params,
+            _ => Vec::new(),
+        }
+    }
+
+    fn join(&self) -> Option<String> {
+        match self {
+            BuiltinFacet::Tag => Some(format!(
+                "({} SELECT media_id, id FROM tagged) AS facet_mt \
+                JOIN {TAGS_TABLE} AS facet_tag \
+                ON facet_tag.id = facet_mt.id AND facet_mt.media_id = {INFO_TABLE}.id",
+                tagged("1")
+            )),
+            _ => None,
+        }
+    }
+}
+
+/// The counts for one facet.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FacetCounts {
+    /// The facet's name.
+    pub facet: String,
+    /// Each value, with how many results have it. The most common values
+    /// come first.
+    pub counts: Vec<(String, u64)>,
+}
+
+impl Search {
+    /// Counts this search's results for each facet.
+    #[tracing::instrument(skip_all)]
+    pub async fn facets(&self, facets: &[&dyn Facet]) -> Result<Vec<FacetCounts>, RavesError> {
+        let Clause { sql, params } = Clause::all(&self.exprs);
+
+        let mut conn = DATABASE.acquire().await.inspect_err(|e| {
+            tracing::error!("Failed to connect to database for facets. err: {e}")
+        })?;
+
+        let mut all_counts = Vec::with_capacity(facets.len());
+        for facet in facets {
+            let join = facet.join().map(|j| format!(", {j}")).unwrap_or_default();
+            let query = format!(
+                "SELECT {} AS facet_value, COUNT(DISTINCT {INFO_TABLE}.id) AS count \
+                FROM {INFO_TABLE}{join} \
+                WHERE ({sql}) AND facet_value IS NOT NULL \
+                GROUP BY facet_value \
+                ORDER BY count DESC, facet_value",
+                facet.value()
+            );
+
+            let mut q = sqlx::query_as::<_, (String, i64)>(&query);
+            for param in facet.params().into_iter().chain(params.iter().cloned()) {
+                q = q.bind(param);
+            }
+            let counts = q
+                .fetch_all(&mut *conn)
+                .await
+                .inspect_err(|e| {
+                    tracing::error!("Failed to count facet `{}`! err: {e}", facet.name())
+                })
+                .map_err(DatabaseError::QueryFailed)?;
+
+            all_counts.push(FacetCounts {
+                facet: facet.name().to_string(),
+                counts: counts
+                    .into_iter()
+                    .map(|(value, count)| (value, count as u64))
+                    .collect(),
+            });
+        }
+
+        Ok(all_counts)
+    }
+}
@@ -0,0 +1,163 @@
+//! Splits sorted search results into groups, like days or folders, for
+//! section headers and scrubbers.
+//!
+//! Groups are found in the database, so only their keys and sizes come back,
+//! not every result.
+
+use jiff::Zoned;
+
+use crate::{
+    database::{DATABASE, INFO_TABLE},
+    error::{DatabaseError, RavesError},
+};
+
+use super::{
+    dates,
+    execute::Search,
+    query::{Clause, PARENT_FOLDER},
+};
+
+/// What to group results by.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum GroupBy {
+    /// The local day a media file was created, like `2024-06-14`.
+    Day,
+    /// The local month a media file was created, like `2024-06`.
+    Month,
+    /// The local year a media file was created, like `2024`.
+    Year,
+    /// The folder a media file is in, like `/sdcard/DCIM/Camera/`.
+    Folder,
+    /// Where a photo was taken, from its EXIF GPS tags. Coordinates are
+    /// rounded to about 10 km, like `48.9, 2.4`.
+    Place,
+}
+
+/// A run of results that share a key.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Group {
+    /// What the results have in common. When this is `None`, they don't have
+    /// one (like photos without GPS tags).
+    pub key: Option<String>,
+    /// How many results are in this group.
+    pub count: u64,
+    /// Where the group's first result is in the search results.
+    pub offset: u64,
+}
+
+impl GroupBy {
+    /// SQL for each result's key, with its parameters.
+    fn key(&self, now: &Zoned) -> Clause {
+        let local = |format: &str| local_creation_date(format, now);
+
+        match self {
+            GroupBy::Day => local("%Y-%m-%d"),
+            GroupBy::Month => local("%Y-%m"),
+            GroupBy::Year => local("%Y"),
+            GroupBy::Folder => Clause {
+                sql: PARENT_FOLDER.into(),
+                params: Vec::new(),
+            },
+            GroupBy::Place => Clause {
+                sql: format!(
+                    "CASE WHEN {lat} IS NOT NULL AND {lon} IS NOT NULL \
+                    THEN printf('%.1f, %.1f', {lat}, {lon}) END",
+                    lat = gps("GPSLatitude", 'S'),
+                    lon = gps("GPSLongitude", 'W'),
+                ),
+                params: Vec::new(),
+            },
+        }
+    }
+}
+
+/// SQL for the local time a media file was created, formatted with
+/// `strftime`, like `%Y-%m-%d`.
+pub(crate) fn local_creation_date(format: &str, now: &Zoned) -> Clause {
+    local_date(&format!("{INFO_TABLE}.creation_date"), format, now)
+}
+
+/// SQL for the local time of a UTC date column, formatted with `strftime`.
+///
+/// Each date is shifted by the offset `now`'s time zone had back then, so
+/// dates near a DST change land on the right day.
+pub(crate) fn local_date(column: &str, format: &str, now: &Zoned) -> Clause {
+    Clause {
+        sql: format!(
+            "strftime('{format}', {column}, ({}) || ' seconds')",
+            dates::local_offset(column, now)
+        ),
+        params: Vec::new(),
+    }
+}
+
+/// Reads a GPS coordinate in decimal degrees.
+///
+/// `kamadak-exif` stores these like `48 deg 51 min 29.4 sec`, with the
+/// direction in a separate `...Ref` tag. `negative` is the direction that
+/// makes it negative.
+fn gps(tag: &str, negative: char) -> String {
+    let value = format!("json_extract({INFO_TABLE}.other_metadata, '$.{tag}.value')");
+    let reference = format!("json_extract({INFO_TABLE}.other_metadata, '$.{tag}Ref.value')");
+
+    format!(
+        "(CASE WHEN instr({reference}, '{negative}') THEN -1 ELSE 1 END * (\
+        CAST({value} AS REAL) + \
+        CAST(substr({value}, instr({value}, 'deg ') + 4) AS REAL) / 60.0 + \
+        CAST(substr({value}, instr({value}, 'min ') + 4) AS REAL) / 3600.0))"
+    )
+}
+
+impl Search {
+    /// Splits this search's results into groups, in the order they're
+    /// sorted.
+    ///
+    /// Each group is a run of results next to each other, so sort by the same
+    /// thing you're grouping by! Otherwise, a key can show up in more than
+    /// one group.
+    #[tracing::instrument(skip(self))]
+    pub async fn groups(&self, by: GroupBy) -> Result<Vec<Group>, RavesError> {
+        let key = by.key(&Zoned::now());
+        let order = self.order_by();
+        let Clause { sql, params } = Clause::all(&self.exprs);
+
+        // number the results, mark where the key changes, then count up the
+        // changes to find each run
+        let query = format!(
+            "SELECT group_key, COUNT(*) AS count, MIN(position) AS first FROM (\
+                SELECT group_key, position, SUM(starts) OVER (ORDER BY position) AS run FROM (\
+                    SELECT group_key, position, \
+                    CASE WHEN group_key IS LAG(group_key) OVER (ORDER BY position) \
+                    THEN 0 ELSE 1 END AS starts FROM (\
+                        SELECT {} AS group_key, ROW_NUMBER() OVER (ORDER BY {}) - 1 AS position \
+                        FROM {INFO_TABLE} WHERE {sql}\
+                    )\
+                )\
+            ) GROUP BY run ORDER BY first",
+            key.sql, order.sql
+        );
+
+        let mut conn = DATABASE.acquire().await.inspect_err(|e| {
+            tracing::error!("Failed to connect to database for groups. err: {e}")
+        })?;
+
+        let mut q = sqlx::query_as::<_, (Option<String>, i64, i64)>(&query);
+        for param in key.params.into_iter().chain(order.params).chain(params) {
+            q = q.bind(param);
+        }
+        let groups = q
+            .fetch_all(&mut *conn)
+            .await
+            .inspect_err(|e| tracing::error!("Failed to group search results! err: {e}"))
+            .map_err(DatabaseError::QueryFailed)?;
+
+        Ok(groups
+            .into_iter()
+            .map(|(key, count, offset)| Group {
+                key,
+                count: count as u64,
+                offset: offset as u64,
+            })
+            .collect())
+    }
+}
@@ -1,9 +1,17 @@
 //! Search utilities for Raves.
 
+pub mod dates;
 pub mod details;
+pub mod execute;
+mod exif;
+pub mod explain;
+pub mod facets;
+pub mod groups;
 pub mod modifiers;
-// pub mod query;
+pub mod parse;
+pub mod query;
 pub mod sort;
+pub mod suggest;
 
 /// `modifier1 AND modifier2`
 pub struct AndBlock();
@@ -1,4 +1,10 @@
-use super::details::{DateDetail, FormatDetail, KindDetail, TagDetail};
+use super::{
+    details::{
+        CompressionDetail, DateDetail, ExifDetail, FileSizeDetail, FormatDetail, FramerateDetail,
+        KindDetail, RangeDetail, TagDetail,
+    },
+    query::Param,
+};
 
 #[derive(Clone, Debug, PartialEq, PartialOrd)]
 pub enum DateTimeModifier {
@@ -7,6 +13,23 @@ pub enum DateTimeModifier {
     After(DateDetail),
 }
 
+/// Compares a number about the media, like its size or length.
+#[derive(Clone, Debug, PartialEq, PartialOrd)]
+pub enum NumericModifier {
+    FileSize(RangeDetail<FileSizeDetail>),
+    /// In pixels.
+    Width(RangeDetail<u32>),
+    /// In pixels.
+    Height(RangeDetail<u32>),
+    /// Width times height, in millions of pixels.
+    Megapixels(RangeDetail<f64>),
+    /// In seconds. Only videos have a duration.
+    Duration(RangeDetail<f64>),
+    Framerate(RangeDetail<FramerateDetail>),
+    /// From 0 to 5 stars. Media that hasn't been rated has 0.
+    Rating(RangeDetail<u8>),
+}
+
 /// A collection modifier directly queries a media based on its metadata.
 #[derive(Clone, Debug, PartialEq, PartialOrd)]
 pub enum CollectionModifier {
@@ -17,16 +40,23 @@ pub enum CollectionModifier {
     Format(FormatDetail),
     Kind(KindDetail),
     Orientation(String),
+    Numeric(NumericModifier),
+    /// The camera model from EXIF, like "Pixel 6".
+    Camera(String),
+    Compression(CompressionDetail),
+    /// Any EXIF field, like `exif:ISOSpeed>800`.
+    Exif(ExifDetail),
 }
 
 /// A modifier that applies `OR`/`NOT`` logic to modifier expressions.
 ///
-/// Note that `AND` is implied by the search itself. It isn't provided here
-/// for that reason.
+/// Note that `AND` is implied by the search itself. `All` only exists for
+/// groups nested inside other modifiers, like `(tag:a tag:b) OR tag:c`.
 #[derive(Clone, Debug, PartialEq, PartialOrd)]
 pub enum BooleanModifier {
     Not(Box<Expr>),
     Any(Vec<Expr>),
+    All(Vec<Expr>),
     // Related(Box<Expr>), // TODO: implement this! it's cool
 }
 
@@ -49,17 +79,12 @@ pub enum Expr {
     Other(OtherModifier),
 }
 
+/// A search that's been compiled into SQL, but not yet executed.
+///
+/// See [`PreExecutionQuery::new`].
+#[derive(Clone, Debug, PartialEq, PartialOrd)]
 pub struct PreExecutionQuery {
     pub query: String,
-    // pub parameters: Vec<Value>, // FIXME: no clue what i was cookin here. `surrealql::Value`..?
+    /// Values for each `?` placeholder in `query`, in order.
+    pub parameters: Vec<Param>,
 }
-
-// /// A modifier must become a query to be used.
-// ///
-// /// All modifiers must implement this trait!
-// pub trait ToQuery {
-//     /// Converts the modifier into a query for use in querying the database.
-//     ///
-//     /// This assumes that each modifier can become a query string.
-//     fn to_query(&self) -> Result<Query, surrealdb::error::Db>;
-// }
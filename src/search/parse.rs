@@ -0,0 +1,1146 @@
+//! Turns text from the search box into search expressions.
+//!
+//! Queries look like this:
+//!
+//! ```text
+//! kind:video tag:holiday after:2023-01-01 -orientation:portrait (tag:cat OR tag:dog)
+//! ```
+//!
+//! - Terms separated by spaces must *all* match.
+//! - `OR` between two terms means that either may match.
+//! - A leading `-` (or `NOT`) negates the term that follows it.
+//! - Parentheses group terms together.
+//! - Values containing spaces can be quoted: `tag:"new york"`.
+//! - Dates can be partial (`during:2023-06`), relative
+//!   (`taken:"last 30 days"`), or a time of day (`taken:"at night"`).
+//!   `created:`, `modified:`, `seen:`, and `taken:` also take `<` or `>`, like
+//!   `seen:>yesterday`.
+//! - Numbers can be compared or given a range, with units:
+//!   `size:>10MiB`, `megapixels:>=12`, `width:<1080`, `duration:10s..2m`,
+//!   `fps:>=60`. Either end of a range can be left open, like `duration:..5`.
+//! - EXIF fields can be searched by name: `exif:Model="Pixel 6"`,
+//!   `exif:ISOSpeed>800`, or `has:exif:GPSLatitude`.
+//! - Anything that isn't a `modifier:value` pair is searched as literal text.
+//!
+//! When something goes wrong, the returned error has a byte [`Span`] into the
+//! original input, so the app can underline the bad part.
+
+use jiff::{
+    civil::{Date, DateTime},
+    tz::TimeZone,
+    Timestamp, Zoned,
+};
+
+use crate::{error::QueryParseError, models::media::metadata::Framerate};
+
+use super::{
+    details::{
+        CalendarUnit, Comparison, CompressionDetail, DateDetail, DateValue, ExifDetail,
+        FileSizeDetail, FormatDetail, FramerateDetail, KindDetail, PartialDate, RangeDetail,
+        RelativeDate, TagDetail, TimeOfDay,
+    },
+    exif,
+    modifiers::{
+        BooleanModifier, CollectionModifier, DateTimeModifier, Expr, NumericModifier, OtherModifier,
+    },
+};
+
+/// A range of bytes in the query text.
+///
+/// `start` is inclusive, while `end` is exclusive.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub const fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+}
+
+impl core::fmt::Display for Span {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}..{}", self.start, self.end)
+    }
+}
+
+/// Parses the given query text into a list of expressions.
+///
+/// All returned expressions must match for a media file to be a result (i.e.
+/// they're implicitly `AND`ed together). An empty query gives an empty list.
+#[tracing::instrument]
+pub fn parse(input: &str) -> Result<Vec<Expr>, QueryParseError> {
+    let tokens = lex(input)?;
+    let mut parser = Parser { tokens, pos: 0 };
+    parser.sequence(None)
+}
+
+/// One piece of a lexed query.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Token {
+    pub kind: TokenKind,
+    pub span: Span,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum TokenKind {
+    /// `(`
+    Open,
+    /// `)`
+    Close,
+    /// `OR`
+    Or,
+    /// `NOT` or a leading `-`
+    Not,
+    /// A `key:value` pair or a plain word.
+    Term(Term),
+}
+
+/// A single search term, like `tag:cat` or `beach`.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Term {
+    /// The modifier name (before the colon), if there is one.
+    pub key: Option<(String, Span)>,
+    /// The value, with any quotes removed.
+    pub value: String,
+    /// Where the value sits in the input, including its quotes.
+    pub value_span: Span,
+    /// Whether any part of the value was quoted.
+    pub quoted: bool,
+}
+
+/// Splits the input into tokens.
+pub(crate) fn lex(input: &str) -> Result<Vec<Token>, QueryParseError> {
+    let mut tokens = Vec::new();
+    let mut chars = input.char_indices().peekable();
+
+    while let Some(&(start, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+
+        match c {
+            '(' | ')' => {
+                chars.next();
+                tokens.push(Token {
+                    kind: if c == '(' {
+                        TokenKind::Open
+                    } else {
+                        TokenKind::Close
+                    },
+                    span: Span::new(start, start + 1),
+                });
+            }
+
+            // a dash right before a term negates it
+            '-' if input[start + 1..]
+                .chars()
+                .next()
+                .is_some_and(|next| !next.is_whitespace() && next != ')') =>
+            {
+                chars.next();
+                tokens.push(Token {
+                    kind: TokenKind::Not,
+                    span: Span::new(start, start + 1),
+                });
+            }
+
+            _ => {
+                let term = lex_term(input, &mut chars)?;
+                let span = Span::new(
+                    term.key
+                        .as_ref()
+                        .map_or(term.value_span.start, |k| k.1.start),
+                    term.value_span.end,
+                );
+
+                // bare, unquoted operators
+                let kind = match (&term.key, term.quoted, term.value.as_str()) {
+                    (None, false, "OR") => TokenKind::Or,
+                    (None, false, "NOT") => TokenKind::Not,
+                    (None, false, "AND") => continue, // implied anyway
+                    _ => TokenKind::Term(term),
+                };
+
+                tokens.push(Token { kind, span });
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// Reads one term, stopping at whitespace or a parenthesis.
+fn lex_term(
+    input: &str,
+    chars: &mut core::iter::Peekable<core::str::CharIndices<'_>>,
+) -> Result<Term, QueryParseError> {
+    let start = chars.peek().map_or(input.len(), |&(i, _)| i);
+
+    // look for a `key:` prefix. keys are simple identifiers, so things like
+    // `12:30` stay plain values
+    let key_len = input[start..]
+        .char_indices()
+        .find(|&(_, c)| !(c.is_ascii_alphanumeric() || c == '_'))
+        .filter(|&(_, c)| c == ':')
+        .map(|(i, _)| i)
+        .filter(|&i| i > 0 && input[start..].starts_with(|c: char| c.is_ascii_alphabetic()));
+
+    let key = key_len.map(|len| {
+        // skip over the key and its colon
+        for _ in 0..=len {
+            chars.next();
+        }
+        (
+            input[start..start + len].to_ascii_lowercase(),
+            Span::new(start, start + len),
+        )
+    });
+
+    let value_start = chars.peek().map_or(input.len(), |&(i, _)| i);
+    let mut value = String::new();
+    let mut quoted = false;
+    let mut end = value_start;
+
+    while let Some(&(i, c)) = chars.peek() {
+        if c.is_whitespace() || c == '(' || c == ')' {
+            break;
+        }
+        chars.next();
+        end = i + c.len_utf8();
+
+        if c == '"' {
+            quoted = true;
+            loop {
+                match chars.next() {
+                    Some((j, '"')) => {
+                        end = j + 1;
+                        break;
+                    }
+                    Some((_, inner)) => value.push(inner),
+                    None => {
+                        return Err(QueryParseError::UnterminatedQuote {
+                            span: Span::new(i, input.len()),
+                        })
+                    }
+                }
+            }
+        } else {
+            value.push(c);
+        }
+    }
+
+    Ok(Term {
+        key,
+        value,
+        value_span: Span::new(value_start, end),
+        quoted,
+    })
+}
+
+/// A recursive-descent parser over lexed tokens.
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    /// Parses terms until the input ends or, when `open` is given, until the
+    /// matching close paren.
+    fn sequence(&mut self, open: Option<Span>) -> Result<Vec<Expr>, QueryParseError> {
+        let mut exprs = Vec::new();
+
+        loop {
+            match self.peek().map(|t| (&t.kind, t.span)) {
+                None => match open {
+                    Some(span) => return Err(QueryParseError::UnmatchedParen { span }),
+                    None => break,
+                },
+                Some((TokenKind::Close, span)) => {
+                    if open.is_none() {
+                        return Err(QueryParseError::UnmatchedParen { span });
+                    }
+                    self.next();
+                    break;
+                }
+                _ => exprs.push(self.any()?),
+            }
+        }
+
+        Ok(exprs)
+    }
+
+    /// `a OR b OR c`
+    fn any(&mut self) -> Result<Expr, QueryParseError> {
+        let mut exprs = vec![self.unary()?];
+
+        while let Some(Token {
+            kind: TokenKind::Or,
+            ..
+        }) = self.peek()
+        {
+            self.next();
+            exprs.push(self.unary()?);
+        }
+
+        Ok(if exprs.len() == 1 {
+            exprs.remove(0)
+        } else {
+            Expr::Boolean(BooleanModifier::Any(exprs))
+        })
+    }
+
+    /// `-a`, `NOT a`, `(a b)`, or just `a`.
+    fn unary(&mut self) -> Result<Expr, QueryParseError> {
+        let Some(token) = self.next() else {
+            // the caller consumed an operator, so point at that
+            let span = self.tokens.last().map(|t| t.span).unwrap_or_default();
+            return Err(QueryParseError::ExpectedTerm { span });
+        };
+
+        match token.kind {
+            TokenKind::Not => {
+                if self.peek().is_none() {
+                    return Err(QueryParseError::ExpectedTerm { span: token.span });
+                }
+                Ok(Expr::Boolean(BooleanModifier::Not(Box::new(self.unary()?))))
+            }
+
+            TokenKind::Open => {
+                let mut inner = self.sequence(Some(token.span))?;
+                match inner.len() {
+                    0 => Err(QueryParseError::ExpectedTerm {
+                        span: Span::new(token.span.start, self.tokens[self.pos - 1].span.end),
+                    }),
+                    1 => Ok(inner.remove(0)),
+                    _ => Ok(Expr::Boolean(BooleanModifier::All(inner))),
+                }
+            }
+
+            TokenKind::Term(term) => term_to_expr(term),
+
+            TokenKind::Close | TokenKind::Or => {
+                Err(QueryParseError::ExpectedTerm { span: token.span })
+            }
+        }
+    }
+}
+
+/// The name of every modifier (without aliases), for suggestions.
+pub(crate) const MODIFIERS: &[&str] = &[
+    "tag",
+    "person",
+    "tags",
+    "album",
+    "kind",
+    "format",
+    "ext",
+    "orientation",
+    "before",
+    "after",
+    "during",
+    "on",
+    "created",
+    "modified",
+    "seen",
+    "taken",
+    "camera",
+    "compression",
+    "size",
+    "width",
+    "height",
+    "megapixels",
+    "duration",
+    "fps",
+    "rating",
+    "exif",
+    "has",
+    "is",
+];
+
+/// Converts one term into its matching modifier.
+fn term_to_expr(term: Term) -> Result<Expr, QueryParseError> {
+    let Some((key, key_span)) = term.key else {
+        return Ok(Expr::Collection(CollectionModifier::Literal(term.value)));
+    };
+
+    let value = term.value;
+    let span = term.value_span;
+
+    if value.is_empty() {
+        return Err(QueryParseError::MissingValue {
+            modifier: key,
+            span: Span::new(key_span.start, span.end),
+        });
+    }
+
+    let invalid = |expected: &str| QueryParseError::InvalidValue {
+        modifier: key.clone(),
+        value: value.clone(),
+        expected: expected.to_string(),
+        span,
+    };
+
+    let modifier = match key.as_str() {
+        "tag" => CollectionModifier::Tag(TagDetail::TagName(value)),
+
+        "person" => match value.split_once(':') {
+            Some((person, marker)) if !person.is_empty() && !marker.is_empty() => {
+                CollectionModifier::Tag(TagDetail::PersonTagWithMarker(
+                    person.to_string(),
+                    marker.to_string(),
+                ))
+            }
+            Some(_) => return Err(invalid("a person, optionally followed by `:marker`")),
+            None => CollectionModifier::Tag(TagDetail::PersonTagName(value)),
+        },
+
+        "tags" => {
+            let (cmp, count) = comparison(&value);
+            let count = count
+                .parse::<u8>()
+                .map_err(|_| invalid("a tag count, like `3` or `>=2`"))?;
+            CollectionModifier::Tag(TagDetail::Count(count, cmp))
+        }
+
+        "album" | "folder" => CollectionModifier::Album(value),
+
+        "kind" | "type" => CollectionModifier::Kind(match value.to_lowercase().as_str() {
+            "image" | "photo" | "picture" => KindDetail::Image,
+            "video" => KindDetail::Video,
+            _ => return Err(invalid("`image` or `video`")),
+        }),
+
+        "format" | "mime" => {
+            CollectionModifier::Format(FormatDetail::MimeType(value.to_lowercase()))
+        }
+
+        "ext" | "extension" => CollectionModifier::Format(FormatDetail::Extension(
+            value.trim_start_matches('.').to_lowercase(),
+        )),
+
+        "orientation" => {
+            let value = value.to_lowercase();
+            match value.as_str() {
+                "portrait" | "landscape" | "square" => CollectionModifier::Orientation(value),
+                _ => return Err(invalid("`portrait`, `landscape`, or `square`")),
+            }
+        }
+
+        "before" | "after" | "during" | "on" => {
+            let date = date_value(&value).ok_or_else(|| invalid(EXPECTED_DATE))?;
+            let detail = DateDetail::Created(date);
+
+            CollectionModifier::DateTime(match key.as_str() {
+                "before" => DateTimeModifier::Before(detail),
+                "after" => DateTimeModifier::After(detail),
+                _ => DateTimeModifier::During(detail),
+            })
+        }
+
+        // these pick a date, then take `<` (before) or `>` (after)
+        "created" | "modified" | "seen" | "taken" | "captured" => {
+            let (cmp, date) = comparison(&value);
+            let date = date_value(date).ok_or_else(|| invalid(EXPECTED_DATE))?;
+
+            let detail = match key.as_str() {
+                "created" => DateDetail::Created(date),
+                "modified" => DateDetail::Modified(date),
+                "seen" => DateDetail::FirstSeen(date),
+                _ => DateDetail::Captured(date),
+            };
+
+            CollectionModifier::DateTime(match cmp {
+                Comparison::Less => DateTimeModifier::Before(detail),
+                Comparison::Greater => DateTimeModifier::After(detail),
+                Comparison::Equal => DateTimeModifier::During(detail),
+                _ => return Err(invalid("a date, optionally after `<` or `>`")),
+            })
+        }
+
+        "camera" | "model" => CollectionModifier::Camera(value),
+        "compression" => CollectionModifier::Compression(match value.to_lowercase().as_str() {
+            "lossless" => CompressionDetail::Lossless,
+            "lossy" => CompressionDetail::Lossy,
+            _ => return Err(invalid("`lossless` or `lossy`")),
+        }),
+
+        "size" | "filesize" => CollectionModifier::Numeric(NumericModifier::FileSize(
+            range(&value, filesize).ok_or_else(|| invalid("a file size, like `>10MiB`"))?,
+        )),
+        "width" => CollectionModifier::Numeric(NumericModifier::Width(
+            range(&value, pixels).ok_or_else(|| invalid("a number of pixels, like `<1080`"))?,
+        )),
+        "height" => CollectionModifier::Numeric(NumericModifier::Height(
+            range(&value, pixels).ok_or_else(|| invalid("a number of pixels, like `<1080`"))?,
+        )),
+        "megapixels" | "mp" => CollectionModifier::Numeric(NumericModifier::Megapixels(
+            range(&value, |v| unit(v, &["mp"]))
+                .ok_or_else(|| invalid("a number of megapixels, like `>=12`"))?,
+        )),
+        "duration" | "length" => CollectionModifier::Numeric(NumericModifier::Duration(
+            range(&value, duration).ok_or_else(|| invalid("a duration, like `10s..2m`"))?,
+        )),
+        "fps" | "framerate" => CollectionModifier::Numeric(NumericModifier::Framerate(
+            range(&value, |v| {
+                unit(v, &["fps"]).map(|fps| FramerateDetail(Framerate::from(fps)))
+            })
+            .ok_or_else(|| invalid("a framerate, like `>=60`"))?,
+        )),
+        "rating" | "stars" => CollectionModifier::Numeric(NumericModifier::Rating(
+            range(&value, |v| {
+                unit(v, &["star", "stars"])
+                    .filter(|stars| stars.fract() == 0.0 && *stars <= 5.0)
+                    .map(|stars| stars as u8)
+            })
+            .ok_or_else(|| invalid("a number of stars from 0 to 5, like `>=4`"))?,
+        )),
+
+        "exif" => CollectionModifier::Exif(
+            exif_detail(&value)
+                .ok_or_else(|| invalid("an EXIF field, like `Model=\"Pixel 6\"` or `ISO>800`"))?,
+        ),
+        "has" => match value.split_once(':') {
+            Some((prefix, field)) if prefix.eq_ignore_ascii_case("exif") && is_field(field) => {
+                CollectionModifier::Exif(ExifDetail::Has(exif::canonical_name(field)))
+            }
+            _ => return Err(invalid("an EXIF field, like `exif:GPSLatitude`")),
+        },
+
+        "is" => {
+            return Ok(Expr::Other(match value.to_lowercase().as_str() {
+                "favorite" | "favourite" | "fav" => OtherModifier::Favorite,
+                "untagged" => OtherModifier::Untagged,
+                "undated" => OtherModifier::Undated,
+                _ => return Err(invalid("`favorite`, `untagged`, or `undated`")),
+            }))
+        }
+
+        _ => {
+            return Err(QueryParseError::UnknownModifier {
+                modifier: key,
+                span: key_span,
+            })
+        }
+    };
+
+    Ok(Expr::Collection(modifier))
+}
+
+/// Splits a leading comparison operator off of the given value.
+///
+/// Values without an operator are compared for equality.
+pub(crate) fn comparison(value: &str) -> (Comparison, &str) {
+    // note: two-char operators must come first
+    [
+        (">=", Comparison::GreaterOrEqual),
+        ("<=", Comparison::LessOrEqual),
+        (">", Comparison::Greater),
+        ("<", Comparison::Less),
+        ("=", Comparison::Equal),
+    ]
+    .into_iter()
+    .find_map(|(op, cmp)| value.strip_prefix(op).map(|rest| (cmp, rest)))
+    .unwrap_or((Comparison::Equal, value))
+}
+
+/// Parses an EXIF field, optionally compared to a value, like `ISO>800`.
+///
+/// Only numbers can be compared with `<` or `>`.
+fn exif_detail(value: &str) -> Option<ExifDetail> {
+    let Some(op) = value.find(['<', '>', '=']) else {
+        return is_field(value).then(|| ExifDetail::Has(exif::canonical_name(value)));
+    };
+
+    let (field, rest) = value.split_at(op);
+    let (cmp, rest) = comparison(rest);
+    if !is_field(field) || rest.is_empty() {
+        return None;
+    }
+    if cmp != Comparison::Equal && exif::number(rest).is_none() {
+        return None;
+    }
+
+    Some(ExifDetail::Compare(
+        exif::canonical_name(field),
+        cmp,
+        rest.to_string(),
+    ))
+}
+
+/// EXIF field names are simple identifiers, like `GPSLatitude`.
+fn is_field(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Parses a comparison (`>=12`) or an inclusive range (`10..20`) of values.
+///
+/// Either side of a range can be left off to leave it open, so `..20` is
+/// anything up to `20`.
+fn range<T: PartialOrd>(value: &str, parse: impl Fn(&str) -> Option<T>) -> Option<RangeDetail<T>> {
+    if let Some((low, high)) = value.split_once("..") {
+        let side = |side: &str| match side.trim() {
+            "" => Some(None),
+            side => parse(side).map(Some),
+        };
+
+        return match (side(low)?, side(high)?) {
+            (Some(low), Some(high)) => (low <= high).then_some(RangeDetail::Between(low, high)),
+            (Some(low), None) => Some(RangeDetail::Compare(Comparison::GreaterOrEqual, low)),
+            (None, Some(high)) => Some(RangeDetail::Compare(Comparison::LessOrEqual, high)),
+            (None, None) => None,
+        };
+    }
+
+    let (cmp, value) = comparison(value);
+    parse(value).map(|v| RangeDetail::Compare(cmp, v))
+}
+
+/// Splits a (positive) number off the front of a value, returning it
+/// alongside the rest of the value.
+fn number(value: &str) -> Option<(f64, &str)> {
+    let end = value
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(value.len());
+    Some((value[..end].parse().ok()?, &value[end..]))
+}
+
+/// Parses a number, optionally followed by one of the given units.
+fn unit(value: &str, units: &[&str]) -> Option<f64> {
+    let (n, rest) = number(value.trim())?;
+    let rest = rest.trim();
+    (rest.is_empty() || units.iter().any(|u| rest.eq_ignore_ascii_case(u))).then_some(n)
+}
+
+/// Parses a whole number of pixels, like `1080` or `1080px`.
+fn pixels(value: &str) -> Option<u32> {
+    unit(value, &["px"])
+        .filter(|px| px.fract() == 0.0 && *px <= f64::from(u32::MAX))
+        .map(|px| px as u32)
+}
+
+/// Parses a file size with SI (`MB`) or IEC (`MiB`) units. Without a unit,
+/// it's in bytes.
+fn filesize(value: &str) -> Option<FileSizeDetail> {
+    let (n, unit) = number(value.trim())?;
+
+    let bytes: u64 = match unit.trim().to_lowercase().as_str() {
+        "" | "b" => 1,
+        "kb" => 1000,
+        "mb" => 1000_u64.pow(2),
+        "gb" => 1000_u64.pow(3),
+        "tb" => 1000_u64.pow(4),
+        "kib" => 1 << 10,
+        "mib" => 1 << 20,
+        "gib" => 1 << 30,
+        "tib" => 1 << 40,
+        _ => return None,
+    };
+
+    Some(FileSizeDetail((n * bytes as f64).round() as u64))
+}
+
+/// Parses a duration into seconds.
+///
+/// Takes `90` (seconds), `1:30` or `1:02:03` (like a clock), and units like
+/// `500ms`, `10s`, `2m`, `1h`, or `1h30m`.
+fn duration(value: &str) -> Option<f64> {
+    let value = value.trim();
+    if value.is_empty() {
+        return None;
+    }
+
+    if value.contains(':') {
+        let parts = value
+            .split(':')
+            .map(|part| part.parse::<f64>().ok().filter(|n| *n >= 0.0))
+            .collect::<Option<Vec<_>>>()?;
+
+        return (parts.len() <= 3).then(|| parts.iter().fold(0.0, |total, n| total * 60.0 + n));
+    }
+
+    let mut rest = value;
+    let mut seconds = 0.0;
+    while !rest.is_empty() {
+        let (n, after) = number(rest)?;
+
+        let unit_len = after
+            .find(|c: char| c.is_ascii_digit() || c == '.')
+            .unwrap_or(after.len());
+        let scale = match after[..unit_len].trim().to_lowercase().as_str() {
+            "ms" => 0.001,
+            "" | "s" | "sec" | "secs" => 1.0,
+            "m" | "min" | "mins" => 60.0,
+            "h" | "hr" | "hrs" => 3600.0,
+            _ => return None,
+        };
+
+        seconds += n * scale;
+        rest = &after[unit_len..];
+    }
+
+    Some(seconds)
+}
+
+const EXPECTED_DATE: &str = "a date, like `2023-06`, `yesterday`, or `at night`";
+
+/// Parses any kind of date value.
+///
+/// See [`DateValue`] for what these look like.
+fn date_value(value: &str) -> Option<DateValue> {
+    let words = value.trim().to_lowercase();
+
+    if let Some(window) = time_of_day(&words) {
+        return Some(DateValue::TimeOfDay(window));
+    }
+    if let Some(relative) = relative_date(&words) {
+        return Some(DateValue::Relative(relative));
+    }
+    if let Some(partial) = partial_date(value) {
+        return Some(DateValue::Partial(partial));
+    }
+
+    datetime(value).map(DateValue::Instant)
+}
+
+/// Parses a named time of day (`at night`) or a range (`09:00..12:00`).
+fn time_of_day(value: &str) -> Option<TimeOfDay> {
+    if let Some((start, end)) = value.split_once("..") {
+        return Some(TimeOfDay::new(start.parse().ok()?, end.parse().ok()?));
+    }
+
+    let name = value
+        .strip_prefix("at ")
+        .or_else(|| value.strip_prefix("in the "))
+        .unwrap_or(value);
+
+    Some(match name {
+        "morning" => TimeOfDay::MORNING,
+        "afternoon" => TimeOfDay::AFTERNOON,
+        "evening" => TimeOfDay::EVENING,
+        "night" => TimeOfDay::NIGHT,
+        _ => return None,
+    })
+}
+
+/// Parses stuff like `yesterday`, `this month`, or `last 30 days`.
+fn relative_date(value: &str) -> Option<RelativeDate> {
+    let unit = |word: &str| match word {
+        "week" => Some(CalendarUnit::Week),
+        "month" => Some(CalendarUnit::Month),
+        "year" => Some(CalendarUnit::Year),
+        _ => None,
+    };
+
+    Some(
+        match value.split_whitespace().collect::<Vec<_>>().as_slice() {
+            ["today"] => RelativeDate::Today,
+            ["yesterday"] => RelativeDate::Yesterday,
+            ["this", word] => RelativeDate::This(unit(word)?),
+            ["last", word] => RelativeDate::Last(unit(word)?),
+            ["last" | "past", n, "day" | "days"] => {
+                RelativeDate::LastDays(n.parse().ok().filter(|n| *n > 0)?)
+            }
+            _ => return None,
+        },
+    )
+}
+
+/// Parses `YYYY`, `YYYY-MM`, or `YYYY-MM-DD`.
+fn partial_date(value: &str) -> Option<PartialDate> {
+    let number = |part: &str, len: usize| {
+        (part.len() == len && part.bytes().all(|b| b.is_ascii_digit()))
+            .then(|| part.parse::<i16>().ok())
+            .flatten()
+    };
+
+    let partial = match value.split('-').collect::<Vec<_>>().as_slice() {
+        [year] => PartialDate {
+            year: number(year, 4)?,
+            month: None,
+            day: None,
+        },
+        [year, month] => PartialDate {
+            year: number(year, 4)?,
+            month: Some(number(month, 2)? as i8),
+            day: None,
+        },
+        [year, month, day] => PartialDate {
+            year: number(year, 4)?,
+            month: Some(number(month, 2)? as i8),
+            day: Some(number(day, 2)? as i8),
+        },
+        _ => return None,
+    };
+
+    // make sure that date actually exists
+    Date::new(
+        partial.year,
+        partial.month.unwrap_or(1),
+        partial.day.unwrap_or(1),
+    )
+    .ok()?;
+
+    Some(partial)
+}
+
+/// Parses a date or datetime in the user's time zone.
+///
+/// Accepts `2023-01-01`, `2023-01-01T09:30`, timestamps like
+/// `2023-01-01T09:30Z`, and zoned datetimes like
+/// `2023-01-01T09:30[America/Chicago]`.
+fn datetime(value: &str) -> Option<Zoned> {
+    if let Ok(zoned) = value.parse::<Zoned>() {
+        return Some(zoned);
+    }
+
+    if let Ok(ts) = value.parse::<Timestamp>() {
+        return Some(ts.to_zoned(TimeZone::system()));
+    }
+
+    if let Ok(dt) = value.parse::<DateTime>() {
+        return dt.to_zoned(TimeZone::system()).ok();
+    }
+
+    value
+        .parse::<Date>()
+        .ok()
+        .and_then(|d| d.to_zoned(TimeZone::system()).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tag(name: &str) -> Expr {
+        Expr::Collection(CollectionModifier::Tag(TagDetail::TagName(name.into())))
+    }
+
+    #[test]
+    fn empty_query() {
+        assert_eq!(parse("").unwrap(), vec![]);
+        assert_eq!(parse("   ").unwrap(), vec![]);
+    }
+
+    #[test]
+    fn full_example() {
+        let exprs = parse(
+            "kind:video tag:holiday after:2023-01-01 -orientation:portrait (tag:cat OR tag:dog)",
+        )
+        .unwrap();
+
+        assert_eq!(exprs.len(), 5);
+        assert_eq!(
+            exprs[0],
+            Expr::Collection(CollectionModifier::Kind(KindDetail::Video))
+        );
+        assert_eq!(exprs[1], tag("holiday"));
+        assert!(matches!(
+            exprs[2],
+            Expr::Collection(CollectionModifier::DateTime(DateTimeModifier::After(
+                DateDetail::Created(_)
+            )))
+        ));
+        assert_eq!(
+            exprs[3],
+            Expr::Boolean(BooleanModifier::Not(Box::new(Expr::Collection(
+                CollectionModifier::Orientation("portrait".into())
+            ))))
+        );
+        assert_eq!(
+            exprs[4],
+            Expr::Boolean(BooleanModifier::Any(vec![tag("cat"), tag("dog")]))
+        );
+    }
+
+    #[test]
+    fn quoted_values_and_literals() {
+        assert_eq!(
+            parse(r#"tag:"new york" "beach day" sunset"#).unwrap(),
+            vec![
+                tag("new york"),
+                Expr::Collection(CollectionModifier::Literal("beach day".into())),
+                Expr::Collection(CollectionModifier::Literal("sunset".into())),
+            ]
+        );
+
+        // a quoted operator is just text
+        assert_eq!(
+            parse(r#""OR""#).unwrap(),
+            vec![Expr::Collection(CollectionModifier::Literal("OR".into()))]
+        );
+    }
+
+    #[test]
+    fn groups_and_negation() {
+        assert_eq!(
+            parse("(tag:a tag:b) OR NOT tag:c").unwrap(),
+            vec![Expr::Boolean(BooleanModifier::Any(vec![
+                Expr::Boolean(BooleanModifier::All(vec![tag("a"), tag("b")])),
+                Expr::Boolean(BooleanModifier::Not(Box::new(tag("c")))),
+            ]))]
+        );
+    }
+
+    #[test]
+    fn tag_counts() {
+        assert_eq!(
+            parse("tags:>=2").unwrap(),
+            vec![Expr::Collection(CollectionModifier::Tag(TagDetail::Count(
+                2,
+                Comparison::GreaterOrEqual
+            )))]
+        );
+    }
+
+    #[test]
+    fn dates() {
+        let date = |input: &str| match parse(input).unwrap().remove(0) {
+            Expr::Collection(CollectionModifier::DateTime(modifier)) => modifier,
+            other => panic!("expected a date modifier, got {other:?}"),
+        };
+
+        assert_eq!(
+            date("during:2023-06"),
+            DateTimeModifier::During(DateDetail::Created(DateValue::Partial(PartialDate {
+                year: 2023,
+                month: Some(6),
+                day: None
+            })))
+        );
+        assert_eq!(
+            date(r#"taken:"last 30 days""#),
+            DateTimeModifier::During(DateDetail::Captured(DateValue::Relative(
+                RelativeDate::LastDays(30)
+            )))
+        );
+        assert_eq!(
+            date("seen:>yesterday"),
+            DateTimeModifier::After(DateDetail::FirstSeen(DateValue::Relative(
+                RelativeDate::Yesterday
+            )))
+        );
+        assert_eq!(
+            date(r#"modified:<"last week""#),
+            DateTimeModifier::Before(DateDetail::Modified(DateValue::Relative(
+                RelativeDate::Last(CalendarUnit::Week)
+            )))
+        );
+        assert_eq!(
+            date(r#"taken:"at night""#),
+            DateTimeModifier::During(DateDetail::Captured(DateValue::TimeOfDay(TimeOfDay::NIGHT)))
+        );
+        assert_eq!(
+            date("created:09:00..12:30"),
+            DateTimeModifier::During(DateDetail::Created(DateValue::TimeOfDay(TimeOfDay::new(
+                jiff::civil::time(9, 0, 0, 0),
+                jiff::civil::time(12, 30, 0, 0)
+            ))))
+        );
+        assert!(matches!(
+            date("before:2023-06-14T09:30Z"),
+            DateTimeModifier::Before(DateDetail::Created(DateValue::Instant(_)))
+        ));
+
+        for bad in [
+            "during:2023-02-30",
+            "taken:\"last 0 days\"",
+            "seen:>=today",
+            "on:soon",
+        ] {
+            assert!(
+                matches!(parse(bad), Err(QueryParseError::InvalidValue { .. })),
+                "`{bad}` shouldn't parse"
+            );
+        }
+    }
+
+    #[test]
+    fn numbers_with_units() {
+        let numeric = |input: &str| match parse(input).unwrap().remove(0) {
+            Expr::Collection(CollectionModifier::Numeric(modifier)) => modifier,
+            other => panic!("expected a numeric modifier, got {other:?}"),
+        };
+
+        assert_eq!(
+            numeric("size:>10MiB"),
+            NumericModifier::FileSize(RangeDetail::Compare(
+                Comparison::Greater,
+                FileSizeDetail(10 * 1024 * 1024)
+            ))
+        );
+        assert_eq!(
+            numeric("size:1.5gb..2GB"),
+            NumericModifier::FileSize(RangeDetail::Between(
+                FileSizeDetail(1_500_000_000),
+                FileSizeDetail(2_000_000_000)
+            ))
+        );
+        assert_eq!(
+            numeric("megapixels:>=12"),
+            NumericModifier::Megapixels(RangeDetail::Compare(Comparison::GreaterOrEqual, 12.0))
+        );
+        assert_eq!(
+            numeric("width:<1080px"),
+            NumericModifier::Width(RangeDetail::Compare(Comparison::Less, 1080))
+        );
+        assert_eq!(
+            numeric("duration:10s..2m"),
+            NumericModifier::Duration(RangeDetail::Between(10.0, 120.0))
+        );
+        // either side can be left open
+        assert_eq!(
+            numeric("duration:..5"),
+            NumericModifier::Duration(RangeDetail::Compare(Comparison::LessOrEqual, 5.0))
+        );
+        assert_eq!(
+            numeric("duration:5.."),
+            NumericModifier::Duration(RangeDetail::Compare(Comparison::GreaterOrEqual, 5.0))
+        );
+        assert_eq!(
+            numeric("length:>1h30m"),
+            NumericModifier::Duration(RangeDetail::Compare(Comparison::Greater, 5400.0))
+        );
+        assert_eq!(
+            numeric("duration:1:02:03"),
+            NumericModifier::Duration(RangeDetail::Compare(Comparison::Equal, 3723.0))
+        );
+        assert_eq!(
+            numeric("fps:>=59.94"),
+            NumericModifier::Framerate(RangeDetail::Compare(
+                Comparison::GreaterOrEqual,
+                FramerateDetail(Framerate::from(59.94))
+            ))
+        );
+        assert_eq!(
+            numeric("rating:>=4"),
+            NumericModifier::Rating(RangeDetail::Compare(Comparison::GreaterOrEqual, 4))
+        );
+
+        for bad in [
+            r#"size:"10 parsecs""#,
+            "size:-1",
+            "width:10.5",
+            "duration:2m..10s",
+            "duration:..",
+            "duration:1x",
+            "fps:fast",
+            "rating:6",
+            "stars:2.5",
+        ] {
+            assert!(
+                matches!(parse(bad), Err(QueryParseError::InvalidValue { .. })),
+                "`{bad}` shouldn't parse"
+            );
+        }
+    }
+
+    #[test]
+    fn exif_fields() {
+        let exif = |input: &str| match parse(input).unwrap().remove(0) {
+            Expr::Collection(CollectionModifier::Exif(detail)) => detail,
+            other => panic!("`{input}` isn't an EXIF search: {other:?}"),
+        };
+
+        assert_eq!(
+            exif(r#"exif:model="Pixel 6""#),
+            ExifDetail::Compare("Model".into(), Comparison::Equal, "Pixel 6".into())
+        );
+        assert_eq!(
+            exif("exif:ISOSpeed>800"),
+            ExifDetail::Compare("ISOSpeed".into(), Comparison::Greater, "800".into())
+        );
+        assert_eq!(
+            exif("exif:ExposureTime<=1/100"),
+            ExifDetail::Compare(
+                "ExposureTime".into(),
+                Comparison::LessOrEqual,
+                "1/100".into()
+            )
+        );
+        assert_eq!(
+            exif("has:exif:gpslatitude"),
+            ExifDetail::Has("GPSLatitude".into())
+        );
+        assert_eq!(exif("exif:Flash"), ExifDetail::Has("Flash".into()));
+
+        for bad in [
+            "exif:Model>Pixel",
+            "exif:ISO>",
+            "exif:=800",
+            "exif:a.b=1",
+            "has:GPSLatitude",
+            "has:exif:",
+        ] {
+            assert!(
+                matches!(parse(bad), Err(QueryParseError::InvalidValue { .. })),
+                "`{bad}` shouldn't parse"
+            );
+        }
+    }
+
+    #[test]
+    fn error_spans() {
+        let input = "tag:cat colour:red";
+        let err = parse(input).unwrap_err();
+        assert_eq!(&input[err.span().start..err.span().end], "colour");
+
+        let input = "kind:audio";
+        let err = parse(input).unwrap_err();
+        assert!(matches!(err, QueryParseError::InvalidValue { .. }));
+        assert_eq!(&input[err.span().start..err.span().end], "audio");
+
+        let input = r#"tag:"never closed"#;
+        let err = parse(input).unwrap_err();
+        assert!(matches!(err, QueryParseError::UnterminatedQuote { .. }));
+        assert_eq!(err.span(), Span::new(4, input.len()));
+
+        let err = parse("(tag:cat").unwrap_err();
+        assert_eq!(
+            err,
+            QueryParseError::UnmatchedParen {
+                span: Span::new(0, 1)
+            }
+        );
+
+        let err = parse("tag:cat)").unwrap_err();
+        assert_eq!(
+            err,
+            QueryParseError::UnmatchedParen {
+                span: Span::new(7, 8)
+            }
+        );
+
+        let err = parse("tag:cat OR").unwrap_err();
+        assert_eq!(
+            err,
+            QueryParseError::ExpectedTerm {
+                span: Span::new(8, 10)
+            }
+        );
+
+        let err = parse("tag:").unwrap_err();
+        assert!(matches!(err, QueryParseError::MissingValue { .. }));
+    }
+
+    #[test]
+    fn spans_count_bytes() {
+        // `é` takes two bytes
+        let input = "café colour:red";
+        let err = parse(input).unwrap_err();
+        assert_eq!(err.span(), Span::new(6, 12));
+    }
+}
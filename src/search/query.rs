@@ -1,65 +1,739 @@
-use surrealdb::{engine::local::Db, method::Query, sql::Query as SqlQuery};
+//! Compiles search expressions into SQL.
+//!
+//! Every modifier becomes a small piece of a `WHERE` clause over the
+//! [`INFO_TABLE`]. User input never ends up in the SQL text itself: it's always
+//! bound as a [`Param`] to a `?` placeholder.
+
+use chrono::{DateTime, Utc};
+use fraction::ToPrimitive as _;
+use jiff::{civil::Time, Zoned};
+use sqlx::{encode::IsNull, error::BoxDynError, sqlite::SqliteArgumentValue, Encode, Sqlite, Type};
+use uuid::Uuid;
+
+use crate::database::{
+    INFO_FTS_TABLE, INFO_TABLE, MEDIA_COLUMNS, MEDIA_TAGS_TABLE, TAG_EXCLUSIONS_TABLE,
+    TAG_IMPLICATIONS_TABLE,
+};
 
 use super::{
-    details::TagDetail,
-    modifiers::{CollectionModifier, ToQuery},
+    dates,
+    details::{
+        Comparison, CompressionDetail, DateDetail, DateValue, ExifDetail, FormatDetail, KindDetail,
+        RangeDetail, TagDetail, TimeOfDay,
+    },
+    exif,
+    modifiers::{
+        BooleanModifier, CollectionModifier, DateTimeModifier, Expr, NumericModifier,
+        OtherModifier, PreExecutionQuery,
+    },
 };
 
-use surrealdb::sql::parse;
+/// A value bound to one `?` placeholder in a compiled query.
+#[derive(Clone, Debug, PartialEq, PartialOrd, serde::Serialize, serde::Deserialize)]
+pub enum Param {
+    Text(String),
+    Integer(i64),
+    Real(f64),
+    DateTime(DateTime<Utc>),
+    Uuid(Uuid),
+}
+
+impl Type<Sqlite> for Param {
+    fn type_info() -> sqlx::sqlite::SqliteTypeInfo {
+        <String as Type<Sqlite>>::type_info()
+    }
+
+    fn compatible(_ty: &sqlx::sqlite::SqliteTypeInfo) -> bool {
+        true
+    }
+}
+
+impl<'q> Encode<'q, Sqlite> for Param {
+    fn encode_by_ref(&self, buf: &mut Vec<SqliteArgumentValue<'q>>) -> Result<IsNull, BoxDynError> {
+        match self {
+            Param::Text(s) => <String as Encode<'q, Sqlite>>::encode(s.clone(), buf),
+            Param::Integer(i) => <i64 as Encode<'q, Sqlite>>::encode_by_ref(i, buf),
+            Param::Real(f) => <f64 as Encode<'q, Sqlite>>::encode_by_ref(f, buf),
+            Param::DateTime(dt) => <DateTime<Utc> as Encode<'q, Sqlite>>::encode_by_ref(dt, buf),
+            Param::Uuid(id) => <Uuid as Encode<'q, Sqlite>>::encode_by_ref(id, buf),
+        }
+    }
+}
+
+/// A piece of a `WHERE` clause, alongside the parameters it binds.
+///
+/// Parameters are in the same order as their `?` placeholders in `sql`.
+#[derive(Clone, Debug, PartialEq, PartialOrd)]
+pub struct Clause {
+    pub sql: String,
+    pub params: Vec<Param>,
+}
+
+impl Clause {
+    /// A clause with no parameters.
+    fn raw(sql: impl Into<String>) -> Self {
+        Self {
+            sql: sql.into(),
+            params: Vec::new(),
+        }
+    }
+
+    fn new(sql: impl Into<String>, params: Vec<Param>) -> Self {
+        Self {
+            sql: sql.into(),
+            params,
+        }
+    }
+
+    /// Joins clauses with the given SQL operator (`AND` or `OR`).
+    ///
+    /// When there aren't any clauses, this uses the `empty` SQL instead.
+    pub(crate) fn join(clauses: impl IntoIterator<Item = Clause>, op: &str, empty: &str) -> Self {
+        let mut sql = Vec::new();
+        let mut params = Vec::new();
+
+        for clause in clauses {
+            sql.push(format!("({})", clause.sql));
+            params.extend(clause.params);
+        }
+
+        if sql.is_empty() {
+            return Self::raw(empty);
+        }
+
+        Self {
+            sql: sql.join(&format!(" {op} ")),
+            params,
+        }
+    }
+
+    /// Combines a list of expressions the same way a search does: they must
+    /// all match.
+    pub fn all(exprs: &[Expr]) -> Self {
+        Self::join(exprs.iter().map(ToQuery::to_query), "AND", "1")
+    }
+}
+
+/// A modifier must become a query to be used.
+///
+/// All modifiers must implement this trait!
+pub trait ToQuery {
+    /// Converts the modifier into a piece of a `WHERE` clause over the
+    /// [`INFO_TABLE`].
+    fn to_query(&self) -> Clause;
+}
+
+impl PreExecutionQuery {
+    /// Compiles the given search into one `SELECT` statement over the
+    /// [`INFO_TABLE`].
+    pub fn new(exprs: &[Expr]) -> Self {
+        let Clause { sql, params } = Clause::all(exprs);
+
+        Self {
+            query: format!("SELECT {MEDIA_COLUMNS} FROM {INFO_TABLE} WHERE {sql}"),
+            parameters: params,
+        }
+    }
+}
+
+impl ToQuery for Expr {
+    fn to_query(&self) -> Clause {
+        match self {
+            Expr::Collection(m) => m.to_query(),
+            Expr::Boolean(m) => m.to_query(),
+            Expr::Other(m) => m.to_query(),
+        }
+    }
+}
 
-/// FIXME: this is a really basic impl. it's not a security risk or anything, but it's likely
-/// bad to use format strings here. that might allow for injection if someone didn't know.
+impl ToQuery for BooleanModifier {
+    fn to_query(&self) -> Clause {
+        match self {
+            BooleanModifier::Not(expr) => {
+                let Clause { sql, params } = expr.to_query();
+                Clause::new(format!("NOT ({sql})"), params)
+            }
+            BooleanModifier::Any(exprs) => {
+                Clause::join(exprs.iter().map(Expr::to_query), "OR", "0")
+            }
+            BooleanModifier::All(exprs) => Clause::all(exprs),
+        }
+    }
+}
+
+impl ToQuery for OtherModifier {
+    fn to_query(&self) -> Clause {
+        match self {
+            OtherModifier::Favorite => Clause::raw(format!("{FAVORITE} = 1")),
+            OtherModifier::Untagged => Clause::raw(format!("{TAG_COUNT} = 0")),
+            OtherModifier::Undated => Clause::raw(format!("{INFO_TABLE}.creation_date IS NULL")),
+        }
+    }
+}
 
 impl ToQuery for CollectionModifier {
-    fn to_query(&self) -> Result<SqlQuery, surrealdb::error::Db> {
+    fn to_query(&self) -> Clause {
         match self {
             CollectionModifier::Tag(tag_detail) => match tag_detail {
-                TagDetail::TagName(name) | TagDetail::PersonTagName(name) => {
-                    // NOTE: there are no "person" tags yet, so these are the same
-                    parse(format!("SELECT * FROM info WHERE media.tags CONTAINS '{name}'").as_str())
+                // NOTE: there are no "person" tags yet, so these are the same
+                TagDetail::TagName(name) | TagDetail::PersonTagName(name) => has_tag(name),
+
+                TagDetail::PersonTagWithMarker(person, marker) => {
+                    Clause::join([has_tag(person), has_tag(marker)], "AND", "1")
                 }
 
-                TagDetail::PersonTagWithMarker(_, _) => unimplemented!(),
-
-                TagDetail::Count(ct, cmp) => {
-                    let cmp = match cmp {
-                        super::details::Comparison::Less => "<",
-                        super::details::Comparison::LessOrEqual => "<=",
-                        super::details::Comparison::Equal => "=",
-                        super::details::Comparison::GreaterOrEqual => ">=",
-                        super::details::Comparison::Greater => ">",
-                    };
-
-                    parse(
-                        format!("SELECT * FROM info WHERE array::len(media.tags) {cmp} {ct}")
-                            .as_str(),
-                    )
+                TagDetail::Count(ct, cmp) => Clause::new(
+                    format!("{TAG_COUNT} {} ?", operator(cmp)),
+                    vec![Param::Integer(*ct as i64)],
+                ),
+            },
+
+            // an album is the folder that media is directly contained in
+            CollectionModifier::Album(name) => Clause::new(
+                format!("{PARENT_FOLDER} LIKE ? ESCAPE '\\'"),
+                vec![Param::Text(format!("%/{}/", escape_like(name)))],
+            ),
+
+            // plain text searches the full-text index
+            CollectionModifier::Literal(s) => match fts_query(s) {
+                Some(fts) => Clause::new(
+                    format!(
+                        "{INFO_TABLE}.id IN \
+                        (SELECT media_id FROM {INFO_FTS_TABLE} WHERE {INFO_FTS_TABLE} MATCH ?)"
+                    ),
+                    vec![Param::Text(fts)],
+                ),
+                None => Clause::raw("1"),
+            },
+
+            CollectionModifier::DateTime(dt) => date_time(dt, &Zoned::now()),
+
+            CollectionModifier::Format(format) => match format {
+                // we only store the part after the slash (`jpeg` in `image/jpeg`)
+                FormatDetail::MimeType(mime) => Clause::new(
+                    format!("json_extract({INFO_TABLE}.format, '$.mime_type') = ? COLLATE NOCASE"),
+                    vec![Param::Text(
+                        mime.rsplit_once('/').map_or(mime.as_str(), |(_, ty)| ty).to_string(),
+                    )],
+                ),
+                FormatDetail::Extension(ext) => Clause::new(
+                    format!("{INFO_TABLE}.path LIKE ? ESCAPE '\\'"),
+                    vec![Param::Text(format!("%.{}", escape_like(ext)))],
+                ),
+            },
+
+            CollectionModifier::Kind(kind) => Clause::raw(match kind {
+                KindDetail::Image => format!(
+                    "json_extract({INFO_TABLE}.format, '$.media_kind') IN ('Photo', 'AnimatedPhoto')"
+                ),
+                KindDetail::Video => {
+                    format!("json_extract({INFO_TABLE}.format, '$.media_kind') = 'Video'")
                 }
+            }),
+
+            CollectionModifier::Numeric(numeric) => numeric.to_query(),
+
+            CollectionModifier::Camera(model) => Clause::new(
+                format!("{CAMERA_MODEL} = ? COLLATE NOCASE"),
+                vec![Param::Text(model.clone())],
+            ),
+
+            // `IS` never gives `NULL`, so `NOT` still matches unknown media
+            CollectionModifier::Compression(compression) => {
+                Clause::raw(format!("{INFO_TABLE}.compression IS {}", match compression {
+                    CompressionDetail::Lossless => "'lossless'",
+                    CompressionDetail::Lossy => "'lossy'",
+                }))
+            }
+
+            CollectionModifier::Exif(detail) => exif_field(detail),
+
+            CollectionModifier::Orientation(orientation) => {
+                Clause::raw(match orientation.to_lowercase().as_str() {
+                    "landscape" => format!("{INFO_TABLE}.width_px > {INFO_TABLE}.height_px"),
+                    "portrait" => format!("{INFO_TABLE}.width_px < {INFO_TABLE}.height_px"),
+                    "square" => format!("{INFO_TABLE}.width_px = {INFO_TABLE}.height_px"),
+                    other => {
+                        tracing::warn!("Unknown orientation `{other}`. It won't match anything.");
+                        "0".into()
+                    }
+                })
+            }
+        }
+    }
+}
+
+impl ToQuery for NumericModifier {
+    fn to_query(&self) -> Clause {
+        match self {
+            NumericModifier::FileSize(r) => range(&format!("{INFO_TABLE}.filesize"), r, |size| {
+                Param::Integer(size.0 as i64)
+            }),
+            NumericModifier::Width(r) => range(&format!("{INFO_TABLE}.width_px"), r, |w| {
+                Param::Integer(*w as i64)
+            }),
+            NumericModifier::Height(r) => range(&format!("{INFO_TABLE}.height_px"), r, |h| {
+                Param::Integer(*h as i64)
+            }),
+            NumericModifier::Megapixels(r) => range(
+                &format!("({INFO_TABLE}.width_px * {INFO_TABLE}.height_px) / 1000000.0"),
+                r,
+                |mp| Param::Real(*mp),
+            ),
+            NumericModifier::Duration(r) => range(VIDEO_LENGTH, r, |secs| Param::Real(*secs)),
+            NumericModifier::Framerate(r) => range(FRAMERATE, r, |fps| {
+                Param::Real(fps.0.to_f64().unwrap_or_default())
+            }),
+            NumericModifier::Rating(r) => range(STARS, r, |stars| Param::Integer(*stars as i64)),
+        }
+    }
+}
+
+/// Compares a numeric column against the range.
+fn range<T>(column: &str, detail: &RangeDetail<T>, param: impl Fn(&T) -> Param) -> Clause {
+    match detail {
+        RangeDetail::Compare(cmp, value) => {
+            Clause::new(format!("{column} {} ?", operator(cmp)), vec![param(value)])
+        }
+        RangeDetail::Between(low, high) => Clause::new(
+            format!("{column} BETWEEN ? AND ?"),
+            vec![param(low), param(high)],
+        ),
+    }
+}
+
+/// The camera model from a media file's EXIF, like `Pixel 6`.
+///
+/// `kamadak-exif` puts quotes around text values, so those are trimmed off.
+/// (It also uses the tag's name as its key, but `nom-exif` uses its code.)
+pub(crate) const CAMERA_MODEL: &str = "trim(COALESCE(\
+    json_extract(info.other_metadata, '$.Model.value'), \
+    json_extract(info.other_metadata, '$.\"272\".value')), '\"')";
+
+/// The camera's maker, like `Google`. It's stored the same way as the
+/// [`CAMERA_MODEL`].
+pub(crate) const CAMERA_MAKE: &str = "trim(COALESCE(\
+    json_extract(info.other_metadata, '$.Make.value'), \
+    json_extract(info.other_metadata, '$.\"271\".value')), '\"')";
+
+/// How long a video is, in seconds. Other media don't have a length.
+pub(crate) const VIDEO_LENGTH: &str = "json_extract(info.specific_metadata, '$.Video.length')";
+
+/// A video's (or animated image's) frames per second.
+///
+/// Framerates are stored as fractions, which look like
+/// `{"Rational":["Plus",[30000,1001]]}`. Division by zero gives `NULL`.
+const FRAMERATE: &str = "COALESCE(\
+    json_extract(info.specific_metadata, '$.Video.framerate.Rational[1][0]') * 1.0 / \
+    json_extract(info.specific_metadata, '$.Video.framerate.Rational[1][1]'), \
+    json_extract(info.specific_metadata, '$.AnimatedImage.framerate.Rational[1][0]') * 1.0 / \
+    json_extract(info.specific_metadata, '$.AnimatedImage.framerate.Rational[1][1]'))";
+
+/// Whether the media is a favorite, as `1` or `0`.
+pub(crate) const FAVORITE: &str =
+    "COALESCE((SELECT favorite FROM rating WHERE rating.media_id = info.id), 0)";
+
+/// How many stars the media has. Unrated media has `0`.
+pub(crate) const STARS: &str =
+    "COALESCE((SELECT stars FROM rating WHERE rating.media_id = info.id), 0)";
+
+/// How many tags the media was given. Implied tags don't count.
+pub(crate) const TAG_COUNT: &str = "(SELECT COUNT(*) FROM media_tags WHERE media_id = info.id)";
+
+/// The folder a media file is in, including the trailing slash.
+///
+/// (`rtrim` strips every character that isn't a slash from the end.)
+pub(crate) const PARENT_FOLDER: &str = "rtrim(info.path, replace(info.path, '/', ''))";
+
+/// When a media file was captured, from its EXIF `DateTimeOriginal`.
+///
+/// This is local time, formatted like `2023-06-14 09:30:00`. (`kamadak-exif`
+/// uses the tag's name as its key, but `nom-exif` uses its code.)
+pub(crate) const CAPTURE_DATE: &str = "replace(substr(COALESCE(\
+    json_extract(info.other_metadata, '$.DateTimeOriginal.value'), \
+    json_extract(info.other_metadata, '$.\"36867\".value')), 1, 19), 'T', ' ')";
+
+/// Checks an EXIF field in the media's `other_metadata`.
+///
+/// The field is read once, as `v`, from wherever each extractor keeps it.
+/// This is wrapped in `EXISTS`, so negating it also matches media without
+/// the field.
+fn exif_field(detail: &ExifDetail) -> Clause {
+    let (name, condition, params) = match detail {
+        ExifDetail::Has(name) => (name, "v IS NOT NULL".to_string(), Vec::new()),
+
+        ExifDetail::Compare(name, cmp, value) => match (exif::number(value), cmp) {
+            // text that's written the same way still matches (like `Model=6`)
+            (Some(n), Comparison::Equal) => (
+                name,
+                format!("v = ? COLLATE NOCASE OR ({EXIF_IS_NUMBER} AND {EXIF_NUMBER} = ?)"),
+                vec![Param::Text(value.clone()), Param::Real(n)],
+            ),
+            (Some(n), cmp) => (
+                name,
+                format!("{EXIF_IS_NUMBER} AND {EXIF_NUMBER} {} ?", operator(cmp)),
+                vec![Param::Real(n)],
+            ),
+            (None, _) => (
+                name,
+                "v = ? COLLATE NOCASE".to_string(),
+                vec![Param::Text(value.clone())],
+            ),
+        },
+    };
+
+    // `COALESCE` needs two arguments, so there's always a `NULL` at the end
+    let paths = exif::json_paths(name);
+    let extract = format!("json_extract({INFO_TABLE}.other_metadata, ?), ").repeat(paths.len());
+
+    Clause::new(
+        format!(
+            "EXISTS (SELECT 1 FROM (SELECT trim(COALESCE({extract}NULL), '\"') AS v) \
+            WHERE {condition})"
+        ),
+        paths.into_iter().map(Param::Text).chain(params).collect(),
+    )
+}
+
+/// Whether an EXIF value, `v`, starts with a number.
+const EXIF_IS_NUMBER: &str = "(v GLOB '[0-9]*' OR v GLOB '[-.][0-9]*' OR v GLOB '-.[0-9]*')";
+
+/// An EXIF value, `v`, as a number. Fractions, like `1/100`, are divided out.
+const EXIF_NUMBER: &str = "CASE WHEN instr(v, '/') \
+    THEN CAST(v AS REAL) / NULLIF(CAST(substr(v, instr(v, '/') + 1) AS REAL), 0) \
+    ELSE CAST(v AS REAL) END";
+
+/// Checks if the media has a tag with the given name, or a tag that implies
+/// it (even indirectly).
+///
+/// Tags excluded from the media don't count, and neither do the tags they
+/// imply. Aliases of merged tags find the tag they were merged into.
+fn has_tag(name: &str) -> Clause {
+    Clause::new(
+        format!(
+            "EXISTS ({} \
+             SELECT 1 FROM tagged WHERE id IN (SELECT id FROM tags WHERE name = ? \
+             UNION ALL SELECT tag_id FROM tag_aliases WHERE name = ?))",
+            tagged(&format!("media_id = {INFO_TABLE}.id"))
+        ),
+        vec![Param::Text(name.to_string()); 2],
+    )
+}
+
+/// A `WITH` clause for every tag that media have, as `tagged(media_id, id)`.
+/// That includes the ones their tags imply, directly or not.
+///
+/// It stops at tags that were excluded from the media. Only media matching
+/// `filter`, a `WHERE` clause over the [`MEDIA_TAGS_TABLE`], are included.
+pub(crate) fn tagged(filter: &str) -> String {
+    format!(
+        "WITH RECURSIVE tagged(media_id, id) AS (\
+        SELECT media_id, tag_id FROM {MEDIA_TAGS_TABLE} WHERE {filter} \
+        UNION \
+        SELECT tagged.media_id, i.implied_id FROM {TAG_IMPLICATIONS_TABLE} AS i \
+        JOIN tagged ON i.tag_id = tagged.id \
+        WHERE i.implied_id NOT IN \
+        (SELECT tag_id FROM {TAG_EXCLUSIONS_TABLE} WHERE media_id = tagged.media_id))"
+    )
+}
+
+fn date_time(modifier: &DateTimeModifier, now: &Zoned) -> Clause {
+    let detail = match modifier {
+        DateTimeModifier::Before(d) | DateTimeModifier::During(d) | DateTimeModifier::After(d) => d,
+    };
+
+    let (column, value) = match detail {
+        DateDetail::Created(v) => (format!("{INFO_TABLE}.creation_date"), v),
+        DateDetail::Modified(v) => (format!("{INFO_TABLE}.modification_date"), v),
+        DateDetail::FirstSeen(v) => (format!("{INFO_TABLE}.first_seen_date"), v),
+        DateDetail::Captured(v) => (CAPTURE_DATE.to_string(), v),
+        DateDetail::Accessed(_) => {
+            tracing::warn!("Raves doesn't track access dates. This modifier won't match anything.");
+            return Clause::raw("0");
+        }
+    };
+
+    // we store most dates in UTC, but cameras write down their local time
+    let captured = matches!(detail, DateDetail::Captured(_));
+    let param = |zoned: &Zoned| {
+        if captured {
+            Param::Text(
+                zoned
+                    .with_time_zone(now.time_zone().clone())
+                    .strftime("%Y-%m-%d %H:%M:%S")
+                    .to_string(),
+            )
+        } else {
+            Param::DateTime(to_utc(zoned))
+        }
+    };
+
+    if let DateValue::TimeOfDay(window) = value {
+        return time_of_day(modifier, &column, captured, window, now);
+    }
+
+    let range = match (modifier, value) {
+        // an exact moment means the whole day it's on
+        (DateTimeModifier::During(_), DateValue::Instant(zoned)) => dates::day_of(zoned),
+        _ => value.range(now),
+    };
+    let Some((start, end)) = range else {
+        tracing::warn!("Date `{value:?}` is out of range. It won't match anything.");
+        return Clause::raw("0");
+    };
+
+    match modifier {
+        DateTimeModifier::Before(_) => Clause::new(format!("{column} < ?"), vec![param(&start)]),
+        DateTimeModifier::After(_) => Clause::new(
+            // instants don't last, so they can't include themselves
+            match value {
+                DateValue::Instant(_) => format!("{column} > ?"),
+                _ => format!("{column} >= ?"),
             },
+            vec![param(&end)],
+        ),
+        DateTimeModifier::During(_) => Clause::new(
+            format!("{column} >= ? AND {column} < ?"),
+            vec![param(&start), param(&end)],
+        ),
+    }
+}
+
+/// Compares the local time of day of a date column.
+///
+/// Stored dates are shifted by the offset `now`'s time zone had at each one,
+/// so DST is taken into account.
+fn time_of_day(
+    modifier: &DateTimeModifier,
+    column: &str,
+    captured: bool,
+    window: &TimeOfDay,
+    now: &Zoned,
+) -> Clause {
+    let local = if captured {
+        format!("time({column})")
+    } else {
+        format!(
+            "time({column}, ({}) || ' seconds')",
+            dates::local_offset(column, now)
+        )
+    };
 
-            // we'll sort by the folder it's contained in
-            CollectionModifier::Album(name) => {
-                // FIXME: this isn't correct. placeholder until we parse it manually
-                // note that fixing it might require us to directly query here..!
-                parse(format!("SELECT * FROM info WHERE path CONTAINS '{name}'").as_str())
+    let compare = |op: &str, t: Time| {
+        Clause::new(
+            format!("{local} {op} ?"),
+            vec![Param::Text(dates::sql_time(t))],
+        )
+    };
+
+    match modifier {
+        DateTimeModifier::Before(_) => compare("<", window.start),
+        DateTimeModifier::After(_) => compare(">=", window.end),
+        DateTimeModifier::During(_) => {
+            let clauses = [compare(">=", window.start), compare("<", window.end)];
+            if window.wraps() {
+                Clause::join(clauses, "OR", "0")
+            } else {
+                Clause::join(clauses, "AND", "1")
             }
+        }
+    }
+}
 
-            CollectionModifier::Literal(s) => {
-                parse(format!("SELECT * FROM info WHERE media.name = '{s}'").as_str())
-            } // FIXME: this should only search the filename
+/// Turns user text into an FTS5 query for the [`INFO_FTS_TABLE`].
+///
+/// Each word becomes a quoted prefix search, so `cat beach` finds
+/// `Caterpillar_beach.jpg`, and FTS5 syntax in the text (`OR`, `NEAR`, `*`,
+/// ...) is just text. Words must all match.
+///
+/// Returns `None` when there aren't any words to search for.
+pub(crate) fn fts_query(text: &str) -> Option<String> {
+    let words = text
+        .split_whitespace()
+        // punctuation isn't indexed, so it can't be searched for alone
+        .filter(|word| word.chars().any(char::is_alphanumeric))
+        .map(|word| format!("\"{}\"*", word.replace('"', "\"\"")))
+        .collect::<Vec<_>>();
 
-            CollectionModifier::DateTime(_) => todo!(),
+    (!words.is_empty()).then(|| words.join(" "))
+}
 
-            CollectionModifier::Format(_) => todo!(),
+/// Converts a `jiff` datetime into the `chrono` one we store in the database.
+pub(crate) fn to_utc(zoned: &Zoned) -> DateTime<Utc> {
+    DateTime::from_timestamp_millis(zoned.timestamp().as_millisecond()).unwrap_or_default()
+}
 
-            CollectionModifier::Kind(_) => todo!(),
+/// The SQL operator for a comparison.
+pub(crate) fn operator(cmp: &Comparison) -> &'static str {
+    match cmp {
+        Comparison::Less => "<",
+        Comparison::LessOrEqual => "<=",
+        Comparison::Equal => "=",
+        Comparison::GreaterOrEqual => ">=",
+        Comparison::Greater => ">",
+    }
+}
 
-            CollectionModifier::Orientation(_) => todo!(),
+/// Escapes `LIKE` wildcards so user text only matches itself.
+///
+/// Use this with `ESCAPE '\'`.
+pub(crate) fn escape_like(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        if matches!(c, '\\' | '%' | '_') {
+            escaped.push('\\');
         }
+        escaped.push(c);
     }
+    escaped
 }
 
-pub trait ToQuery2 {
-    /// Takes in an (unexecuted) query and adds additional clauses on it.
-    fn to_query(query: Query<'_, Db>) -> Query<'_, Db>;
+#[cfg(test)]
+mod tests {
+    use crate::search::parse::parse;
+
+    use super::*;
+
+    #[test]
+    fn user_text_is_never_in_sql() {
+        let evil = r#"'); DROP TABLE info; --"#;
+        let exprs = vec![
+            Expr::Collection(CollectionModifier::Tag(TagDetail::TagName(evil.into()))),
+            Expr::Collection(CollectionModifier::Album(evil.into())),
+            Expr::Collection(CollectionModifier::Literal(evil.into())),
+            Expr::Collection(CollectionModifier::Format(FormatDetail::Extension(
+                evil.into(),
+            ))),
+        ];
+
+        let query = PreExecutionQuery::new(&exprs);
+        assert!(!query.query.contains("DROP"));
+        assert_eq!(query.parameters.len(), 5);
+        assert_eq!(query.query.matches('?').count(), 5);
+    }
+
+    #[test]
+    fn booleans_nest() {
+        let exprs = parse("kind:video -(tag:cat OR tag:dog)").unwrap();
+        let clause = Clause::all(&exprs);
+
+        assert_eq!(
+            clause.sql,
+            format!(
+                "(json_extract(info.format, '$.media_kind') = 'Video') AND \
+                 (NOT (({tag}) OR ({tag})))",
+                tag = has_tag("").sql
+            )
+        );
+        assert_eq!(
+            clause.params,
+            ["cat", "cat", "dog", "dog"]
+                .map(|p| Param::Text(p.into()))
+                .to_vec()
+        );
+    }
+
+    #[test]
+    fn empty_search_matches_everything() {
+        assert_eq!(
+            PreExecutionQuery::new(&[]).query,
+            format!("SELECT {MEDIA_COLUMNS} FROM info WHERE 1")
+        );
+        assert_eq!(BooleanModifier::Any(vec![]).to_query().sql, "0");
+    }
+
+    #[test]
+    fn times_of_day_use_local_time() {
+        let now = "2024-06-14T15:30[America/Chicago]"
+            .parse::<Zoned>()
+            .unwrap();
+        let night = |detail| date_time(&DateTimeModifier::During(detail), &now);
+
+        // stored dates are shifted into local time, and night wraps midnight
+        let clause = night(DateDetail::Created(DateValue::TimeOfDay(TimeOfDay::NIGHT)));
+        let local = format!(
+            "time(info.creation_date, ({}) || ' seconds')",
+            dates::local_offset("info.creation_date", &now)
+        );
+        assert_eq!(clause.sql, format!("({local} >= ?) OR ({local} < ?)"));
+        assert_eq!(
+            clause.params,
+            ["21:00:00", "05:00:00"]
+                .map(|p| Param::Text(p.into()))
+                .to_vec()
+        );
+
+        // capture dates are already local
+        let clause = night(DateDetail::Captured(DateValue::TimeOfDay(TimeOfDay::NIGHT)));
+        assert_eq!(
+            clause.sql,
+            format!("(time({CAPTURE_DATE}) >= ?) OR (time({CAPTURE_DATE}) < ?)")
+        );
+        assert_eq!(clause.params.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn times_of_day_follow_dst() {
+        use sqlx::{Connection as _, SqliteConnection};
+
+        let now = "2024-06-14T15:30[America/Chicago]"
+            .parse::<Zoned>()
+            .unwrap();
+        let clause = date_time(
+            &DateTimeModifier::During(DateDetail::Created(DateValue::TimeOfDay(
+                TimeOfDay::MORNING,
+            ))),
+            &now,
+        );
+
+        let mut conn = SqliteConnection::connect("sqlite::memory:").await.unwrap();
+        sqlx::query("CREATE TABLE info (creation_date TEXT)")
+            .execute(&mut conn)
+            .await
+            .unwrap();
+        for date in [
+            // 06:30 in summer (CDT, -05:00)
+            "2024-07-01T11:30:00+00:00",
+            // 04:30 in summer, so too early
+            "2024-07-01T09:30:00+00:00",
+            // 05:30 in winter (CST, -06:00)
+            "2024-01-15T11:30:00+00:00",
+            // 04:30 in winter, which summer's offset would make 05:30
+            "2024-01-15T10:30:00+00:00",
+        ] {
+            sqlx::query("INSERT INTO info VALUES ($1)")
+                .bind(date)
+                .execute(&mut conn)
+                .await
+                .unwrap();
+        }
+
+        let sql = format!(
+            "SELECT creation_date FROM info WHERE {} ORDER BY creation_date",
+            clause.sql
+        );
+        let mut query = sqlx::query_scalar::<_, String>(&sql);
+        for param in clause.params {
+            query = query.bind(param);
+        }
+        assert_eq!(
+            query.fetch_all(&mut conn).await.unwrap(),
+            ["2024-01-15T11:30:00+00:00", "2024-07-01T11:30:00+00:00"]
+        );
+    }
+
+    #[test]
+    fn fts_queries_are_quoted_prefixes() {
+        assert_eq!(fts_query("cat beach").unwrap(), r#""cat"* "beach"*"#);
+        assert_eq!(
+            fts_query(r#"say "hi" OR NEAR"#).unwrap(),
+            r#""say"* """hi"""* "OR"* "NEAR"*"#
+        );
+        assert_eq!(fts_query(" -- ! "), None);
+    }
+
+    #[test]
+    fn like_wildcards_are_escaped() {
+        assert_eq!(escape_like(r"100%_a\b"), r"100\%\_a\\b");
+    }
 }
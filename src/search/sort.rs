@@ -1,31 +1,68 @@
 //! Helps to sort media.
 
-use core::mem;
-
-use crate::models::media::{metadata::SpecificMetadata, Media};
-
-pub struct PreparedQuery {
-    pub initial_select: String, // something like "SELECT * FROM info"
-    pub where_clauses: Vec<(String,)>,
-}
-
-pub type WhereClause = String;
-pub type Param = String;
+use core::cmp::Ordering;
+use std::collections::HashMap;
+
+use uuid::Uuid;
+
+use crate::{
+    database::{DATABASE, INFO_TABLE, NATURAL_COLLATION, RATING_TABLE},
+    error::{DatabaseError, RavesError},
+    models::{
+        media::{
+            metadata::{AspectRatio, Resolution, SpecificMetadata},
+            Media,
+        },
+        rating::Rating,
+    },
+};
+
+use super::query::{
+    CAMERA_MAKE, CAMERA_MODEL, FAVORITE, PARENT_FOLDER, STARS, TAG_COUNT, VIDEO_LENGTH,
+};
 
 /// Different sorts users can apply to a search.
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub enum SortType {
-    /// No order at all. All elements are randomly sorted.
-    Random,
+    /// A random order that stays the same for the same seed, even across
+    /// pages. Use [`SortType::random`] to get a new seed.
+    Random {
+        seed: u64,
+    },
     DateFirstSeen,
     DateModified,
     DateCreated,
     TagCount,
     Type,
     Size,
+    /// How many pixels it has (its megapixels), so a tall panorama doesn't
+    /// beat a big square photo.
     Resolution,
-    /// How long a video is. This will put all photos at the end.
+    /// How long a video is. Anything that isn't a video has a length of
+    /// zero, so it comes first.
     Duration,
+    /// The file name, with numbers in order (`IMG_2` before `IMG_10`) and
+    /// case ignored.
+    Name,
+    /// How wide the media is compared to its height. Tall media comes
+    /// first.
+    AspectRatio,
+    /// How many bytes a video uses per second. Like [`SortType::Duration`],
+    /// anything that isn't a video is zero.
+    Bitrate,
+    /// The camera's make, then its model. Media without one (like most
+    /// videos and screenshots) comes first.
+    Camera,
+    /// Favorites come last, so sort descending to get them first.
+    Favorite,
+    /// How many stars the media has. Unrated media has zero.
+    Rating,
+    /// How well the media matches the text in a search. Higher is better, so
+    /// sort descending to get the best matches first.
+    ///
+    /// When a search has no text, everything ties. This needs the search's
+    /// text, so [`FinishedQuery::sort`] can't use it.
+    Relevance,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
@@ -42,104 +79,385 @@ pub enum SortOrder {
     Descending,
 }
 
+/// A list of sorts to apply, in order. Later sorts only break ties between
+/// earlier ones.
+///
+/// Any ties left over are broken by each media file's `id`, lowest first.
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SortSpec {
+    pub keys: Vec<(SortType, SortOrder)>,
+}
+
+impl SortSpec {
+    pub fn new(ty: SortType, order: SortOrder) -> Self {
+        Self {
+            keys: vec![(ty, order)],
+        }
+    }
+
+    /// Adds another sort to break ties with.
+    pub fn then(mut self, ty: SortType, order: SortOrder) -> Self {
+        self.keys.push((ty, order));
+        self
+    }
+}
+
+impl From<(SortType, SortOrder)> for SortSpec {
+    fn from((ty, order): (SortType, SortOrder)) -> Self {
+        Self::new(ty, order)
+    }
+}
+
+impl From<Vec<(SortType, SortOrder)>> for SortSpec {
+    fn from(keys: Vec<(SortType, SortOrder)>) -> Self {
+        Self { keys }
+    }
+}
+
 /// A query that has been executed and can now be sorted based on user input.
+#[derive(Clone, Debug, PartialEq, PartialOrd)]
 pub struct FinishedQuery(Vec<Media>);
 
-use rand::seq::SliceRandom;
-use rand::thread_rng;
+impl From<Vec<Media>> for FinishedQuery {
+    fn from(value: Vec<Media>) -> Self {
+        Self(value)
+    }
+}
 
 impl FinishedQuery {
-    pub async fn sort(&mut self, ty: SortType, order: SortOrder) {
-        let v = &mut self.0;
-
-        match ty {
-            SortType::Random => v.shuffle(&mut thread_rng()),
-            SortType::DateFirstSeen => v.sort_by(|a, b| a.first_seen_date.cmp(&b.first_seen_date)),
-            SortType::DateModified => {
-                v.sort_by(|a, b| a.modification_date.cmp(&b.modification_date))
-            }
-            SortType::DateCreated => v.sort_by(|a, b| a.creation_date.cmp(&b.creation_date)),
-            SortType::TagCount => v.sort_by(|a, b| a.tags.len().cmp(&b.tags.len())),
-            SortType::Type => v.sort_by(|a, b| a.format.cmp(&b.format)),
-            SortType::Size => v.sort_by(|a, b| a.filesize.cmp(&b.filesize)),
-            SortType::Resolution => {
-                v.sort_by(|a, b| (a.width_px + a.height_px).cmp(&(b.width_px + b.height_px)))
-            }
+    /// The media in this query, in their current order.
+    pub fn media(&self) -> &[Media] {
+        &self.0
+    }
 
-            // this one is different b/c it relies on a sort specific to videos.
-            //
-            // so we sort the types independently to ensure that photos are
-            // also useful when a user finds them.
-            SortType::Duration => {
-                // create a list of all videos
-                let mut videos = Vec::new();
-                let mut photos = Vec::new();
-
-                // move the entirety of `v` into a new vec
-                let vec: Vec<Media> = mem::take(v);
-
-                // split the vec into photos and videos
-                for media in vec.into_iter() {
-                    match media.specific_metadata.0 {
-                        SpecificMetadata::Image {} => photos.push(media),
-                        SpecificMetadata::Video { length } => videos.push((media, length)),
-                        _ => unreachable!("animated images aren't yet distinct from photos"),
-                    }
-                }
+    pub fn into_media(self) -> Vec<Media> {
+        self.0
+    }
+}
 
-                // sort the videos by their duration
-                videos.sort_by(|(_, a_len), (_, b_len)| a_len.total_cmp(b_len));
+impl SortType {
+    /// An SQL expression that gives each row of the [`INFO_TABLE`] its key
+    /// for this sort.
+    ///
+    /// The key is never `NULL`, so rows can always be compared.
+    pub(crate) fn sql_key(&self) -> String {
+        match self {
+            SortType::Random { seed } => random_sql_key(*seed),
+            SortType::DateFirstSeen => format!("{INFO_TABLE}.first_seen_date"),
+            SortType::DateModified => format!("COALESCE({INFO_TABLE}.modification_date, '')"),
+            SortType::DateCreated => format!("COALESCE({INFO_TABLE}.creation_date, '')"),
+            SortType::TagCount => TAG_COUNT.into(),
+            // this matches the order of `MediaKind`, then the MIME type
+            SortType::Type => format!(
+                "printf('%d/%s', \
+                CASE json_extract({INFO_TABLE}.format, '$.media_kind') \
+                WHEN 'Photo' THEN 0 WHEN 'AnimatedPhoto' THEN 1 ELSE 2 END, \
+                json_extract({INFO_TABLE}.format, '$.mime_type'))"
+            ),
+            SortType::Size => format!("{INFO_TABLE}.filesize"),
+            SortType::Resolution => format!("{INFO_TABLE}.width_px * {INFO_TABLE}.height_px"),
+            // photos don't have a length, so they come first
+            SortType::Duration => format!("COALESCE({VIDEO_LENGTH}, 0.0)"),
+            SortType::Name => format!(
+                "substr({INFO_TABLE}.path, length({PARENT_FOLDER}) + 1) COLLATE {NATURAL_COLLATION}"
+            ),
+            SortType::AspectRatio => format!(
+                "COALESCE(CAST({INFO_TABLE}.width_px AS REAL) / NULLIF({INFO_TABLE}.height_px, 0), 0.0)"
+            ),
+            // dividing by zero gives `NULL`
+            SortType::Bitrate => format!("COALESCE({INFO_TABLE}.filesize * 1.0 / {VIDEO_LENGTH}, 0.0)"),
+            // the tab sorts below everything else, so makes stay together
+            SortType::Camera => format!(
+                "(COALESCE({CAMERA_MAKE}, '') || char(9) || COALESCE({CAMERA_MODEL}, '')) \
+                COLLATE {NATURAL_COLLATION}"
+            ),
+            SortType::Favorite => FAVORITE.into(),
+            SortType::Rating => STARS.into(),
+            // NOTE: this depends on the search's text, so `Search` builds the
+            // real key. here, everything ties
+            SortType::Relevance => "0.0".into(),
+        }
+    }
+}
+
+impl SortType {
+    /// Compares two media files by this sort, lowest first.
+    ///
+    /// This matches [`SortType::sql_key`], so sorting in memory gives the same
+    /// order as the database. Ratings have their own table, so they're passed
+    /// in. Media without one are unrated.
+    fn compare(&self, a: &Media, b: &Media, ratings: &HashMap<Uuid, Rating>) -> Ordering {
+        let rating = |media: &Media| ratings.get(&media.id).copied().unwrap_or_default();
+
+        match self {
+            SortType::Random { seed } => random_key(a.id, *seed).cmp(&random_key(b.id, *seed)),
+            SortType::Favorite => rating(a).favorite.cmp(&rating(b).favorite),
+            SortType::Rating => rating(a).stars.cmp(&rating(b).stars),
+            // this needs the search, so everything ties
+            SortType::Relevance => Ordering::Equal,
+            SortType::DateFirstSeen => a.first_seen_date.cmp(&b.first_seen_date),
+            SortType::DateModified => a.modification_date.cmp(&b.modification_date),
+            SortType::DateCreated => a.creation_date.cmp(&b.creation_date),
+            SortType::TagCount => a.tags.len().cmp(&b.tags.len()),
+            SortType::Type => a.format.cmp(&b.format),
+            SortType::Size => a.filesize.cmp(&b.filesize),
+            SortType::Resolution => pixels(a).cmp(&pixels(b)),
+            SortType::Duration => video_length(a).total_cmp(&video_length(b)),
+            SortType::Name => natural_cmp(file_name(a), file_name(b)),
+            SortType::AspectRatio => aspect_ratio(a).total_cmp(&aspect_ratio(b)),
+            SortType::Bitrate => bitrate(a).total_cmp(&bitrate(b)),
+            SortType::Camera => natural_cmp(&camera(a), &camera(b)),
+        }
+    }
+}
 
-                // always sort photos by the creation date (this sucks but whatever)
-                photos.sort_by(|a, b| a.creation_date.cmp(&b.creation_date));
+fn pixels(media: &Media) -> u64 {
+    u64::from(media.width_px) * u64::from(media.height_px)
+}
 
-                #[cfg(debug_assertions)]
-                assert!(v.is_empty(), "the original vec should still be empty here");
-                let total_len = videos.len() + photos.len();
+/// Everything after the last slash in the media's path.
+fn file_name(media: &Media) -> &str {
+    media.path.rsplit('/').next().unwrap_or_default()
+}
 
-                // and add everything back into `v`
-                for (vid, _len) in videos {
-                    v.push(vid);
-                }
+/// Width over height, or zero when there's no height.
+fn aspect_ratio(media: &Media) -> f64 {
+    let ratio = AspectRatio::from(Resolution::new(media.width_px, media.height_px));
+    match ratio.height() {
+        0 => 0.0,
+        height => f64::from(ratio.width()) / f64::from(height),
+    }
+}
 
-                for photo in photos {
-                    v.insert(0, photo);
-                }
+/// Bytes per second, or zero for anything that isn't a video.
+fn bitrate(media: &Media) -> f64 {
+    let length = video_length(media);
+    if length > 0.0 {
+        media.filesize as f64 / length
+    } else {
+        0.0
+    }
+}
+
+/// The camera's make and model, split by a tab.
+fn camera(media: &Media) -> String {
+    // `kamadak-exif` uses names, but `nom-exif` uses codes
+    let field = |name: &str, code: &str| {
+        media
+            .other_metadata
+            .as_ref()
+            .and_then(|map| map.0 .0.get(name).or_else(|| map.0 .0.get(code)))
+            .map(|value| value.value.trim_matches('"'))
+            .unwrap_or_default()
+            .to_string()
+    };
+
+    format!("{}\t{}", field("Make", "271"), field("Model", "272"))
+}
 
-                #[cfg(debug_assertions)]
-                assert_eq!(
-                    total_len,
-                    v.len(),
-                    "the sorted vec should have all original elements"
+/// Compares text like a person would: runs of digits are compared as
+/// numbers, and case is ignored.
+///
+/// When two strings only differ by case or leading zeroes, they're compared
+/// exactly, so only equal strings are equal.
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a_chars, mut b_chars) = (a.chars().peekable(), b.chars().peekable());
+
+    loop {
+        let ordering = match (a_chars.peek(), b_chars.peek()) {
+            (None, None) => return a.cmp(b),
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(a_char), Some(b_char)) if a_char.is_ascii_digit() && b_char.is_ascii_digit() => {
+                let a_digits = digits(&mut a_chars);
+                let b_digits = digits(&mut b_chars);
+                let (a_digits, b_digits) = (
+                    a_digits.trim_start_matches('0'),
+                    b_digits.trim_start_matches('0'),
                 );
+
+                a_digits
+                    .len()
+                    .cmp(&b_digits.len())
+                    .then_with(|| a_digits.cmp(b_digits))
+            }
+            (Some(&a_char), Some(&b_char)) => {
+                a_chars.next();
+                b_chars.next();
+                a_char.to_lowercase().cmp(b_char.to_lowercase())
             }
+        };
+
+        if ordering.is_ne() {
+            return ordering;
+        }
+    }
+}
+
+/// Takes the run of digits at the start.
+fn digits(chars: &mut core::iter::Peekable<core::str::Chars<'_>>) -> String {
+    let mut digits = String::new();
+    while let Some(c) = chars.next_if(char::is_ascii_digit) {
+        digits.push(c);
+    }
+    digits
+}
+
+/// The length of a video, or zero for anything else.
+fn video_length(media: &Media) -> f64 {
+    match *media.specific_metadata {
+        SpecificMetadata::Video { length, .. } => length,
+        _ => 0.0,
+    }
+}
+
+/// Random keys are hashed modulo this prime (`2^31 - 1`).
+///
+/// Everything stays below `2^62` while hashing, so SQLite never overflows
+/// into floats.
+const RANDOM_MODULUS: i64 = 2_147_483_647;
+
+impl SortType {
+    /// A random sort with a new seed.
+    pub fn random() -> Self {
+        SortType::Random {
+            seed: rand::random(),
         }
+    }
+}
+
+/// Spreads the seed's bits out (SplitMix64), then turns it into the two
+/// factors used when hashing.
+fn random_factors(seed: u64) -> (i64, i64) {
+    let mut z = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^= z >> 31;
+
+    let base = 16 + (z % (RANDOM_MODULUS as u64 - 16)) as i64;
+    let multiplier = 1 + ((z >> 32) % (RANDOM_MODULUS as u64 - 1)) as i64;
+    (base, multiplier)
+}
+
+/// Hashes each hex digit of the `id`, using the seed's factors.
+///
+/// This must match [`random_sql_key`] exactly!
+fn random_key(id: Uuid, seed: u64) -> i64 {
+    let (base, multiplier) = random_factors(seed);
+
+    let hash = id
+        .as_bytes()
+        .iter()
+        .flat_map(|byte| [byte >> 4, byte & 0xf])
+        .fold(0, |hash, digit| {
+            (hash * base + i64::from(digit)) % RANDOM_MODULUS
+        });
+
+    (hash * multiplier + 1) % RANDOM_MODULUS
+}
+
+/// The same hash as [`random_key`], but in SQL.
+///
+/// `hex()` gives the id's digits in the same order as its bytes.
+fn random_sql_key(seed: u64) -> String {
+    let (base, multiplier) = random_factors(seed);
+
+    let hash = (1..=32).fold(String::from("0"), |hash, i| {
+        format!(
+            "(({hash}) * {base} + instr('0123456789ABCDEF', substr(hex({INFO_TABLE}.id), {i}, 1)) - 1) \
+            % {RANDOM_MODULUS}"
+        )
+    });
+
+    format!("(({hash}) * {multiplier} + 1) % {RANDOM_MODULUS}")
+}
 
-        // if we're not doing a random sort, reverse the order when we're descending
-        if ty != SortType::Random {
-            if let SortOrder::Descending = order {
-                v.reverse();
+impl FinishedQuery {
+    /// Sorts the media by each key in turn, using later keys to break ties.
+    /// Any remaining ties are broken by `id`, so the order is always the same.
+    ///
+    /// Ratings have their own table, so sorting by [`SortType::Favorite`] or
+    /// [`SortType::Rating`] uses the given ones. Grab them with
+    /// [`FinishedQuery::ratings`].
+    ///
+    /// [`SortType::Relevance`] needs the search's text, so it's ignored here.
+    /// Use a `Search` instead.
+    pub fn sort(&mut self, spec: impl Into<SortSpec>, ratings: &HashMap<Uuid, Rating>) {
+        let spec = spec.into();
+        if spec.keys.iter().any(|(ty, _)| *ty == SortType::Relevance) {
+            tracing::warn!("Can't sort by relevance without a search. It'll be ignored.");
+        }
+
+        self.0.sort_by(|a, b| {
+            spec.keys
+                .iter()
+                .map(|(ty, order)| {
+                    let ordering = ty.compare(a, b, ratings);
+
+                    match order {
+                        SortOrder::Ascending => ordering,
+                        SortOrder::Descending => ordering.reverse(),
+                    }
+                })
+                .fold(Ordering::Equal, Ordering::then)
+                .then_with(|| a.id.cmp(&b.id))
+        });
+    }
+
+    /// Grabs the ratings of the media that have one, for sorting with
+    /// [`FinishedQuery::sort`].
+    pub async fn ratings(&self) -> Result<HashMap<Uuid, Rating>, RavesError> {
+        let mut conn = DATABASE.acquire().await.inspect_err(|e| {
+            tracing::error!("Failed to connect to database to sort by rating. err: {e}")
+        })?;
+
+        let mut ratings = HashMap::new();
+        // SQLite limits how many parameters a query can have
+        for media in self.0.chunks(RATINGS_PER_QUERY) {
+            let query = format!(
+                "SELECT media_id, favorite, stars FROM {RATING_TABLE} WHERE media_id IN ({})",
+                vec!["?"; media.len()].join(", ")
+            );
+            let mut query = sqlx::query_as::<_, (Uuid, bool, u8)>(&query);
+            for m in media {
+                query = query.bind(m.id);
             }
+
+            let rows = query
+                .fetch_all(&mut *conn)
+                .await
+                .inspect_err(|e| tracing::error!("Failed to get ratings for sorting! err: {e}"))
+                .map_err(DatabaseError::QueryFailed)?;
+            ratings.extend(
+                rows.into_iter()
+                    .map(|(id, favorite, stars)| (id, Rating { favorite, stars })),
+            );
         }
+
+        Ok(ratings)
     }
 }
 
+/// How many media files to get ratings for in each query.
+const RATINGS_PER_QUERY: usize = 500;
+
 #[cfg(test)]
 mod tests {
     use chrono::Utc;
     use sqlx::types::Json;
     use uuid::Uuid;
 
-    use crate::models::media::metadata::Format;
+    use crate::models::media::metadata::{Format, Framerate};
 
     use super::*;
 
-    #[tokio::test]
-    async fn sort_by_size() {
+    #[test]
+    fn sort_by_size() {
         let mut v: Vec<Media> = Vec::new();
         for len in 0..=10 {
             v.push({
                 let mut m = create_default_media();
+                m.id = Uuid::from_u128(len);
                 m.filesize = len as i64 * 1024;
                 m
             });
@@ -147,17 +465,22 @@ mod tests {
 
         let mut query = FinishedQuery(v.clone());
 
+        let mut seed = 0;
         while query.0 == v {
-            query.sort(SortType::Random, SortOrder::Ascending).await;
+            query.sort(
+                (SortType::Random { seed }, SortOrder::Ascending),
+                &HashMap::new(),
+            );
+            seed += 1;
         }
         assert_ne!(query.0, v);
 
-        query.sort(SortType::Size, SortOrder::Ascending).await;
+        query.sort((SortType::Size, SortOrder::Ascending), &HashMap::new());
         assert_eq!(query.0, v);
     }
 
-    #[tokio::test]
-    async fn sort_by_duration() {
+    #[test]
+    fn sort_by_duration() {
         let mut v: Vec<Media> = Vec::new();
 
         v.push({
@@ -169,14 +492,17 @@ mod tests {
         for len in 1..=10 {
             v.push({
                 let mut m = create_default_media();
-                *m.specific_metadata = SpecificMetadata::Video { length: len as f64 };
+                *m.specific_metadata = SpecificMetadata::Video {
+                    length: len as f64,
+                    framerate: None,
+                };
                 m.filesize = len as i64 * 1024;
                 m
             });
         }
 
         let mut query = FinishedQuery(v.clone());
-        query.sort(SortType::Duration, SortOrder::Descending).await;
+        query.sort((SortType::Duration, SortOrder::Descending), &HashMap::new());
 
         trait F {
             fn get_length(&self) -> f64;
@@ -184,7 +510,7 @@ mod tests {
 
         impl F for Media {
             fn get_length(&self) -> f64 {
-                if let SpecificMetadata::Video { length } = self.specific_metadata.clone().0 {
+                if let SpecificMetadata::Video { length, .. } = self.specific_metadata.clone().0 {
                     length
                 } else {
                     0_f64
@@ -199,7 +525,7 @@ mod tests {
         );
 
         // let's do another one, but ascending!
-        query.sort(SortType::Duration, SortOrder::Ascending).await;
+        query.sort((SortType::Duration, SortOrder::Ascending), &HashMap::new());
 
         assert_eq!(
             query.0.iter().map(|m| m.get_length()).collect::<Vec<_>>(),
@@ -208,6 +534,144 @@ mod tests {
         );
     }
 
+    #[test]
+    fn sort_by_several_keys() {
+        let media = |id: u128, mime: &str, filesize: i64| {
+            let mut m = create_default_media();
+            m.id = Uuid::from_u128(id);
+            m.format = Json(Format::new_from_mime(mime).unwrap());
+            m.filesize = filesize;
+            m
+        };
+
+        let mut animated = media(6, "image/gif", 10);
+        *animated.specific_metadata = SpecificMetadata::AnimatedImage {
+            frame_count: 10,
+            framerate: Framerate::new(10_u64, 1_u64),
+        };
+
+        let mut video = media(5, "video/mp4", 5);
+        *video.specific_metadata = SpecificMetadata::Video {
+            length: 1.0,
+            framerate: None,
+        };
+
+        let v = vec![
+            media(3, "image/jpeg", 20),
+            media(1, "image/jpeg", 10),
+            media(2, "image/jpeg", 10),
+            media(4, "image/png", 30),
+            video,
+            animated,
+        ];
+
+        // types go up, then sizes go down, then ids go up
+        let mut query = FinishedQuery(v.clone());
+        query.sort(
+            SortSpec::new(SortType::Type, SortOrder::Ascending)
+                .then(SortType::Size, SortOrder::Descending),
+            &HashMap::new(),
+        );
+        assert_eq!(
+            query.0.iter().map(|m| m.id.as_u128()).collect::<Vec<_>>(),
+            vec![6, 3, 1, 2, 4, 5]
+        );
+
+        // animated images don't have a length, so they tie with photos
+        query.sort((SortType::Duration, SortOrder::Ascending), &HashMap::new());
+        assert_eq!(
+            query.0.iter().map(|m| m.id.as_u128()).collect::<Vec<_>>(),
+            vec![1, 2, 3, 4, 6, 5]
+        );
+    }
+
+    #[test]
+    fn random_sorts_are_seeded() {
+        let v = (0..50)
+            .map(|i| {
+                let mut m = create_default_media();
+                m.id = Uuid::from_u128(i);
+                m
+            })
+            .collect::<Vec<_>>();
+
+        fn shuffled(v: &[Media], seed: u64) -> Vec<Media> {
+            let mut query = FinishedQuery(v.to_vec());
+            query.sort(
+                (SortType::Random { seed }, SortOrder::Ascending),
+                &HashMap::new(),
+            );
+            query.into_media()
+        }
+
+        assert_eq!(shuffled(&v, 7), shuffled(&v, 7));
+        assert_ne!(shuffled(&v, 7), shuffled(&v, 8));
+        assert_ne!(shuffled(&v, 7), v);
+    }
+
+    #[test]
+    fn natural_order() {
+        let mut names = vec![
+            "IMG_10.jpg",
+            "img_2.jpg",
+            "IMG_2.jpg",
+            "IMG_02.jpg",
+            "b.png",
+            "A.png",
+            "IMG_1.jpg",
+            "ÉTÉ.jpg",
+        ];
+        names.sort_by(|a, b| natural_cmp(a, b));
+
+        assert_eq!(
+            names,
+            vec![
+                "A.png",
+                "b.png",
+                "IMG_1.jpg",
+                "IMG_02.jpg",
+                "IMG_2.jpg",
+                "img_2.jpg",
+                "IMG_10.jpg",
+                "ÉTÉ.jpg"
+            ]
+        );
+        assert_eq!(natural_cmp("été", "ÉTÉ"), "été".cmp("ÉTÉ"));
+    }
+
+    #[test]
+    fn sort_by_shape() {
+        let media = |id: u128, width_px: u32, height_px: u32| {
+            let mut m = create_default_media();
+            m.id = Uuid::from_u128(id);
+            m.width_px = width_px;
+            m.height_px = height_px;
+            m
+        };
+        let mut query = FinishedQuery(vec![
+            media(1, 4000, 100),
+            media(2, 2000, 2000),
+            media(3, 1080, 1920),
+            media(4, 32, 18),
+            media(5, 0, 0),
+        ]);
+        let ids =
+            |query: &FinishedQuery| query.0.iter().map(|m| m.id.as_u128()).collect::<Vec<_>>();
+
+        // the panorama is the smallest
+        query.sort(
+            (SortType::Resolution, SortOrder::Descending),
+            &HashMap::new(),
+        );
+        assert_eq!(ids(&query), vec![2, 3, 1, 4, 5]);
+
+        query.sort(
+            (SortType::AspectRatio, SortOrder::Ascending),
+            &HashMap::new(),
+        );
+        assert_eq!(ids(&query), vec![5, 3, 2, 4, 1]);
+    }
+
     fn create_default_media() -> Media {
         Media {
             id: Uuid::nil(),
@@ -222,6 +686,7 @@ mod tests {
             specific_metadata: Json(SpecificMetadata::Image {}),
             other_metadata: None,
             tags: Json(vec![]),
+            compression: None,
         }
     }
 }
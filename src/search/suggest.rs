@@ -0,0 +1,439 @@
+//! Suggests completions for a search while it's being typed.
+//!
+//! Suggestions come from the modifier names themselves, plus values that
+//! actually exist in the library: tags, albums, camera models, formats, and
+//! years. Each value knows how many media it matches.
+
+use std::cmp::Reverse;
+
+use jiff::Zoned;
+use sqlx::SqliteConnection;
+
+use crate::{
+    database::{DATABASE, INFO_TABLE, TAGS_TABLE, TAG_SECTIONS_TABLE},
+    error::{DatabaseError, RavesError},
+};
+
+use super::{
+    groups::{local_creation_date, local_date},
+    parse::{parse, Span, MODIFIERS},
+    query::{escape_like, tagged, Clause, CAMERA_MODEL, CAPTURE_DATE, PARENT_FOLDER},
+};
+
+/// What kind of thing a suggestion completes.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SuggestionKind {
+    /// A modifier name, like `tag:`.
+    Modifier,
+    /// One of a modifier's fixed values, like `kind:video`.
+    Value,
+    Tag,
+    Album,
+    Camera,
+    Format,
+    Year,
+}
+
+/// A completion for the term under the cursor.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Suggestion {
+    pub kind: SuggestionKind,
+    /// The text to put in the query, like `tag:"new york"`.
+    pub text: String,
+    /// The part of the query that `text` replaces.
+    pub replaces: Span,
+    /// Extra info to show alongside it, like a tag's section.
+    pub detail: Option<String>,
+    /// How many media this matches.
+    ///
+    /// Modifier names don't match anything on their own, so they don't have
+    /// a count.
+    pub count: Option<u64>,
+}
+
+impl Suggestion {
+    /// Puts this suggestion into the query it came from.
+    ///
+    /// Returns the new query and where the cursor should go: right after the
+    /// suggestion.
+    pub fn apply(&self, query: &str) -> (String, usize) {
+        let Span { start, end } = self.replaces;
+        let applied = format!("{}{}{}", &query[..start], self.text, &query[end..]);
+        (applied, start + self.text.len())
+    }
+}
+
+/// Suggests up to `limit` completions for the term at `cursor`, a byte offset
+/// into `query`.
+///
+/// Modifier names come first, followed by values with the most matches.
+#[tracing::instrument]
+pub async fn suggest(
+    query: &str,
+    cursor: usize,
+    limit: u32,
+) -> Result<Vec<Suggestion>, RavesError> {
+    let term = Term::at(query, cursor);
+
+    let mut conn = DATABASE.acquire().await.inspect_err(|e| {
+        tracing::error!("Failed to connect to database for suggestions. err: {e}")
+    })?;
+
+    // years are in local time, like `during:` searches
+    let now = Zoned::now();
+
+    let mut suggestions = Vec::new();
+    match term.key.as_deref() {
+        // without a key, it could be anything! suggest modifiers and some
+        // common values
+        None => {
+            suggestions.extend(
+                MODIFIERS
+                    .iter()
+                    .filter(|m| starts_with(m, term.value))
+                    .map(|m| {
+                        term.suggestion(SuggestionKind::Modifier, format!("{m}:"), None, None)
+                    }),
+            );
+
+            for (key, source) in [
+                ("tag", Source::Tag),
+                ("album", Source::Album),
+                ("camera", Source::Camera),
+            ] {
+                suggestions.extend(source.suggest(&mut conn, &term, key, limit).await?);
+            }
+
+            if !term.value.is_empty() && term.value.bytes().all(|b| b.is_ascii_digit()) {
+                let years = Source::Year(local_creation_date("%Y", &now).sql);
+                suggestions.extend(years.suggest(&mut conn, &term, "during", limit).await?);
+            }
+        }
+
+        Some(key) => {
+            let source = match key {
+                "tag" | "person" => Source::Tag,
+                "album" | "folder" => Source::Album,
+                "camera" | "model" => Source::Camera,
+                "format" | "mime" => Source::Format,
+                "before" | "after" | "during" | "on" | "created" => {
+                    Source::Year(local_creation_date("%Y", &now).sql)
+                }
+                "modified" => Source::Year(
+                    local_date(&format!("{INFO_TABLE}.modification_date"), "%Y", &now).sql,
+                ),
+                "seen" => Source::Year(
+                    local_date(&format!("{INFO_TABLE}.first_seen_date"), "%Y", &now).sql,
+                ),
+                // capture dates are already local
+                "taken" | "captured" => Source::Year(format!("substr({CAPTURE_DATE}, 1, 4)")),
+                "kind" | "type" => Source::Fixed(&["image", "video"]),
+                "orientation" => Source::Fixed(&["portrait", "landscape", "square"]),
+                "compression" => Source::Fixed(&["lossless", "lossy"]),
+                "rating" | "stars" => Source::Fixed(&["5", "4", "3", "2", "1"]),
+                "is" => Source::Fixed(&["favorite", "untagged", "undated"]),
+                _ => Source::Fixed(&[]),
+            };
+
+            suggestions.extend(source.suggest(&mut conn, &term, key, limit).await?);
+        }
+    }
+
+    // note: this sort is stable, so modifiers stay in their usual order
+    suggestions.sort_by_key(|s| (s.kind != SuggestionKind::Modifier, Reverse(s.count)));
+    suggestions.truncate(limit as usize);
+    Ok(suggestions)
+}
+
+/// The term being typed, and what's been typed of it so far.
+#[derive(Clone, Debug, PartialEq)]
+struct Term<'q> {
+    /// All of the term, which suggestions replace. This doesn't include a
+    /// leading `-`, so negations stick around.
+    span: Span,
+    /// The modifier name, if there is one.
+    key: Option<String>,
+    /// A comparison between the key and the value, like `>=`.
+    op: &'q str,
+    /// The value so far, without quotes.
+    value: &'q str,
+}
+
+impl<'q> Term<'q> {
+    /// Finds the term that the cursor is in.
+    fn at(query: &'q str, cursor: usize) -> Self {
+        let mut cursor = cursor.min(query.len());
+        while !query.is_char_boundary(cursor) {
+            cursor -= 1;
+        }
+
+        let splits = |c: char| c.is_whitespace() || c == '(' || c == ')';
+
+        // terms start after a space or paren, unless it's quoted
+        let mut start = 0;
+        let mut quoted = false;
+        for (i, c) in query[..cursor].char_indices() {
+            if c == '"' {
+                quoted = !quoted;
+            } else if !quoted && splits(c) {
+                start = i + c.len_utf8();
+            }
+        }
+        if query[start..cursor].starts_with('-') {
+            start += 1;
+        }
+
+        let end = query[cursor..]
+            .find(splits)
+            .map_or(query.len(), |i| cursor + i);
+        let typed = &query[start..cursor];
+
+        // keys look the same as they do to the parser
+        let (key, rest) = match typed.split_once(':') {
+            Some((key, rest))
+                if key.starts_with(|c: char| c.is_ascii_alphabetic())
+                    && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') =>
+            {
+                (Some(key.to_ascii_lowercase()), rest)
+            }
+            _ => (None, typed),
+        };
+
+        let value = rest.trim_start_matches(['<', '>', '=']);
+        Self {
+            span: Span::new(start, end),
+            key,
+            op: &rest[..rest.len() - value.len()],
+            value: value.trim_matches('"'),
+        }
+    }
+
+    fn suggestion(
+        &self,
+        kind: SuggestionKind,
+        text: String,
+        detail: Option<String>,
+        count: Option<u64>,
+    ) -> Suggestion {
+        Suggestion {
+            kind,
+            text,
+            replaces: self.span,
+            detail,
+            count,
+        }
+    }
+
+    /// The text for a `key:value` pair, keeping the comparison that was typed.
+    fn text(&self, key: &str, value: &str) -> String {
+        let needs_quotes = value.is_empty()
+            || value.starts_with('-')
+            || value.contains(|c: char| c.is_whitespace() || c == '(' || c == ')');
+
+        if needs_quotes {
+            format!("{key}:{}\"{value}\"", self.op)
+        } else {
+            format!("{key}:{}{value}", self.op)
+        }
+    }
+}
+
+/// Where a modifier's values come from.
+enum Source {
+    Tag,
+    Album,
+    Camera,
+    Format,
+    /// Years, from SQL for a date's local year.
+    Year(String),
+    /// A modifier with only a few possible values.
+    Fixed(&'static [&'static str]),
+}
+
+impl Source {
+    /// Finds values that start with the typed value, completing them with
+    /// the given key.
+    async fn suggest(
+        &self,
+        conn: &mut SqliteConnection,
+        term: &Term<'_>,
+        key: &str,
+        limit: u32,
+    ) -> Result<Vec<Suggestion>, RavesError> {
+        // fixed values are counted by searching for them
+        if let Source::Fixed(values) = self {
+            let mut suggestions = Vec::new();
+            for value in values.iter().filter(|v| starts_with(v, term.value)) {
+                let text = term.text(key, value);
+                let Ok(exprs) = parse(&text) else {
+                    continue;
+                };
+
+                let Clause { sql, params } = Clause::all(&exprs);
+                let query = format!("SELECT COUNT(*) FROM {INFO_TABLE} WHERE {sql}");
+                let mut q = sqlx::query_scalar::<_, i64>(&query);
+                for param in params {
+                    q = q.bind(param);
+                }
+                let count = q
+                    .fetch_one(&mut *conn)
+                    .await
+                    .inspect_err(|e| tracing::error!("Failed to count suggestion! err: {e}"))
+                    .map_err(DatabaseError::QueryFailed)?;
+
+                suggestions.push(term.suggestion(
+                    SuggestionKind::Value,
+                    text,
+                    None,
+                    Some(count as u64),
+                ));
+            }
+            return Ok(suggestions);
+        }
+
+        let (kind, value, detail, from) = self.sql();
+        let query = format!(
+            "SELECT {value} AS suggestion, {detail} AS detail, COUNT(DISTINCT {INFO_TABLE}.id) AS count \
+            FROM {from} \
+            WHERE suggestion LIKE ? ESCAPE '\\' AND suggestion != '' \
+            GROUP BY suggestion, detail \
+            ORDER BY count DESC, suggestion \
+            LIMIT ?"
+        );
+
+        let rows = sqlx::query_as::<_, (String, Option<String>, i64)>(&query)
+            .bind(format!("{}%", escape_like(term.value)))
+            .bind(limit as i64)
+            .fetch_all(&mut *conn)
+            .await
+            .inspect_err(|e| tracing::error!("Failed to find suggestions! err: {e}"))
+            .map_err(DatabaseError::QueryFailed)?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(value, detail, count)| {
+                term.suggestion(
+                    kind.clone(),
+                    term.text(key, &value),
+                    detail,
+                    Some(count as u64),
+                )
+            })
+            .collect())
+    }
+
+    /// The SQL for a value, its detail, and what table it comes from.
+    fn sql(&self) -> (SuggestionKind, String, String, String) {
+        let info = INFO_TABLE.to_string();
+
+        match self {
+            // counted like `tag:` searches, so with implied tags but not
+            // excluded ones
+            Source::Tag => (
+                SuggestionKind::Tag,
+                "t.name".into(),
+                "s.name".into(),
+                format!(
+                    "{INFO_TABLE} JOIN ({} SELECT media_id, id FROM tagged) AS mt \
+                    ON mt.media_id = {INFO_TABLE}.id \
+                    JOIN {TAGS_TABLE} AS t ON t.id = mt.id \
+                    LEFT JOIN {TAG_SECTIONS_TABLE} AS s ON s.id = t.section_id",
+                    tagged("1")
+                ),
+            ),
+            // the last folder in the path, like `Camera` in
+            // `/sdcard/DCIM/Camera/`
+            Source::Album => {
+                let folder = format!("rtrim({PARENT_FOLDER}, '/')");
+                (
+                    SuggestionKind::Album,
+                    format!(
+                        "substr({folder}, length(rtrim({folder}, replace({folder}, '/', ''))) + 1)"
+                    ),
+                    "NULL".into(),
+                    info,
+                )
+            }
+            Source::Camera => (
+                SuggestionKind::Camera,
+                CAMERA_MODEL.into(),
+                "NULL".into(),
+                info,
+            ),
+            Source::Format => (
+                SuggestionKind::Format,
+                format!("json_extract({INFO_TABLE}.format, '$.mime_type')"),
+                format!("json_extract({INFO_TABLE}.format, '$.media_kind')"),
+                info,
+            ),
+            Source::Year(year) => (
+                SuggestionKind::Year,
+                format!("CASE WHEN {year} GLOB '[0-9][0-9][0-9][0-9]' THEN {year} END"),
+                "NULL".into(),
+                info,
+            ),
+            Source::Fixed(_) => unreachable!("fixed values don't come from the database"),
+        }
+    }
+}
+
+/// Case-insensitively checks if `s` starts with `prefix`.
+fn starts_with(s: &str, prefix: &str) -> bool {
+    s.get(..prefix.len())
+        .is_some_and(|start| start.eq_ignore_ascii_case(prefix))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_the_term_under_the_cursor() {
+        let query = "kind:video -tag:ca (album:\"new yo";
+
+        // right after the key
+        let term = Term::at(query, 16);
+        assert_eq!(term.key.as_deref(), Some("tag"));
+        assert_eq!(term.value, "");
+        assert_eq!(&query[term.span.start..term.span.end], "tag:ca");
+
+        // the negation stays put
+        let term = Term::at(query, 18);
+        assert_eq!(term.value, "ca");
+        assert_eq!(term.span, Span::new(12, 18));
+
+        // quotes can have spaces
+        let term = Term::at(query, query.len());
+        assert_eq!(term.key.as_deref(), Some("album"));
+        assert_eq!(term.value, "new yo");
+
+        // no key yet
+        let term = Term::at("kind:video sun", 14);
+        assert_eq!(term.key, None);
+        assert_eq!(term.value, "sun");
+
+        // comparisons are kept
+        let term = Term::at("taken:>20", 9);
+        assert_eq!(term.op, ">");
+        assert_eq!(term.text("taken", "2023"), "taken:>2023");
+    }
+
+    #[test]
+    fn values_are_quoted_when_needed() {
+        let term = Term::at("tag:ne", 6);
+        assert_eq!(term.text("tag", "new york"), r#"tag:"new york""#);
+        assert_eq!(term.text("tag", "cat"), "tag:cat");
+    }
+
+    #[test]
+    fn suggestions_apply_to_the_query() {
+        let query = "kind:video -tag:ca sunset";
+        let suggestion =
+            Term::at(query, 18).suggestion(SuggestionKind::Tag, "tag:cat".into(), None, Some(1));
+
+        assert_eq!(
+            suggestion.apply(query),
+            ("kind:video -tag:cat sunset".into(), 19)
+        );
+    }
+}
@@ -12,16 +12,19 @@ use std::{
 
 use backdrop::{
     config::{BugReportInfo, Config, CONFIG},
-    database,
+    database::{self, INFO_TABLE, MEDIA_COLUMNS},
     error::bug_msg,
+    models::media::Media,
+    search::{modifiers::PreExecutionQuery, parse::parse},
 };
+use sqlx::SqliteConnection;
 use tracing_subscriber::{filter, layer::SubscriberExt as _, util::SubscriberInitExt as _, Layer};
 use uuid::Uuid;
 
 /// One above the last-used port in `setup`.
 ///
 /// Use this in the next created test.
-const _AVAILABLE_PORT: u16 = 6674;
+const _AVAILABLE_PORT: u16 = 6697;
 
 /// args for setup
 #[allow(dead_code, reason = "it's used in the other tests")]
@@ -78,6 +81,39 @@ pub async fn setup(args: Setup) {
     }
 }
 
+/// Runs the given text query, returning the paths of all results.
+#[allow(dead_code, reason = "it's used in the other tests")]
+pub async fn search(conn: &mut SqliteConnection, text: &str) -> Vec<String> {
+    let compiled = PreExecutionQuery::new(&parse(text).unwrap());
+
+    let mut query = sqlx::query_as::<_, Media>(&compiled.query);
+    for param in compiled.parameters {
+        query = query.bind(param);
+    }
+
+    let mut paths = query
+        .fetch_all(conn)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|m| m.path)
+        .collect::<Vec<_>>();
+    paths.sort();
+    paths
+}
+
+/// Grabs a media file from the database.
+#[allow(dead_code, reason = "it's used in the other tests")]
+pub async fn get_media(conn: &mut SqliteConnection, id: Uuid) -> Media {
+    sqlx::query_as::<_, Media>(&format!(
+        "SELECT {MEDIA_COLUMNS} FROM {INFO_TABLE} WHERE id = $1"
+    ))
+    .bind(id)
+    .fetch_one(conn)
+    .await
+    .unwrap()
+}
+
 /// Initializes the config static with testing values.
 pub async fn init_config_testing(watched_paths: &[Utf8PathBuf]) {
     if CONFIG.get().is_none() {
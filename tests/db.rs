@@ -13,8 +13,9 @@ mod tests {
     use std::{env::temp_dir, str::FromStr as _};
 
     use backdrop::{
-        database::{self, DATABASE, RAVES_DB_FILE},
+        database::{self, DATABASE, MEDIA_COLUMNS, NATURAL_COLLATION, RAVES_DB_FILE},
         models::media::{metadata::Format, Media},
+        search::sort::natural_cmp,
     };
     use camino::{Utf8Path, Utf8PathBuf};
     use sqlx::{sqlite::SqliteConnectOptions, Sqlite};
@@ -52,11 +53,12 @@ mod tests {
         let media_id = media.id; // TODO: remove media local and just use .id on it directly
 
         // check if its registered in db
-        let media_from_db = sqlx::query_as::<_, Media>("SELECT * FROM info WHERE id = $1")
-            .bind(media_id)
-            .fetch_one(&mut *conn)
-            .await
-            .expect("media should be registered in db");
+        let media_from_db =
+            sqlx::query_as::<_, Media>(&format!("SELECT {MEDIA_COLUMNS} FROM info WHERE id = $1"))
+                .bind(media_id)
+                .fetch_one(&mut *conn)
+                .await
+                .expect("media should be registered in db");
 
         // check some of the metadata
         assert_eq!(media_from_db.id, media_id, "id match");
@@ -87,7 +89,8 @@ mod tests {
                 "sqlite://{raves_db_folder}/{RAVES_DB_FILE}"
             ))
             .expect("database opts str")
-            .create_if_missing(true);
+            .create_if_missing(true)
+            .collation(NATURAL_COLLATION, natural_cmp);
 
             // connect to the pool
             let pool = sqlx::Pool::<Sqlite>::connect_lazy_with(options);
@@ -124,4 +127,87 @@ mod tests {
             set.join_all().await;
         }
     }
+
+    /// Media tags move from `info.tags` into their own table without losing
+    /// anything.
+    #[tokio::test]
+    async fn media_tags_migration() {
+        let folder = Utf8PathBuf::try_from(temp_dir())
+            .unwrap()
+            .join(Uuid::new_v4().to_string());
+        tokio::fs::create_dir_all(&folder).await.unwrap();
+        let options = SqliteConnectOptions::from_str(&format!("sqlite://{folder}/{RAVES_DB_FILE}"))
+            .unwrap()
+            .create_if_missing(true)
+            .collation(NATURAL_COLLATION, natural_cmp);
+        let pool = sqlx::Pool::<Sqlite>::connect_lazy_with(options);
+
+        // make a database from before `media_tags`...
+        let mut old = sqlx::migrate!("./migrations");
+        old.migrations = old
+            .migrations
+            .iter()
+            .filter(|m| m.version < 13)
+            .cloned()
+            .collect::<Vec<_>>()
+            .into();
+        old.run(&pool).await.expect("old migrations");
+
+        // ...with a media file whose tags aren't all in the catalog yet
+        let id = Uuid::new_v4();
+        let tags = r#"[
+            {"name":"zebra","uuid":"z","tag_section":{"name":"animals"},"implies":[]},
+            {"name":"beach","uuid":"b","tag_section":null,"implies":[]}
+        ]"#;
+        sqlx::query(
+            "INSERT INTO info \
+            (id, path, filesize, format, creation_date, modification_date, first_seen_date, width_px, height_px, specific_metadata, other_metadata, tags) \
+            VALUES ($1, '/sdcard/a.jpg', 1, '{}', NULL, NULL, '2024-01-01', 1, 1, '{}', NULL, $2)",
+        )
+        .bind(id)
+        .bind(tags)
+        .execute(&pool)
+        .await
+        .unwrap();
+        sqlx::query("INSERT INTO tags (id, name) VALUES ('b', 'beach')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO imported_tags (media_id, tag_id) VALUES ($1, 'b')")
+            .bind(id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        sqlx::migrate!("./migrations")
+            .run(&pool)
+            .await
+            .expect("new migrations");
+
+        // the tags are in order, and remember where they came from
+        let rows = sqlx::query_as::<_, (String, String)>(
+            "SELECT tag_id, source FROM media_tags WHERE media_id = $1 ORDER BY rowid",
+        )
+        .bind(id)
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+        assert_eq!(
+            rows,
+            [("z".into(), "user".into()), ("b".into(), "file".into())]
+        );
+
+        // and media read the same as before
+        let tags = sqlx::query_scalar::<_, String>(&format!(
+            "SELECT tags FROM (SELECT {MEDIA_COLUMNS} FROM info WHERE id = $1)"
+        ))
+        .bind(id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(
+            tags,
+            r#"[{"name":"zebra","uuid":"z","tag_section":{"name":"animals"},"implies":[]},{"name":"beach","uuid":"b","tag_section":null,"implies":[]}]"#
+        );
+    }
 }
@@ -7,7 +7,7 @@ mod tests {
     use std::{env::temp_dir, time::Duration};
 
     use backdrop::{
-        database::{DATABASE, INFO_TABLE},
+        database::{DATABASE, INFO_TABLE, MEDIA_COLUMNS},
         models::media::{metadata::Format, Media},
         watch::Watch,
     };
@@ -72,10 +72,11 @@ mod tests {
 
         // wait... then check if we got metadata!
         tokio::time::sleep(Duration::from_secs(5)).await;
-        let media = sqlx::query_as::<_, Media>(&format!("SELECT * FROM {INFO_TABLE}"))
-            .fetch_one(&mut *conn)
-            .await
-            .expect("should find media after adding it");
+        let media =
+            sqlx::query_as::<_, Media>(&format!("SELECT {MEDIA_COLUMNS} FROM {INFO_TABLE}"))
+                .fetch_one(&mut *conn)
+                .await
+                .expect("should find media after adding it");
 
         assert!(
             media.path.contains("fear.avif"),
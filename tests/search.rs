@@ -0,0 +1,1191 @@
+//! Tests searching the database.
+//!
+//! These insert some fake media into a fresh database, then check that
+//! searches find the right files.
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use backdrop::{
+        database::{
+            DATABASE, INFO_TABLE, MEDIA_COLUMNS, MEDIA_TAGS_TABLE, RATING_TABLE, TAGS_TABLE,
+        },
+        error::RavesError,
+        models::{
+            media::metadata::{Compression, Format},
+            rating::Rating,
+            tags::Tag,
+        },
+        search::{
+            execute::Search,
+            explain::explain,
+            facets::{BuiltinFacet, Facet, FacetCounts},
+            groups::{Group, GroupBy},
+            modifiers::{BooleanModifier, Expr, PreExecutionQuery},
+            parse::parse,
+            query::Param,
+            sort::{SortOrder, SortSpec, SortType},
+            suggest::{suggest, SuggestionKind},
+        },
+    };
+    use chrono::{TimeZone as _, Utc};
+    use sqlx::{types::Json, SqliteConnection};
+    use uuid::Uuid;
+
+    use crate::common::{search, setup, Setup};
+
+    /// Searches compiled from text should find the right media.
+    #[tokio::test]
+    async fn compiled_queries_find_media() {
+        setup(Setup::new(6674)).await;
+        let mut conn = DATABASE.acquire().await.unwrap();
+        insert_library(&mut conn).await;
+
+        assert_eq!(
+            search(&mut conn, "kind:video").await,
+            vec!["/sdcard/Movies/clip.mp4"]
+        );
+        assert_eq!(
+            search(&mut conn, "orientation:portrait").await,
+            vec!["/sdcard/DCIM/Camera/IMG_0002.jpg"]
+        );
+        assert_eq!(
+            search(&mut conn, "album:Camera").await,
+            vec![
+                "/sdcard/DCIM/Camera/IMG_0001.jpg",
+                "/sdcard/DCIM/Camera/IMG_0002.jpg"
+            ]
+        );
+        assert_eq!(
+            search(&mut conn, "tag:cat OR tag:dog").await,
+            vec![
+                "/sdcard/DCIM/Camera/IMG_0001.jpg",
+                "/sdcard/Pictures/Screenshots/shot_100%.png"
+            ]
+        );
+        assert_eq!(
+            search(&mut conn, "-tag:cat is:untagged").await,
+            vec![
+                "/sdcard/DCIM/Camera/IMG_0002.jpg",
+                "/sdcard/Movies/clip.mp4"
+            ]
+        );
+        assert_eq!(
+            search(&mut conn, "format:png").await,
+            vec!["/sdcard/Pictures/Screenshots/shot_100%.png"]
+        );
+        assert_eq!(
+            search(&mut conn, "ext:mp4 OR 0002").await,
+            vec![
+                "/sdcard/DCIM/Camera/IMG_0002.jpg",
+                "/sdcard/Movies/clip.mp4"
+            ]
+        );
+        assert_eq!(
+            search(&mut conn, "before:2024-01-01T00:00Z").await,
+            vec!["/sdcard/DCIM/Camera/IMG_0001.jpg"]
+        );
+
+        assert_eq!(
+            search(&mut conn, r#"camera:"pixel 6""#).await,
+            vec!["/sdcard/DCIM/Camera/IMG_0001.jpg"]
+        );
+
+        // `%` is just text, not a wildcard
+        assert_eq!(
+            search(&mut conn, "100%").await,
+            vec!["/sdcard/Pictures/Screenshots/shot_100%.png"]
+        );
+        assert!(search(&mut conn, "1%0").await.is_empty());
+    }
+
+    /// Plain text should search file names, folders, and metadata.
+    #[tokio::test]
+    async fn full_text_search() {
+        setup(Setup::new(6676)).await;
+        let mut conn = DATABASE.acquire().await.unwrap();
+        let ids = insert_library(&mut conn).await;
+
+        // metadata values
+        assert_eq!(
+            search(&mut conn, "pixel").await,
+            vec!["/sdcard/DCIM/Camera/IMG_0001.jpg"]
+        );
+        // prefixes of folder names
+        assert_eq!(
+            search(&mut conn, "scr").await,
+            vec!["/sdcard/Pictures/Screenshots/shot_100%.png"]
+        );
+        // words can match in different places
+        assert_eq!(
+            search(&mut conn, "camera pixel").await,
+            vec!["/sdcard/DCIM/Camera/IMG_0001.jpg"]
+        );
+        assert_eq!(
+            search(&mut conn, "camera -0001").await,
+            vec!["/sdcard/DCIM/Camera/IMG_0002.jpg"]
+        );
+        // fts syntax is just text
+        assert!(search(&mut conn, r#""clip NOT mp4""#).await.is_empty());
+
+        // the index follows changes to the media
+        sqlx::query(&format!("UPDATE {INFO_TABLE} SET path = $1 WHERE id = $2"))
+            .bind("/sdcard/Movies/holiday.mp4")
+            .bind(ids[3])
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+        assert!(search(&mut conn, "clip").await.is_empty());
+        assert_eq!(
+            search(&mut conn, "holiday").await,
+            vec!["/sdcard/Movies/holiday.mp4"]
+        );
+
+        sqlx::query(&format!("DELETE FROM {INFO_TABLE} WHERE id = $1"))
+            .bind(ids[3])
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+        assert!(search(&mut conn, "holiday").await.is_empty());
+
+        // file names rank above folders
+        let ranked = Search::new(
+            parse("0002 OR camera").unwrap(),
+            (SortType::Relevance, SortOrder::Descending),
+        );
+        let page = ranked.page(None, 10).await.unwrap();
+        assert_eq!(
+            page.results
+                .media()
+                .iter()
+                .map(|m| m.id)
+                .collect::<Vec<_>>(),
+            vec![ids[1], ids[0]]
+        );
+        assert_eq!(ranked.position(ids[0]).await.unwrap(), Some(1));
+    }
+
+    /// Dates can be partial, relative, or just a time of day.
+    #[tokio::test]
+    async fn date_searches() {
+        setup(Setup::new(6677)).await;
+        let mut conn = DATABASE.acquire().await.unwrap();
+        insert_library(&mut conn).await;
+
+        assert_eq!(
+            search(&mut conn, "during:2024").await,
+            vec!["/sdcard/DCIM/Camera/IMG_0002.jpg"]
+        );
+        assert_eq!(
+            search(&mut conn, "created:<2024-06").await,
+            vec!["/sdcard/DCIM/Camera/IMG_0001.jpg"]
+        );
+        assert_eq!(
+            search(&mut conn, "created:>2025-06-14").await,
+            vec!["/sdcard/Movies/clip.mp4"]
+        );
+
+        // everything was just added
+        assert_eq!(search(&mut conn, "seen:today").await.len(), 4);
+        assert_eq!(search(&mut conn, r#"seen:"last 7 days""#).await.len(), 4);
+        assert!(search(&mut conn, "seen:<yesterday").await.is_empty());
+
+        // capture dates come from exif
+        assert_eq!(
+            search(&mut conn, "taken:2019-12").await,
+            vec!["/sdcard/DCIM/Camera/IMG_0001.jpg"]
+        );
+        assert_eq!(
+            search(&mut conn, r#"taken:"at night""#).await,
+            vec!["/sdcard/DCIM/Camera/IMG_0001.jpg"]
+        );
+        assert!(search(&mut conn, "taken:2020").await.is_empty());
+        assert!(search(&mut conn, "taken:06:00..12:00").await.is_empty());
+    }
+
+    /// Numbers can be compared with units.
+    #[tokio::test]
+    async fn numeric_searches() {
+        setup(Setup::new(6678)).await;
+        let mut conn = DATABASE.acquire().await.unwrap();
+        insert_library(&mut conn).await;
+
+        assert_eq!(
+            search(&mut conn, "size:>2KiB").await,
+            vec![
+                "/sdcard/Movies/clip.mp4",
+                "/sdcard/Pictures/Screenshots/shot_100%.png"
+            ]
+        );
+        assert_eq!(
+            search(&mut conn, "size:2KiB..3KiB").await,
+            vec![
+                "/sdcard/DCIM/Camera/IMG_0002.jpg",
+                "/sdcard/Pictures/Screenshots/shot_100%.png"
+            ]
+        );
+        assert_eq!(
+            search(&mut conn, "megapixels:>=12").await,
+            vec![
+                "/sdcard/DCIM/Camera/IMG_0001.jpg",
+                "/sdcard/DCIM/Camera/IMG_0002.jpg"
+            ]
+        );
+        assert_eq!(
+            search(&mut conn, "width:<1920").await,
+            vec!["/sdcard/Pictures/Screenshots/shot_100%.png"]
+        );
+        assert_eq!(
+            search(&mut conn, "height:1080 -kind:video").await,
+            vec!["/sdcard/Pictures/Screenshots/shot_100%.png"]
+        );
+
+        // only videos have a length
+        assert_eq!(
+            search(&mut conn, "duration:10s..2m").await,
+            vec!["/sdcard/Movies/clip.mp4"]
+        );
+        assert!(search(&mut conn, "duration:<10s").await.is_empty());
+
+        // 60000/1001 is about 59.94
+        assert_eq!(
+            search(&mut conn, "fps:>=59").await,
+            vec!["/sdcard/Movies/clip.mp4"]
+        );
+        assert!(search(&mut conn, "fps:>=60").await.is_empty());
+    }
+
+    /// Suggestions should complete the term under the cursor with stuff from
+    /// the library.
+    #[tokio::test]
+    async fn suggestions() {
+        setup(Setup::new(6679)).await;
+        let mut conn = DATABASE.acquire().await.unwrap();
+        let ids = insert_library(&mut conn).await;
+
+        let texts = |query: &str| {
+            let query = query.to_string();
+            async move {
+                suggest(&query, query.len(), 10)
+                    .await
+                    .unwrap()
+                    .into_iter()
+                    .map(|s| (s.text, s.count))
+                    .collect::<Vec<_>>()
+            }
+        };
+
+        // modifier names come first
+        let modifiers = suggest("ta", 2, 10).await.unwrap();
+        assert!(modifiers
+            .iter()
+            .all(|s| s.kind == SuggestionKind::Modifier && s.count.is_none()));
+        assert_eq!(
+            modifiers
+                .iter()
+                .map(|s| s.text.as_str())
+                .collect::<Vec<_>>(),
+            vec!["tag:", "tags:", "taken:"]
+        );
+
+        // values come from the library
+        let mut tags = texts("tag:").await;
+        tags.sort();
+        assert_eq!(
+            tags,
+            vec![("tag:Dog".into(), Some(1)), ("tag:cat".into(), Some(1))]
+        );
+
+        // implied tags are suggested, and counted like `tag:` searches
+        let pet = Tag::create("pet", None).await.unwrap();
+        for animal in ["1", "2"] {
+            Tag::set_implies(&animal.into(), core::slice::from_ref(&pet.uuid))
+                .await
+                .unwrap();
+        }
+        assert_eq!(texts("tag:pe").await, vec![("tag:pet".into(), Some(2))]);
+        Tag::exclude(&pet.uuid, &[ids[2]]).await.unwrap();
+        assert_eq!(texts("tag:pe").await, vec![("tag:pet".into(), Some(1))]);
+        assert_eq!(search(&mut conn, "tag:pet").await.len(), 1);
+
+        // years are local, so their counts match `during:` searches, even
+        // for something made right before the new year in UTC
+        sqlx::query(&format!(
+            "UPDATE {INFO_TABLE} SET creation_date = $1 WHERE id = $2"
+        ))
+        .bind(Utc.with_ymd_and_hms(2024, 12, 31, 23, 30, 0).unwrap())
+        .bind(ids[1])
+        .execute(&mut *conn)
+        .await
+        .unwrap();
+        let years = texts("during:20").await;
+        assert!(!years.is_empty());
+        for (text, count) in years {
+            assert_eq!(
+                Some(search(&mut conn, &text).await.len() as u64),
+                count,
+                "{text}"
+            );
+        }
+        assert_eq!(
+            texts("camera:p").await,
+            vec![(r#"camera:"Pixel 6""#.into(), Some(1))]
+        );
+        assert_eq!(
+            texts("taken:>20").await,
+            vec![("taken:>2019".into(), Some(1))]
+        );
+        assert_eq!(texts("format:").await[0], ("format:jpeg".into(), Some(2)));
+        assert_eq!(
+            texts("kind:").await,
+            vec![
+                ("kind:image".into(), Some(3)),
+                ("kind:video".into(), Some(1))
+            ]
+        );
+
+        // suggestions replace the whole term
+        let query = "kind:image -album:ca sunset";
+        let album = suggest(query, 20, 10).await.unwrap().remove(0);
+        assert_eq!(album.kind, SuggestionKind::Album);
+        assert_eq!(album.count, Some(2));
+        assert_eq!(
+            album.apply(query),
+            ("kind:image -album:Camera sunset".into(), 24)
+        );
+    }
+
+    /// Facets count results by group.
+    #[tokio::test]
+    async fn facet_counts() {
+        setup(Setup::new(6680)).await;
+        let mut conn = DATABASE.acquire().await.unwrap();
+        let ids = insert_library(&mut conn).await;
+
+        let everything = Search::new(vec![], (SortType::Size, SortOrder::Ascending));
+        let facets = everything
+            .facets(&[
+                &BuiltinFacet::Kind,
+                &BuiltinFacet::MimeType,
+                &BuiltinFacet::Orientation,
+                &BuiltinFacet::Camera,
+            ])
+            .await
+            .unwrap();
+
+        let counts = |facets: &[FacetCounts], i: usize| {
+            facets[i]
+                .counts
+                .iter()
+                .map(|(value, count)| (value.clone(), *count))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(facets[0].facet, "kind");
+        assert_eq!(
+            counts(&facets, 0),
+            vec![("Photo".into(), 3), ("Video".into(), 1)]
+        );
+        assert_eq!(
+            counts(&facets, 1),
+            vec![
+                ("image/jpeg".into(), 2),
+                ("image/png".into(), 1),
+                ("video/mp4".into(), 1)
+            ]
+        );
+        assert_eq!(
+            counts(&facets, 2),
+            vec![
+                ("landscape".into(), 2),
+                ("portrait".into(), 1),
+                ("square".into(), 1)
+            ]
+        );
+        assert_eq!(counts(&facets, 3), vec![("Pixel 6".into(), 1)]);
+
+        // counts follow the search
+        let images = Search::new(
+            parse("kind:image").unwrap(),
+            (SortType::Size, SortOrder::Ascending),
+        );
+        let facets = images
+            .facets(&[&BuiltinFacet::Year, &BuiltinFacet::Tag])
+            .await
+            .unwrap();
+        assert_eq!(
+            counts(&facets, 0),
+            vec![("2023".into(), 1), ("2024".into(), 1), ("2025".into(), 1)]
+        );
+        assert_eq!(
+            counts(&facets, 1),
+            vec![("cat".into(), 1), ("Dog".into(), 1)]
+        );
+
+        // years are local, like groups, so late on new year's eve goes in the
+        // same year for both
+        sqlx::query(&format!(
+            "UPDATE {INFO_TABLE} SET creation_date = $1 WHERE id = $2"
+        ))
+        .bind(Utc.with_ymd_and_hms(2024, 12, 31, 23, 30, 0).unwrap())
+        .bind(ids[1])
+        .execute(&mut *conn)
+        .await
+        .unwrap();
+        let mut years = counts(&images.facets(&[&BuiltinFacet::Year]).await.unwrap(), 0);
+        years.sort();
+        let by_date = Search::new(
+            parse("kind:image").unwrap(),
+            (SortType::DateCreated, SortOrder::Ascending),
+        );
+        let grouped = by_date
+            .groups(GroupBy::Year)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|g| (g.key.unwrap(), g.count))
+            .collect::<Vec<_>>();
+        assert_eq!(years, grouped);
+
+        // implied tags count too, just like in `tag:` searches, unless they
+        // were excluded
+        let pet = Tag::create("pet", None).await.unwrap();
+        for animal in ["1", "2"] {
+            Tag::set_implies(&animal.into(), core::slice::from_ref(&pet.uuid))
+                .await
+                .unwrap();
+        }
+        let pet_count = |facets: &[FacetCounts]| {
+            counts(facets, 0)
+                .into_iter()
+                .find(|(value, _)| value == "pet")
+                .map(|(_, count)| count)
+        };
+        let facets = images.facets(&[&BuiltinFacet::Tag]).await.unwrap();
+        assert_eq!(pet_count(&facets), Some(2));
+
+        Tag::exclude(&pet.uuid, &[ids[2]]).await.unwrap();
+        let facets = images.facets(&[&BuiltinFacet::Tag]).await.unwrap();
+        assert_eq!(pet_count(&facets), Some(1));
+        assert_eq!(
+            Search::new(
+                parse("kind:image tag:pet").unwrap(),
+                (SortType::Size, SortOrder::Ascending)
+            )
+            .page(None, 10)
+            .await
+            .unwrap()
+            .results
+            .media()
+            .len(),
+            1
+        );
+
+        // ...and anyone can add their own
+        struct Folder;
+        impl Facet for Folder {
+            fn name(&self) -> &str {
+                "folder"
+            }
+
+            fn value(&self) -> String {
+                "rtrim(info.path, replace(info.path, '/', ''))".into()
+            }
+        }
+        let facets = images.facets(&[&Folder]).await.unwrap();
+        assert_eq!(
+            counts(&facets, 0),
+            vec![
+                ("/sdcard/DCIM/Camera/".into(), 2),
+                ("/sdcard/Pictures/Screenshots/".into(), 1)
+            ]
+        );
+    }
+
+    /// Pages should cover every result exactly once, in order.
+    #[tokio::test]
+    async fn pages_through_results() {
+        setup(Setup::new(6675)).await;
+        let mut conn = DATABASE.acquire().await.unwrap();
+        let ids = insert_library(&mut conn).await;
+
+        // filesizes grow with each inserted media, so this is the reverse
+        let search = Search::new(vec![], (SortType::Size, SortOrder::Descending));
+        assert_eq!(search.count().await.unwrap(), 4);
+
+        let first = search.page(None, 3).await.unwrap();
+        assert_eq!(
+            first
+                .results
+                .media()
+                .iter()
+                .map(|m| m.id)
+                .collect::<Vec<_>>(),
+            vec![ids[3], ids[2], ids[1]]
+        );
+        let next = first.next.expect("there should be another page");
+
+        let second = search.page(Some(&next), 3).await.unwrap();
+        assert_eq!(
+            second
+                .results
+                .media()
+                .iter()
+                .map(|m| m.id)
+                .collect::<Vec<_>>(),
+            vec![ids[0]]
+        );
+        assert!(second.next.is_none(), "that was the last page");
+
+        // positions follow the sort
+        assert_eq!(search.position(ids[3]).await.unwrap(), Some(0));
+        assert_eq!(search.position(ids[0]).await.unwrap(), Some(3));
+
+        // ...and the filter
+        let images = Search::new(
+            parse("kind:image").unwrap(),
+            (SortType::DateCreated, SortOrder::Ascending),
+        );
+        assert_eq!(images.count().await.unwrap(), 3);
+        assert_eq!(images.position(ids[2]).await.unwrap(), Some(2));
+        assert_eq!(images.position(ids[3]).await.unwrap(), None, "it's a video");
+    }
+
+    /// Sorts can have several keys, each going its own way.
+    #[tokio::test]
+    async fn sorts_by_several_keys() {
+        setup(Setup::new(6681)).await;
+        let mut conn = DATABASE.acquire().await.unwrap();
+        let ids = insert_library(&mut conn).await;
+
+        // jpegs come first, biggest first
+        let search = Search::new(
+            vec![],
+            SortSpec::new(SortType::Type, SortOrder::Ascending)
+                .then(SortType::Size, SortOrder::Descending),
+        );
+        let expected = vec![ids[1], ids[0], ids[2], ids[3]];
+
+        let mut paged = Vec::new();
+        let mut cursor = None;
+        loop {
+            let page = search.page(cursor.as_ref(), 1).await.unwrap();
+            paged.extend(page.results.media().iter().map(|m| m.id));
+            match page.next {
+                Some(next) => cursor = Some(next),
+                None => break,
+            }
+        }
+        assert_eq!(paged, expected);
+        assert_eq!(search.position(ids[2]).await.unwrap(), Some(2));
+
+        // sorting in memory gives the same order
+        let mut everything = Search::new(vec![], (SortType::Size, SortOrder::Ascending))
+            .page(None, 10)
+            .await
+            .unwrap()
+            .results;
+        let ratings = everything.ratings().await.unwrap();
+        everything.sort(search.sort.clone(), &ratings);
+        assert_eq!(
+            everything.media().iter().map(|m| m.id).collect::<Vec<_>>(),
+            expected
+        );
+
+        // the video is the only thing with a length
+        let search = Search::new(
+            vec![],
+            SortSpec::new(SortType::Duration, SortOrder::Descending)
+                .then(SortType::DateCreated, SortOrder::Ascending),
+        );
+        let first = search.page(None, 2).await.unwrap();
+        assert_eq!(
+            first
+                .results
+                .media()
+                .iter()
+                .map(|m| m.id)
+                .collect::<Vec<_>>(),
+            vec![ids[3], ids[0]]
+        );
+        let second = search.page(first.next.as_ref(), 2).await.unwrap();
+        assert_eq!(
+            second
+                .results
+                .media()
+                .iter()
+                .map(|m| m.id)
+                .collect::<Vec<_>>(),
+            vec![ids[1], ids[2]]
+        );
+    }
+
+    /// Random orders should be the same on every page, and in memory.
+    #[tokio::test]
+    async fn random_order_is_seeded() {
+        setup(Setup::new(6682)).await;
+        let mut conn = DATABASE.acquire().await.unwrap();
+        insert_library(&mut conn).await;
+
+        for seed in [0, 1, 42, u64::MAX] {
+            let search = Search::new(vec![], (SortType::Random { seed }, SortOrder::Ascending));
+
+            let mut paged = Vec::new();
+            let mut cursor = None;
+            loop {
+                let page = search.page(cursor.as_ref(), 1).await.unwrap();
+                paged.extend(page.results.media().iter().map(|m| m.id));
+                match page.next {
+                    Some(next) => cursor = Some(next),
+                    None => break,
+                }
+            }
+
+            let mut everything = search.page(None, 10).await.unwrap().results;
+            assert_eq!(
+                everything.media().iter().map(|m| m.id).collect::<Vec<_>>(),
+                paged
+            );
+
+            // scramble it, then sort it back
+            let ratings = everything.ratings().await.unwrap();
+            everything.sort((SortType::Size, SortOrder::Descending), &ratings);
+            everything.sort(search.sort.clone(), &ratings);
+            assert_eq!(
+                everything.media().iter().map(|m| m.id).collect::<Vec<_>>(),
+                paged,
+                "seed {seed}"
+            );
+            assert_eq!(search.position(paged[2]).await.unwrap(), Some(2));
+        }
+    }
+
+    /// The database and memory should agree on every sort.
+    #[tokio::test]
+    async fn sorts_match_in_memory() {
+        setup(Setup::new(6683)).await;
+        let mut conn = DATABASE.acquire().await.unwrap();
+        let ids = insert_library(&mut conn).await;
+
+        let sorted = |ty: SortType, order: SortOrder| async move {
+            Search::new(vec![], (ty, order))
+                .page(None, 10)
+                .await
+                .unwrap()
+                .results
+                .media()
+                .iter()
+                .map(|m| m.id)
+                .collect::<Vec<_>>()
+        };
+
+        // file names sort naturally
+        assert_eq!(
+            sorted(SortType::Name, SortOrder::Ascending).await,
+            vec![ids[3], ids[0], ids[1], ids[2]]
+        );
+        // tall, square, then wide
+        assert_eq!(
+            sorted(SortType::AspectRatio, SortOrder::Ascending).await,
+            vec![ids[1], ids[2], ids[0], ids[3]]
+        );
+        // only one photo has a camera
+        assert_eq!(
+            sorted(SortType::Camera, SortOrder::Descending).await[0],
+            ids[0]
+        );
+        // ...and only one video has a bitrate
+        assert_eq!(
+            sorted(SortType::Bitrate, SortOrder::Descending).await[0],
+            ids[3]
+        );
+
+        // ratings are in their own table, but memory still sees them
+        Rating::set_stars(&[ids[1], ids[3]], 4).await.unwrap();
+        Rating::set_stars(&[ids[2]], 2).await.unwrap();
+        Rating::set_favorite(&[ids[2]], true).await.unwrap();
+
+        for ty in [
+            SortType::DateFirstSeen,
+            SortType::DateModified,
+            SortType::DateCreated,
+            SortType::TagCount,
+            SortType::Type,
+            SortType::Size,
+            SortType::Resolution,
+            SortType::Duration,
+            SortType::Name,
+            SortType::AspectRatio,
+            SortType::Bitrate,
+            SortType::Camera,
+            SortType::Favorite,
+            SortType::Rating,
+        ] {
+            for order in [SortOrder::Ascending, SortOrder::Descending] {
+                let in_db = sorted(ty.clone(), order.clone()).await;
+
+                let mut in_memory = Search::new(vec![], (SortType::random(), SortOrder::Ascending))
+                    .page(None, 10)
+                    .await
+                    .unwrap()
+                    .results;
+                let ratings = in_memory.ratings().await.unwrap();
+                in_memory.sort((ty.clone(), order.clone()), &ratings);
+
+                assert_eq!(
+                    in_memory.media().iter().map(|m| m.id).collect::<Vec<_>>(),
+                    in_db,
+                    "{ty:?} {order:?}"
+                );
+            }
+        }
+
+        // relevance needs the search's text, so memory ignores it
+        let mut results = Search::new(vec![], (SortType::Size, SortOrder::Descending))
+            .page(None, 10)
+            .await
+            .unwrap()
+            .results;
+        results.sort(
+            (SortType::Relevance, SortOrder::Descending),
+            &HashMap::new(),
+        );
+        let mut by_id = ids.clone();
+        by_id.sort();
+        assert_eq!(
+            results.media().iter().map(|m| m.id).collect::<Vec<_>>(),
+            by_id
+        );
+    }
+
+    /// Groups are runs of sorted results with the same key.
+    #[tokio::test]
+    async fn groups_sorted_results() {
+        setup(Setup::new(6684)).await;
+        let mut conn = DATABASE.acquire().await.unwrap();
+        let ids = insert_library(&mut conn).await;
+
+        let group = |key: Option<&str>, count, offset| Group {
+            key: key.map(String::from),
+            count,
+            offset,
+        };
+
+        let by_date = Search::new(vec![], (SortType::DateCreated, SortOrder::Descending));
+        assert_eq!(
+            by_date.groups(GroupBy::Year).await.unwrap(),
+            vec![
+                group(Some("2026"), 1, 0),
+                group(Some("2025"), 1, 1),
+                group(Some("2024"), 1, 2),
+                group(Some("2023"), 1, 3),
+            ]
+        );
+        assert_eq!(
+            by_date.groups(GroupBy::Day).await.unwrap()[0],
+            group(Some("2026-06-14"), 1, 0)
+        );
+
+        // groups follow the filter, too
+        let by_name = Search::new(
+            parse("kind:image").unwrap(),
+            (SortType::Name, SortOrder::Ascending),
+        );
+        assert_eq!(
+            by_name.groups(GroupBy::Folder).await.unwrap(),
+            vec![
+                group(Some("/sdcard/DCIM/Camera/"), 2, 0),
+                group(Some("/sdcard/Pictures/Screenshots/"), 1, 2),
+            ]
+        );
+
+        // when the sort doesn't match, keys can come back
+        let by_shape = Search::new(vec![], (SortType::AspectRatio, SortOrder::Ascending));
+        assert_eq!(
+            by_shape.groups(GroupBy::Folder).await.unwrap(),
+            vec![
+                group(Some("/sdcard/DCIM/Camera/"), 1, 0),
+                group(Some("/sdcard/Pictures/Screenshots/"), 1, 1),
+                group(Some("/sdcard/DCIM/Camera/"), 1, 2),
+                group(Some("/sdcard/Movies/"), 1, 3),
+            ]
+        );
+
+        // only the first photo has GPS tags
+        sqlx::query(&format!(
+            "UPDATE {INFO_TABLE} SET other_metadata = json_set(other_metadata, \
+            '$.GPSLatitude', json_object('user_facing_name', 'GPSLatitude', 'value', '48 deg 51 min 29.4 sec'), \
+            '$.GPSLatitudeRef', json_object('user_facing_name', 'GPSLatitudeRef', 'value', '\"N\"'), \
+            '$.GPSLongitude', json_object('user_facing_name', 'GPSLongitude', 'value', '2 deg 17 min 40.2 sec'), \
+            '$.GPSLongitudeRef', json_object('user_facing_name', 'GPSLongitudeRef', 'value', '\"W\"')) \
+            WHERE id = $1"
+        ))
+        .bind(ids[0])
+        .execute(&mut *conn)
+        .await
+        .unwrap();
+
+        let by_size = Search::new(vec![], (SortType::Size, SortOrder::Ascending));
+        assert_eq!(
+            by_size.groups(GroupBy::Place).await.unwrap(),
+            vec![group(Some("48.9, -2.3"), 1, 0), group(None, 3, 1)]
+        );
+    }
+
+    /// Favorites and stars are saved apart from the media, and can be
+    /// searched.
+    #[tokio::test]
+    async fn ratings() {
+        setup(Setup::new(6685)).await;
+        let mut conn = DATABASE.acquire().await.unwrap();
+        let ids = insert_library(&mut conn).await;
+
+        assert_eq!(Rating::get(ids[0]).await.unwrap(), Rating::default());
+        assert!(search(&mut conn, "is:favorite").await.is_empty());
+
+        Rating::set(
+            ids[0],
+            Rating {
+                favorite: true,
+                stars: 4,
+            },
+        )
+        .await
+        .unwrap();
+        Rating::set_favorite(&[ids[1], ids[2]], true).await.unwrap();
+        Rating::set_stars(&[ids[2], ids[3]], 5).await.unwrap();
+        Rating::set_favorite(&[ids[1]], false).await.unwrap();
+        assert!(matches!(
+            Rating::set_stars(&[ids[1]], 6).await,
+            Err(RavesError::InvalidRating { stars: 6 })
+        ));
+
+        // setting one part keeps the other
+        assert_eq!(
+            Rating::get(ids[2]).await.unwrap(),
+            Rating {
+                favorite: true,
+                stars: 5
+            }
+        );
+        assert_eq!(
+            Rating::get(ids[1]).await.unwrap(),
+            Rating {
+                favorite: false,
+                stars: 0
+            }
+        );
+
+        // rescans don't touch ratings
+        sqlx::query(&format!(
+            "UPDATE {INFO_TABLE} SET filesize = 1 WHERE id = $1"
+        ))
+        .bind(ids[0])
+        .execute(&mut *conn)
+        .await
+        .unwrap();
+        assert_eq!(Rating::get(ids[0]).await.unwrap().stars, 4);
+
+        assert_eq!(
+            search(&mut conn, "is:favorite").await,
+            vec![
+                "/sdcard/DCIM/Camera/IMG_0001.jpg",
+                "/sdcard/Pictures/Screenshots/shot_100%.png"
+            ]
+        );
+        assert_eq!(
+            search(&mut conn, "rating:>=4 -is:fav").await,
+            vec!["/sdcard/Movies/clip.mp4"]
+        );
+        assert_eq!(
+            search(&mut conn, "stars:0").await,
+            vec!["/sdcard/DCIM/Camera/IMG_0002.jpg"]
+        );
+
+        let best = Search::new(
+            vec![],
+            SortSpec::new(SortType::Rating, SortOrder::Descending)
+                .then(SortType::Favorite, SortOrder::Descending),
+        );
+        assert_eq!(
+            best.page(None, 10)
+                .await
+                .unwrap()
+                .results
+                .media()
+                .iter()
+                .map(|m| m.id)
+                .collect::<Vec<_>>(),
+            vec![ids[2], ids[3], ids[0], ids[1]]
+        );
+
+        // media that don't exist can't be rated, and nothing else changes
+        assert!(matches!(
+            Rating::set_stars(&[ids[0], Uuid::nil()], 1).await,
+            Err(RavesError::MediaDoesntExist { .. })
+        ));
+        assert!(matches!(
+            Rating::set_favorite(&[Uuid::nil()], true).await,
+            Err(RavesError::MediaDoesntExist { .. })
+        ));
+        assert_eq!(Rating::get(ids[0]).await.unwrap().stars, 4);
+
+        // deleting media removes their ratings
+        sqlx::query(&format!("DELETE FROM {INFO_TABLE} WHERE id = $1"))
+            .bind(ids[2])
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+        let left = sqlx::query_scalar::<_, i64>(&format!(
+            "SELECT COUNT(*) FROM {RATING_TABLE} WHERE media_id = $1"
+        ))
+        .bind(ids[2])
+        .fetch_one(&mut *conn)
+        .await
+        .unwrap();
+        assert_eq!(left, 0);
+    }
+
+    /// Media knows whether it's compressed losslessly.
+    #[tokio::test]
+    async fn compression() {
+        setup(Setup::new(6686)).await;
+        let mut conn = DATABASE.acquire().await.unwrap();
+        insert_library(&mut conn).await;
+
+        assert_eq!(
+            search(&mut conn, "compression:lossless").await,
+            vec!["/sdcard/Pictures/Screenshots/shot_100%.png"]
+        );
+        assert_eq!(
+            search(&mut conn, "compression:LOSSY").await,
+            vec![
+                "/sdcard/DCIM/Camera/IMG_0001.jpg",
+                "/sdcard/DCIM/Camera/IMG_0002.jpg"
+            ]
+        );
+
+        // the video's codec is unknown, so it's neither
+        assert_eq!(
+            search(&mut conn, "-compression:lossy -compression:lossless").await,
+            vec!["/sdcard/Movies/clip.mp4"]
+        );
+        assert!(parse("compression:zip").is_err());
+    }
+
+    /// EXIF fields can be searched by name, no matter which extractor read
+    /// them.
+    #[tokio::test]
+    async fn exif_fields() {
+        setup(Setup::new(6687)).await;
+        let mut conn = DATABASE.acquire().await.unwrap();
+        let ids = insert_library(&mut conn).await;
+
+        // the first photo was read by `kamadak-exif`, which uses names...
+        sqlx::query(&format!(
+            "UPDATE {INFO_TABLE} SET other_metadata = json_set(other_metadata, \
+            '$.PhotographicSensitivity', json_object('user_facing_name', 'PhotographicSensitivity', 'value', '400'), \
+            '$.ExposureTime', json_object('user_facing_name', 'ExposureTime', 'value', '1/30'), \
+            '$.GPSLatitude', json_object('user_facing_name', 'GPSLatitude', 'value', '48 deg 51 min 29.4 sec')) \
+            WHERE id = $1"
+        ))
+        .bind(ids[0])
+        .execute(&mut *conn)
+        .await
+        .unwrap();
+
+        // ...while the second was read by `nom-exif`, which uses numbers
+        sqlx::query(&format!(
+            "UPDATE {INFO_TABLE} SET other_metadata = json_object(\
+            '272', json_object('user_facing_name', NULL, 'value', 'Pixel 7'), \
+            '34855', json_object('user_facing_name', NULL, 'value', '1600'), \
+            '33434', json_object('user_facing_name', NULL, 'value', '1/500')) \
+            WHERE id = $1"
+        ))
+        .bind(ids[1])
+        .execute(&mut *conn)
+        .await
+        .unwrap();
+
+        let first = vec!["/sdcard/DCIM/Camera/IMG_0001.jpg"];
+        let second = vec!["/sdcard/DCIM/Camera/IMG_0002.jpg"];
+
+        assert_eq!(search(&mut conn, r#"exif:Model="Pixel 6""#).await, first);
+        assert_eq!(search(&mut conn, r#"exif:model="pixel 7""#).await, second);
+        assert_eq!(search(&mut conn, "exif:ISOSpeedRatings>800").await, second);
+        assert_eq!(search(&mut conn, "exif:ISO<=400").await, first);
+        assert_eq!(search(&mut conn, "exif:ISO=1600").await, second);
+        assert_eq!(search(&mut conn, "exif:ExposureTime<1/100").await, second);
+        assert_eq!(search(&mut conn, "exif:ShutterSpeed>0.01").await, first);
+
+        // text isn't a number, so it can't be more or less than one
+        assert!(search(&mut conn, "exif:Model<10").await.is_empty());
+
+        assert_eq!(search(&mut conn, "has:exif:GPSLatitude").await, first);
+        assert_eq!(
+            search(&mut conn, "-has:exif:GPSLatitude").await,
+            vec![
+                "/sdcard/DCIM/Camera/IMG_0002.jpg",
+                "/sdcard/Movies/clip.mp4",
+                "/sdcard/Pictures/Screenshots/shot_100%.png"
+            ]
+        );
+        assert_eq!(
+            search(&mut conn, "has:exif:Model").await,
+            vec![
+                "/sdcard/DCIM/Camera/IMG_0001.jpg",
+                "/sdcard/DCIM/Camera/IMG_0002.jpg"
+            ]
+        );
+    }
+
+    /// Explaining a search shows what ran, and how.
+    #[tokio::test]
+    async fn explains_searches() {
+        setup(Setup::new(6688)).await;
+        let mut conn = DATABASE.acquire().await.unwrap();
+        insert_library(&mut conn).await;
+
+        let expr = Expr::Boolean(BooleanModifier::All(
+            parse("(kind:image (tag:cat OR (tag:dog OR tag:bird))) NOT -ext:jpg").unwrap(),
+        ));
+        let explanation = explain(&expr).await.unwrap();
+
+        // nested groups are merged and double negatives cancel
+        assert_eq!(
+            explanation.normalized,
+            Expr::Boolean(BooleanModifier::All(
+                parse("kind:image (tag:cat OR tag:dog OR tag:bird) ext:jpg").unwrap()
+            ))
+        );
+        assert_eq!(
+            explanation.sql,
+            PreExecutionQuery::new(core::slice::from_ref(&explanation.normalized)).query
+        );
+        assert_eq!(
+            explanation.params,
+            ["cat", "cat", "dog", "dog", "bird", "bird", "%.jpg"]
+                .map(|p| Param::Text(p.into()))
+                .to_vec()
+        );
+        assert_eq!(explanation.rows, 1);
+        assert!(!explanation.plan.is_empty());
+        assert!(explanation
+            .plan
+            .iter()
+            .any(|step| step.detail.contains(INFO_TABLE)));
+
+        // it's readable in logs, too
+        let text = explanation.to_string();
+        assert!(
+            text.contains(&format!("sql: SELECT {MEDIA_COLUMNS} FROM info WHERE")),
+            "{text}"
+        );
+        assert!(text.contains("\n  AND\n    Kind(Image)"), "{text}");
+        assert!(text.ends_with(&format!(
+            "1 row(s) in {:?} (+ {:?} decoding)",
+            explanation.execution, explanation.decoding
+        )));
+    }
+
+    /// Adds some fake media to the database, returning their ids.
+    async fn insert_library(conn: &mut SqliteConnection) -> Vec<Uuid> {
+        let library: [(_, _, _, &[(&str, &str)], _); 4] = [
+            (
+                "/sdcard/DCIM/Camera/IMG_0001.jpg",
+                "image/jpeg",
+                (4000, 3000),
+                &[("1", "cat")],
+                Some(
+                    r#"{"Model":{"user_facing_name":"Model","value":"\"Pixel 6\""},"DateTimeOriginal":{"user_facing_name":"DateTimeOriginal","value":"2019-12-31 23:30:00"}}"#,
+                ),
+            ),
+            (
+                "/sdcard/DCIM/Camera/IMG_0002.jpg",
+                "image/jpeg",
+                (3000, 4000),
+                &[],
+                None,
+            ),
+            (
+                "/sdcard/Pictures/Screenshots/shot_100%.png",
+                "image/png",
+                (1080, 1080),
+                &[("2", "Dog")],
+                None,
+            ),
+            (
+                "/sdcard/Movies/clip.mp4",
+                "video/mp4",
+                (1920, 1080),
+                &[],
+                None,
+            ),
+        ];
+
+        let mut ids = Vec::new();
+        for (i, (path, mime, (width_px, height_px), tags, other_metadata)) in
+            library.into_iter().enumerate()
+        {
+            let format = Format::new_from_mime(mime).unwrap();
+
+            // note: `SpecificMetadata` can't be built out here, so we write
+            // its json directly
+            let specific_metadata = if mime.starts_with("video") {
+                r#"{"Video":{"length":12.0,"framerate":{"Rational":["Plus",[60000,1001]]}}}"#
+            } else {
+                r#"{"Image":{}}"#
+            };
+
+            let id = Uuid::new_v4();
+            ids.push(id);
+
+            sqlx::query(&format!(
+                "INSERT INTO {INFO_TABLE} \
+                (id, path, filesize, format, creation_date, modification_date, first_seen_date, width_px, height_px, specific_metadata, other_metadata, compression) \
+                VALUES ($1, $2, $3, $4, $5, NULL, $6, $7, $8, $9, $10, $11)"
+            ))
+            .bind(id)
+            .bind(path)
+            .bind(1024 * (i as i64 + 1))
+            .bind(Json(format))
+            .bind(Utc.with_ymd_and_hms(2023 + i as i32, 6, 14, 12, 0, 0).unwrap())
+            .bind(Utc::now())
+            .bind(width_px)
+            .bind(height_px)
+            .bind(specific_metadata)
+            .bind(other_metadata)
+            .bind(match mime {
+                "image/png" => Some(Compression::Lossless),
+                "image/jpeg" => Some(Compression::Lossy),
+                _ => None,
+            })
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+
+            for (tag_id, name) in tags {
+                sqlx::query(&format!(
+                    "INSERT OR IGNORE INTO {TAGS_TABLE} (id, name) VALUES ($1, $2)"
+                ))
+                .bind(tag_id)
+                .bind(name)
+                .execute(&mut *conn)
+                .await
+                .unwrap();
+                sqlx::query(&format!(
+                    "INSERT INTO {MEDIA_TAGS_TABLE} (media_id, tag_id) VALUES ($1, $2)"
+                ))
+                .bind(id)
+                .bind(tag_id)
+                .execute(&mut *conn)
+                .await
+                .unwrap();
+            }
+        }
+
+        ids
+    }
+}
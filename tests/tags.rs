@@ -0,0 +1,706 @@
+//! Tests the tag catalog.
+//!
+//! These make some tags, give them to fake media, then check that edits to
+//! the catalog reach the media.
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use backdrop::{
+        database::{DATABASE, INFO_TABLE, MEDIA_TAGS_TABLE},
+        error::RavesError,
+        models::{
+            media::metadata::Format,
+            rating::Rating,
+            tag_edits::{MediaSelection, TagEdit},
+            tag_stats::{suggest_tags, tags_used_together},
+            tags::{EffectiveTag, SuppressedTag, Tag, TagOrigin, TagSection},
+            xmp::export_sidecar,
+        },
+        search::parse::parse,
+    };
+    use chrono::Utc;
+    use sqlx::{types::Json, SqliteConnection};
+    use uuid::Uuid;
+
+    use crate::common::{get_media, search, setup, Setup};
+
+    /// Tags can be made, renamed, listed, and deleted. Media with a tag
+    /// follow along.
+    #[tokio::test]
+    async fn catalog_crud() {
+        setup(Setup::new(6689)).await;
+        let mut conn = DATABASE.acquire().await.unwrap();
+
+        let animals = TagSection::create("animals").await.unwrap();
+        let cat = Tag::create("cat", Some(animals.id)).await.unwrap();
+        let beach = Tag::create(" beach ", None).await.unwrap();
+        assert_eq!(beach.name, "beach");
+        assert_eq!(cat.tag_section, Some(animals.section.clone()));
+
+        // names are unique within a section, but not across them
+        assert!(matches!(
+            Tag::create("CAT", Some(animals.id)).await,
+            Err(RavesError::TagNameTaken { .. })
+        ));
+        let other_cat = Tag::create("cat", None).await.unwrap();
+        assert!(matches!(
+            TagSection::create("Animals").await,
+            Err(RavesError::TagSectionNameTaken { .. })
+        ));
+        assert!(matches!(
+            Tag::create("  ", None).await,
+            Err(RavesError::EmptyTagName)
+        ));
+
+        let media = insert_media(&mut conn, "/sdcard/cat.jpg", &[cat.clone(), beach.clone()]).await;
+
+        // renaming keeps the uuid, and the media sees the new name
+        let kitty = Tag::rename(&cat.uuid, "kitty").await.unwrap();
+        assert_eq!(kitty.uuid, cat.uuid);
+        assert_eq!(Tag::get(&cat.uuid).await.unwrap().name, "kitty");
+        assert_eq!(tag_names(&mut conn, media).await, ["kitty", "beach"]);
+
+        // ...as long as nothing else in the section has it
+        let dog = Tag::create("dog", Some(animals.id)).await.unwrap();
+        assert!(matches!(
+            Tag::rename(&cat.uuid, "Dog").await,
+            Err(RavesError::TagNameTaken { .. })
+        ));
+        Tag::delete(&dog.uuid).await.unwrap();
+
+        // so do sections
+        TagSection::rename(animals.id, "pets").await.unwrap();
+        let tags = get_media(&mut conn, media).await.tags.0;
+        assert_eq!(
+            tags[0].tag_section,
+            Some(TagSection {
+                name: "pets".into()
+            })
+        );
+        assert_eq!(tags[1].tag_section, None);
+
+        assert_eq!(
+            Tag::list()
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|t| t.name)
+                .collect::<Vec<_>>(),
+            ["beach", "cat", "kitty"]
+        );
+
+        // sections with tags can't be deleted
+        assert!(matches!(
+            TagSection::delete(animals.id).await,
+            Err(RavesError::TagSectionNotEmpty { tags: 1, .. })
+        ));
+
+        // deleting a tag takes it off of media, too
+        Tag::delete(&cat.uuid).await.unwrap();
+        assert_eq!(tag_names(&mut conn, media).await, ["beach"]);
+        assert!(matches!(
+            Tag::get(&cat.uuid).await,
+            Err(RavesError::TagNotFound { .. })
+        ));
+
+        TagSection::delete(animals.id).await.unwrap();
+        assert!(TagSection::list().await.unwrap().is_empty());
+        assert!(Tag::get(&other_cat.uuid).await.is_ok());
+    }
+
+    /// Tags imply others, all the way down, but never in a circle.
+    #[tokio::test]
+    async fn implied_tags() {
+        setup(Setup::new(6690)).await;
+        let mut conn = DATABASE.acquire().await.unwrap();
+
+        let christmas = Tag::create("christmas", None).await.unwrap();
+        let holiday = Tag::create("holiday", None).await.unwrap();
+        let winter = Tag::create("winter", None).await.unwrap();
+        let season = Tag::create("season", None).await.unwrap();
+
+        let christmas = Tag::set_implies(
+            &christmas.uuid,
+            &[holiday.uuid.clone(), winter.uuid.clone()],
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            christmas.implies,
+            [holiday.uuid.clone(), winter.uuid.clone()]
+        );
+        Tag::set_implies(&holiday.uuid, core::slice::from_ref(&season.uuid))
+            .await
+            .unwrap();
+        Tag::set_implies(&winter.uuid, core::slice::from_ref(&season.uuid))
+            .await
+            .unwrap();
+
+        // cycles are caught, no matter how long
+        let err = Tag::set_implies(&season.uuid, core::slice::from_ref(&christmas.uuid))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, RavesError::TagImplicationCycle { .. }));
+        assert!(
+            err.to_string()
+                .contains("`season` -> `christmas` -> `holiday` -> `season`"),
+            "{err}"
+        );
+        assert!(matches!(
+            Tag::set_implies(&holiday.uuid, core::slice::from_ref(&holiday.uuid)).await,
+            Err(RavesError::TagImplicationCycle { .. })
+        ));
+        assert_eq!(
+            Tag::get(&season.uuid).await.unwrap().implies,
+            Vec::<String>::new()
+        );
+
+        let mut implied = Tag::implied(&christmas.uuid)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|t| t.name)
+            .collect::<Vec<_>>();
+        implied.sort();
+        assert_eq!(implied, ["holiday", "season", "winter"]);
+
+        // media with just "christmas" have the rest, too
+        let photo = insert_media(
+            &mut conn,
+            "/sdcard/tree.jpg",
+            core::slice::from_ref(&christmas),
+        )
+        .await;
+        insert_media(&mut conn, "/sdcard/beach.jpg", &[]).await;
+
+        for tag in ["christmas", "holiday", "SEASON"] {
+            assert_eq!(
+                search(&mut conn, &format!("tag:{tag}")).await,
+                ["/sdcard/tree.jpg"]
+            );
+        }
+        assert_eq!(
+            search(&mut conn, "-tag:winter").await,
+            ["/sdcard/beach.jpg"]
+        );
+
+        let effective = Tag::effective_tags(photo).await.unwrap();
+        let implied_by_christmas = TagOrigin::Implied {
+            by: christmas.uuid.clone(),
+        };
+        assert_eq!(
+            effective
+                .iter()
+                .map(|EffectiveTag { tag, origin }| (tag.name.as_str(), origin))
+                .collect::<Vec<_>>(),
+            [
+                ("christmas", &TagOrigin::Explicit),
+                ("holiday", &implied_by_christmas),
+                ("winter", &implied_by_christmas),
+                ("season", &implied_by_christmas),
+            ]
+        );
+
+        // deleting a tag in the middle breaks the chain
+        Tag::delete(&holiday.uuid).await.unwrap();
+        Tag::delete(&winter.uuid).await.unwrap();
+        assert!(search(&mut conn, "tag:season").await.is_empty());
+        assert_eq!(
+            Tag::get(&christmas.uuid).await.unwrap().implies,
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            get_media(&mut conn, photo).await.tags.0[0].implies,
+            Vec::<String>::new()
+        );
+    }
+
+    /// Media can opt out of implied tags, and say why they're missing.
+    #[tokio::test]
+    async fn implied_tag_exclusions() {
+        setup(Setup::new(6691)).await;
+        let mut conn = DATABASE.acquire().await.unwrap();
+
+        let christmas = Tag::create("christmas", None).await.unwrap();
+        let winter = Tag::create("winter", None).await.unwrap();
+        let cold = Tag::create("cold", None).await.unwrap();
+        let christmas = Tag::set_implies(&christmas.uuid, core::slice::from_ref(&winter.uuid))
+            .await
+            .unwrap();
+        Tag::set_implies(&winter.uuid, core::slice::from_ref(&cold.uuid))
+            .await
+            .unwrap();
+
+        // christmas in australia isn't in winter
+        let sydney = insert_media(
+            &mut conn,
+            "/sdcard/sydney.jpg",
+            core::slice::from_ref(&christmas),
+        )
+        .await;
+        insert_media(
+            &mut conn,
+            "/sdcard/ohio.jpg",
+            core::slice::from_ref(&christmas),
+        )
+        .await;
+        Tag::exclude(&winter.uuid, &[sydney]).await.unwrap();
+
+        // ...and isn't cold, since that only came from winter
+        let effective = Tag::effective_tags(sydney).await.unwrap();
+        assert_eq!(
+            effective
+                .into_iter()
+                .map(|t| t.tag.name)
+                .collect::<Vec<_>>(),
+            ["christmas"]
+        );
+        for tag in ["winter", "cold"] {
+            assert_eq!(
+                search(&mut conn, &format!("tag:{tag}")).await,
+                ["/sdcard/ohio.jpg"]
+            );
+        }
+        assert_eq!(
+            search(&mut conn, "tag:christmas").await,
+            ["/sdcard/ohio.jpg", "/sdcard/sydney.jpg"]
+        );
+
+        assert_eq!(
+            Tag::suppressed_tags(sydney).await.unwrap(),
+            [SuppressedTag {
+                tag: Tag::get(&winter.uuid).await.unwrap(),
+                by: christmas.uuid.clone(),
+            }]
+        );
+
+        // excluding a tag the media was given directly does nothing
+        Tag::exclude(&christmas.uuid, &[sydney]).await.unwrap();
+        assert_eq!(Tag::effective_tags(sydney).await.unwrap().len(), 1);
+        assert_eq!(Tag::suppressed_tags(sydney).await.unwrap().len(), 1);
+
+        assert!(matches!(
+            Tag::exclude(&"nothing".into(), &[sydney]).await,
+            Err(RavesError::TagNotFound { .. })
+        ));
+        // unknown media aren't excluded, and neither is anything else
+        assert!(matches!(
+            Tag::exclude(&cold.uuid, &[sydney, Uuid::nil()]).await,
+            Err(RavesError::MediaDoesntExist { .. })
+        ));
+
+        // unexcluding checks the same way
+        assert!(matches!(
+            Tag::unexclude(&"nothing".into(), &[sydney]).await,
+            Err(RavesError::TagNotFound { .. })
+        ));
+        assert!(matches!(
+            Tag::unexclude(&winter.uuid, &[sydney, Uuid::nil()]).await,
+            Err(RavesError::MediaDoesntExist { .. })
+        ));
+        assert_eq!(Tag::suppressed_tags(sydney).await.unwrap().len(), 1);
+
+        // undoing it brings everything back
+        Tag::unexclude(&winter.uuid, &[sydney]).await.unwrap();
+        assert_eq!(Tag::effective_tags(sydney).await.unwrap().len(), 3);
+        assert!(Tag::suppressed_tags(sydney).await.unwrap().is_empty());
+        assert_eq!(
+            search(&mut conn, "tag:cold").await,
+            ["/sdcard/ohio.jpg", "/sdcard/sydney.jpg"]
+        );
+
+        // and deleting an excluded tag forgets the exclusion
+        Tag::exclude(&cold.uuid, &[sydney]).await.unwrap();
+        Tag::delete(&cold.uuid).await.unwrap();
+        let left: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM tag_exclusions WHERE tag_id = $1")
+            .bind(&cold.uuid)
+            .fetch_one(&mut *conn)
+            .await
+            .unwrap();
+        assert_eq!(left, 0);
+    }
+
+    /// Keywords from files become tags, and importing again doesn't double
+    /// them up.
+    #[tokio::test]
+    async fn imported_keywords() {
+        setup(Setup::new(6692)).await;
+
+        let keywords = TagSection {
+            name: "keywords".into(),
+        };
+        let beach = Tag::create("beach", None).await.unwrap();
+        let media = Uuid::new_v4();
+
+        let words = ["Beach", "sunset", " beach ", "", "dog"].map(String::from);
+        let (imported, forgotten) = Tag::import_keywords(media, &words, Some(&keywords))
+            .await
+            .unwrap();
+        assert!(forgotten.is_empty());
+
+        // they're made in the section, even when a tag elsewhere has the name
+        assert_eq!(
+            imported.iter().map(|t| t.name.as_str()).collect::<Vec<_>>(),
+            ["Beach", "sunset", "dog"]
+        );
+        assert!(imported
+            .iter()
+            .all(|t| t.tag_section == Some(keywords.clone())));
+        assert!(imported.iter().all(|t| t.uuid != beach.uuid));
+
+        // importing again finds the same tags, and forgets the ones the file
+        // doesn't have anymore
+        let words = ["sunset", "BEACH"].map(String::from);
+        let (again, forgotten) = Tag::import_keywords(media, &words, Some(&keywords))
+            .await
+            .unwrap();
+        assert_eq!(again, [imported[1].clone(), imported[0].clone()]);
+        assert_eq!(forgotten, [imported[2].uuid.clone()]);
+        assert_eq!(TagSection::list().await.unwrap().len(), 1);
+        assert_eq!(Tag::list().await.unwrap().len(), 4);
+
+        // without a section, they use tags outside of any
+        let (loose, _) = Tag::import_keywords(media, &["beach".into()], None)
+            .await
+            .unwrap();
+        assert_eq!(loose, [beach]);
+    }
+
+    /// Sidecars get a media file's effective tags and rating, without losing
+    /// what other apps wrote there.
+    #[tokio::test]
+    async fn sidecar_export() {
+        setup(Setup::new(6693)).await;
+        let mut conn = DATABASE.acquire().await.unwrap();
+
+        let dir = std::env::temp_dir().join(Uuid::new_v4().to_string());
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        let path = dir.join("photo.jpg").to_string_lossy().into_owned();
+
+        let animals = TagSection::create("animals").await.unwrap();
+        let pet = Tag::create("pet", None).await.unwrap();
+        let cat = Tag::create("cat", Some(animals.id)).await.unwrap();
+        let cat = Tag::set_implies(&cat.uuid, core::slice::from_ref(&pet.uuid))
+            .await
+            .unwrap();
+        let media = insert_media(&mut conn, &path, core::slice::from_ref(&cat)).await;
+        Rating::set(
+            media,
+            Rating {
+                favorite: false,
+                stars: 3,
+            },
+        )
+        .await
+        .unwrap();
+
+        // another app already wrote something
+        tokio::fs::write(
+            format!("{path}.xmp"),
+            r#"<x:xmpmeta xmlns:x="adobe:ns:meta/">
+ <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+  <rdf:Description rdf:about="" xmlns:dc="http://purl.org/dc/elements/1.1/" xmlns:xmp="http://ns.adobe.com/xap/1.0/" xmp:Rating="5" xmp:Label="Red">
+   <dc:subject><rdf:Bag><rdf:li>vacation</rdf:li></rdf:Bag></dc:subject>
+  </rdf:Description>
+ </rdf:RDF>
+</x:xmpmeta>
+"#,
+        )
+        .await
+        .unwrap();
+
+        let sidecar = export_sidecar(media).await.unwrap();
+        assert_eq!(sidecar.as_str(), format!("{path}.xmp"));
+        let xmp = tokio::fs::read_to_string(&sidecar).await.unwrap();
+
+        for expected in [
+            "<rdf:li>vacation</rdf:li>",
+            "<rdf:li>cat</rdf:li>",
+            "<rdf:li>pet</rdf:li>",
+            "<rdf:li>animals|cat</rdf:li>",
+            "<xmp:Rating>3</xmp:Rating>",
+            r#"xmp:Label="Red""#,
+        ] {
+            assert!(xmp.contains(expected), "missing `{expected}` in:\n{xmp}");
+        }
+        assert!(!xmp.contains("xmp:Rating=\"5\""));
+
+        // excluded tags aren't exported, but exporting again keeps the old
+        // sidecar's stuff
+        Tag::exclude(&pet.uuid, &[media]).await.unwrap();
+        let old = tokio::fs::read_to_string(&sidecar).await.unwrap();
+        tokio::fs::write(&sidecar, old.replace("<rdf:li>pet</rdf:li>", ""))
+            .await
+            .unwrap();
+        export_sidecar(media).await.unwrap();
+        let xmp = tokio::fs::read_to_string(&sidecar).await.unwrap();
+        assert!(!xmp.contains("<rdf:li>pet</rdf:li>"), "{xmp}");
+        assert_eq!(xmp.matches("<rdf:li>cat</rdf:li>").count(), 1);
+        assert!(xmp.contains("<rdf:li>vacation</rdf:li>"));
+
+        tokio::fs::remove_dir_all(dir).await.unwrap();
+    }
+
+    /// Duplicate tags merge into one, and their old names still work.
+    #[tokio::test]
+    async fn merged_tags() {
+        setup(Setup::new(6694)).await;
+        let mut conn = DATABASE.acquire().await.unwrap();
+
+        let animals = TagSection::create("animals").await.unwrap();
+        let cat = Tag::create("cat", Some(animals.id)).await.unwrap();
+        let cats = Tag::create("cats", Some(animals.id)).await.unwrap();
+        let kitty = Tag::create("kitty", Some(animals.id)).await.unwrap();
+        let pet = Tag::create("pet", None).await.unwrap();
+        let lion = Tag::create("lion", None).await.unwrap();
+        Tag::set_implies(&kitty.uuid, core::slice::from_ref(&pet.uuid))
+            .await
+            .unwrap();
+        Tag::set_implies(&lion.uuid, core::slice::from_ref(&cats.uuid))
+            .await
+            .unwrap();
+
+        let both = insert_media(&mut conn, "/sdcard/both.jpg", &[cat.clone(), cats.clone()]).await;
+        let one = insert_media(&mut conn, "/sdcard/one.jpg", core::slice::from_ref(&kitty)).await;
+        let wild = insert_media(&mut conn, "/sdcard/wild.jpg", core::slice::from_ref(&lion)).await;
+        Tag::exclude(&pet.uuid, &[one]).await.unwrap();
+
+        // merges that would make a cycle change nothing
+        let wildcat = Tag::create("wildcat", None).await.unwrap();
+        Tag::set_implies(&wildcat.uuid, core::slice::from_ref(&lion.uuid))
+            .await
+            .unwrap();
+        assert!(matches!(
+            Tag::merge(core::slice::from_ref(&wildcat.uuid), &cats.uuid).await,
+            Err(RavesError::TagImplicationCycle { .. })
+        ));
+        assert!(Tag::get(&wildcat.uuid).await.is_ok());
+        Tag::delete(&wildcat.uuid).await.unwrap();
+
+        let cat = Tag::merge(&[cats.uuid.clone(), kitty.uuid.clone()], &cat.uuid)
+            .await
+            .unwrap();
+        assert_eq!(Tag::aliases(&cat.uuid).await.unwrap(), ["cats", "kitty"]);
+        assert!(Tag::get(&cats.uuid).await.is_err());
+        assert!(Tag::get(&kitty.uuid).await.is_err());
+
+        // media have the target once, and implications follow it
+        assert_eq!(tag_names(&mut conn, both).await, ["cat"]);
+        assert_eq!(tag_names(&mut conn, one).await, ["cat"]);
+        assert_eq!(cat.implies, core::slice::from_ref(&pet.uuid));
+        assert_eq!(
+            Tag::get(&lion.uuid).await.unwrap().implies,
+            core::slice::from_ref(&cat.uuid)
+        );
+        assert_eq!(
+            get_media(&mut conn, wild).await.tags.0[0].implies,
+            core::slice::from_ref(&cat.uuid)
+        );
+        assert_eq!(Tag::suppressed_tags(one).await.unwrap().len(), 1);
+
+        // old names still find it
+        for name in ["cat", "cats", "KITTY"] {
+            assert_eq!(
+                search(&mut conn, &format!("tag:{name}")).await,
+                ["/sdcard/both.jpg", "/sdcard/one.jpg", "/sdcard/wild.jpg"]
+            );
+        }
+        let section = TagSection {
+            name: "animals".into(),
+        };
+        let (imported, _) = Tag::import_keywords(Uuid::new_v4(), &["Kitty".into()], Some(&section))
+            .await
+            .unwrap();
+        assert_eq!(imported, core::slice::from_ref(&cat));
+
+        // a new tag can take an alias's name, and then the name only finds it
+        let kitty = Tag::create("Kitty", Some(animals.id)).await.unwrap();
+        assert_eq!(Tag::aliases(&cat.uuid).await.unwrap(), ["cats"]);
+        let kitten = insert_media(
+            &mut conn,
+            "/sdcard/kitten.jpg",
+            core::slice::from_ref(&kitty),
+        )
+        .await;
+        assert_eq!(search(&mut conn, "tag:kitty").await, ["/sdcard/kitten.jpg"]);
+        assert_eq!(tag_names(&mut conn, kitten).await, ["Kitty"]);
+
+        // ...and so can renamed ones
+        Tag::rename(&kitty.uuid, "cats").await.unwrap();
+        assert!(Tag::aliases(&cat.uuid).await.unwrap().is_empty());
+        assert_eq!(search(&mut conn, "tag:cats").await, ["/sdcard/kitten.jpg"]);
+    }
+
+    /// Tags can be added to or removed from lots of media at once, then
+    /// undone.
+    #[tokio::test]
+    async fn bulk_tag_edits() {
+        setup(Setup::new(6695)).await;
+        let mut conn = DATABASE.acquire().await.unwrap();
+
+        let beach = Tag::create("beach", None).await.unwrap();
+        let trip = Tag::create("trip", None).await.unwrap();
+        let a = insert_media(&mut conn, "/sdcard/a.jpg", core::slice::from_ref(&beach)).await;
+        let b = insert_media(&mut conn, "/sdcard/b.jpg", &[]).await;
+        let c = insert_media(&mut conn, "/sdcard/c.jpg", core::slice::from_ref(&trip)).await;
+
+        // only media that change are counted
+        let by_search = MediaSelection::Search(parse("tag:beach OR tags:0").unwrap());
+        let added = TagEdit::add(&by_search, &[trip.uuid.clone(), beach.uuid.clone()])
+            .await
+            .unwrap();
+        assert_eq!(added.affected, 2);
+        assert_eq!(tag_names(&mut conn, a).await, ["beach", "trip"]);
+        assert_eq!(tag_names(&mut conn, b).await, ["trip", "beach"]);
+        assert_eq!(tag_names(&mut conn, c).await, ["trip"]);
+
+        let by_ids = MediaSelection::Ids(vec![a, c]);
+        let removed = TagEdit::remove(&by_ids, core::slice::from_ref(&trip.uuid))
+            .await
+            .unwrap();
+        assert_eq!(removed.affected, 2);
+        assert_eq!(tag_names(&mut conn, a).await, ["beach"]);
+        assert_eq!(tag_names(&mut conn, c).await, Vec::<String>::new());
+
+        // undoing puts back exactly what changed
+        assert_eq!(TagEdit::undo(removed.id).await.unwrap(), 2);
+        assert_eq!(tag_names(&mut conn, a).await, ["beach", "trip"]);
+        assert_eq!(tag_names(&mut conn, c).await, ["trip"]);
+        assert_eq!(TagEdit::undo(added.id).await.unwrap(), 2);
+        assert_eq!(tag_names(&mut conn, a).await, ["beach"]);
+        assert_eq!(tag_names(&mut conn, b).await, Vec::<String>::new());
+        assert_eq!(tag_names(&mut conn, c).await, ["trip"]);
+
+        assert!(matches!(
+            TagEdit::undo(added.id).await,
+            Err(RavesError::TagEditAlreadyUndone { .. })
+        ));
+        assert!(matches!(
+            TagEdit::undo(Uuid::nil()).await,
+            Err(RavesError::TagEditNotFound { .. })
+        ));
+        assert!(matches!(
+            TagEdit::add(&by_ids, &["nope".into()]).await,
+            Err(RavesError::TagNotFound { .. })
+        ));
+
+        // the same tag twice is just added once
+        let twice = TagEdit::add(&by_ids, &[beach.uuid.clone(), beach.uuid.clone()])
+            .await
+            .unwrap();
+        assert_eq!(twice.affected, 1);
+        assert_eq!(twice.tags, core::slice::from_ref(&beach.uuid));
+        assert_eq!(tag_names(&mut conn, c).await, ["trip", "beach"]);
+        assert_eq!(TagEdit::undo(twice.id).await.unwrap(), 1);
+        assert_eq!(tag_names(&mut conn, c).await, ["trip"]);
+    }
+
+    /// Tags are suggested from the tags they're used with and from media in
+    /// the same folder.
+    #[tokio::test]
+    async fn tag_suggestions() {
+        setup(Setup::new(6696)).await;
+        let mut conn = DATABASE.acquire().await.unwrap();
+
+        let beach = Tag::create("beach", None).await.unwrap();
+        let sea = Tag::create("sea", None).await.unwrap();
+        let sunset = Tag::create("sunset", None).await.unwrap();
+        let dog = Tag::create("dog", None).await.unwrap();
+        let cat = Tag::create("cat", None).await.unwrap();
+        insert_media(
+            &mut conn,
+            "/sdcard/Trip/a.jpg",
+            &[beach.clone(), sea.clone()],
+        )
+        .await;
+        insert_media(
+            &mut conn,
+            "/sdcard/Trip/b.jpg",
+            &[beach.clone(), sea.clone(), sunset.clone()],
+        )
+        .await;
+        insert_media(&mut conn, "/sdcard/Pets/c.jpg", &[dog.clone(), cat.clone()]).await;
+        insert_media(&mut conn, "/sdcard/Pets/d.jpg", core::slice::from_ref(&dog)).await;
+        let e = insert_media(
+            &mut conn,
+            "/sdcard/Trip/e.jpg",
+            core::slice::from_ref(&beach),
+        )
+        .await;
+
+        // `sea` goes with `beach` more often than `sunset` does, and both are
+        // on the whole folder. pets never show up with either
+        let suggested = suggest_tags(e).await.unwrap();
+        let idents = suggested.iter().map(|(t, _)| t.clone()).collect::<Vec<_>>();
+        assert_eq!(idents, [sea.uuid.clone(), sunset.uuid.clone()]);
+        assert!(suggested[0].1 > suggested[1].1);
+
+        // tags it already has aren't suggested, even if they're implied
+        Tag::set_implies(&beach.uuid, core::slice::from_ref(&sea.uuid))
+            .await
+            .unwrap();
+        let suggested = suggest_tags(e).await.unwrap();
+        assert_eq!(suggested.len(), 1);
+        assert_eq!(suggested[0].0, sunset.uuid);
+
+        // `beach` and `sea` are on two media together
+        let pairs = tags_used_together(10).await.unwrap();
+        assert_eq!(pairs.len(), 4);
+        let mut names = [pairs[0].tags.0.name.clone(), pairs[0].tags.1.name.clone()];
+        names.sort();
+        assert_eq!(names, ["beach", "sea"]);
+        assert_eq!(pairs[0].together, 2);
+        assert!((pairs[0].similarity - 2.0 / 3.0).abs() < 1e-9);
+        assert!(pairs[1..].iter().all(|p| p.together == 1));
+        assert_eq!(tags_used_together(1).await.unwrap().len(), 1);
+
+        assert!(matches!(
+            suggest_tags(Uuid::nil()).await,
+            Err(RavesError::MediaDoesntExist { .. })
+        ));
+    }
+
+    /// Adds a fake photo with the given tags to the database.
+    async fn insert_media(conn: &mut SqliteConnection, path: &str, tags: &[Tag]) -> Uuid {
+        let id = Uuid::new_v4();
+
+        sqlx::query(&format!(
+            "INSERT INTO {INFO_TABLE} \
+            (id, path, filesize, format, creation_date, modification_date, first_seen_date, width_px, height_px, specific_metadata, other_metadata) \
+            VALUES ($1, $2, 1024, $3, NULL, NULL, $4, 1920, 1080, '{{\"Image\":{{}}}}', NULL)"
+        ))
+        .bind(id)
+        .bind(path)
+        .bind(Json(Format::new_from_mime("image/jpeg").unwrap()))
+        .bind(Utc::now())
+        .execute(&mut *conn)
+        .await
+        .unwrap();
+
+        for tag in tags {
+            sqlx::query(&format!(
+                "INSERT INTO {MEDIA_TAGS_TABLE} (media_id, tag_id) VALUES ($1, $2)"
+            ))
+            .bind(id)
+            .bind(&tag.uuid)
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+        }
+
+        id
+    }
+
+    /// The names of a media file's tags, in order.
+    async fn tag_names(conn: &mut SqliteConnection, id: Uuid) -> Vec<String> {
+        get_media(conn, id)
+            .await
+            .tags
+            .0
+            .into_iter()
+            .map(|t| t.name)
+            .collect()
+    }
+}